@@ -0,0 +1,30 @@
+# abstract types only group other types, they can't be instantiated
+
+abstract type Shape end
+abstract type Polygon <: Shape end
+
+struct Square <: Polygon
+    side::Float64
+end
+
+struct Circle <: Shape
+    r::Float64
+end
+
+# redefining a type the same way is fine
+abstract type Shape end
+
+function area(s::Square)::Float64
+    s.side * s.side
+end
+
+function area(c::Circle)::Float64
+    3.0 * c.r * c.r
+end
+
+function total(a::Shape, b::Shape)::Float64
+    area(a) + area(b)
+end
+
+println(supertype(Polygon), " ", supertype(Square), " ", typeof(Shape))
+println(total(Square(2.0), Circle(1.0)))
@@ -0,0 +1,21 @@
+function second(a::Vector{Float64})::Float64
+    a[2]
+end
+
+function main()
+    a = [1, 2, 3]
+    printf(a[2])
+
+    b = Vector{Float64}(undef, 3)
+    b[1] = 1.5
+    b[2] = 2
+    printf(b[1] + b[2])
+    printf(second(b))
+
+    push!(a, 10)
+    printf(length(a))
+    printf(a[4])
+
+    # throws a BoundsError
+    # printf(a[5])
+end
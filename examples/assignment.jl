@@ -0,0 +1,41 @@
+mutable struct Point
+    x::Int64
+    y::Float64
+end
+
+function bump(v::Vector{Int64})::Int64
+    v[2] += 10
+end
+
+function swap(a::Int64, b::Int64)::Int64
+    a, b = b, a
+    a - b
+end
+
+x = 1
+x += 2
+x *= 3
+x -= 1
+a = b = 5
+print(x, " ", a, " ", b, "|")
+c, d = 7, 8
+c, d = d, c
+print(c, " ", d, "|")
+v = [1, 2, 3]
+v[1] += 1
+print(bump(v), " ", v, "|")
+e, f = v
+print(e, " ", f, "|")
+p = Point(1, 2.5)
+p.x = 2
+p.y /= 2
+print(p.x, " ", p.y, "|")
+print(swap(1, 4), "|")
+function sum3(n::Int64)::Int64
+    s = 0
+    s += n
+    s += n
+    s += n
+    s
+end
+print(sum3(2), "|")
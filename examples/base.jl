@@ -42,8 +42,8 @@ primitive type UInt128 <: Unsigned 128 end
 #   x + y
 # end
 
-# a = 1
-# printf(a)
+a = 1
+printf(a)
 
 function main()
   a = 1
@@ -0,0 +1,26 @@
+function is_big(x::Int64)::Bool
+  x > 10
+end
+
+function nop(x::Int64)::Nothing
+  printf(x)
+end
+
+function main()
+  t = true
+  f = false
+  printf(t)
+  printf(" ")
+  printf(f)
+  printf(" ")
+  printf(is_big(42))
+  printf(" ")
+  printf(nothing)
+  printf(" ")
+  n = nop(7)
+  printf(" ")
+  printf(n)
+  printf(" ")
+  printf(printf(""))
+  printf(" $t $(is_big(1)) $n\n")
+end
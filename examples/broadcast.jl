@@ -0,0 +1,26 @@
+function sq(v::Float64)::Float64
+    v * v
+end
+
+function scale(v::Vector{Int64}, k::Int64)::Int64
+    r = v .* k .+ 1
+    r[2]
+end
+
+x = [1.0, 2.0, 3.0]
+y = x .+ 1
+# fused into a single loop, sq.(x) .* 2 is never stored
+z = sq.(x) .* 2 .+ y
+print(y, " ", z, "|")
+x .= x .* 2
+x .+= 1
+print(x, " ", x .>= 5.0, "|")
+@. y = sq(x) / 2 + 1
+print(y, " ", scale([1, 2, 3], 3), "|")
+print(string.(["a", "b"], 1, "c"), " ", 1 .+ 2, "|")
+print(:(x .* y), " ", :(f.(x, 1)), "|")
+try
+    [1, 2] .+ [1, 2, 3]
+catch e
+    print(e.msg)
+end
@@ -0,0 +1,14 @@
+function main()
+  a = 'a'
+  l = 'λ'
+  printf(a)
+  printf(l)
+  printf('\n')
+  printf('\u{1F600}')
+  printf('\'')
+  printf('\x41')
+  printf("\n")
+  printf(a < 'b')
+  printf("\n")
+  printf("char: $l and $(a)!\n")
+end
@@ -0,0 +1,40 @@
+function describe(x::Integer)::String
+  "integer "
+end
+
+function describe(x::AbstractFloat)::String
+  "float "
+end
+
+function describe(x::Any)::String
+  "other "
+end
+
+function half(x::Real)::Real
+  x / 2
+end
+
+function main()
+  # argument types known at compile time
+  printf(describe(1))
+  printf(describe(2.5))
+  printf(describe("hi"))
+
+  # elements of a Vector{Any} are boxed, so describe is picked at runtime
+  xs = Vector{Any}(undef, 3)
+  xs[1] = 1
+  xs[2] = 2.5
+  xs[3] = 'c'
+
+  printf(describe(xs[1]))
+  printf(describe(xs[2]))
+  printf(describe(xs[3]))
+  printf(xs[3])
+  printf(typeof(xs[2]))
+  printf(half(3))
+
+  # declared types stick, x stays boxed when reassigned
+  x::Any = 1
+  x = 2.5
+  printf(describe(x))
+end
@@ -0,0 +1,73 @@
+struct ParseError <: Exception
+  msg::String
+end
+
+function fail(msg::String)::Nothing
+  error(msg)
+end
+
+function check(xs::Vector{Int64}, i::Int64)::Int64
+  xs[i]
+end
+
+function main()
+  try
+    printf("before ")
+    fail("boom")
+    printf("not printed")
+  catch e
+    printf(e.msg)
+    printf(" ")
+  finally
+    printf("cleanup ")
+  end
+
+  try
+    check([1, 2, 3], 4)
+  catch e
+    showerror(e)
+    printf(" ")
+  end
+
+  try
+    div(7, 0)
+  catch e
+    showerror(e)
+    printf(" ")
+  end
+
+  try
+    throw(ParseError("bad input"))
+  catch e
+    printf(typeof(e))
+    printf(" ")
+    printf(e.msg)
+    printf(" ")
+  end
+
+  # the catch block sees what the try assigned before throwing
+  x = 1
+  try
+    x = 2
+    error("oops")
+  catch
+    x = x + 10
+  end
+  printf(x)
+  printf(" ")
+
+  # finally runs before the exception is passed on
+  try
+    try
+      throw(DivideError())
+    finally
+      printf("inner ")
+    end
+  catch e
+    showerror(e)
+    printf(" ")
+  end
+
+  fail("uncaught")
+  printf("not printed")
+end
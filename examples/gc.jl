@@ -0,0 +1,27 @@
+greeting = "hello " * "world"
+
+function garbage(n::Int64)::Int64
+    s = string("item ", n)
+    xs = [s, s * "!", "static"]
+    length(xs)
+end
+
+function keep(n::Int64)::String
+    string("kept ", n)
+end
+
+function main()
+    kept = keep(1)
+    boxes = Vector{Any}(undef, 2)
+    boxes[1] = keep(2)
+    boxes[2] = 2.5
+    garbage(1)
+    garbage(2)
+    GC.gc()
+    printf(kept)
+    printf(boxes[1])
+    printf(boxes[2])
+    printf(greeting)
+    printf(Base.gc_live_bytes() < Base.gc_bytes())
+    GC.stats()
+end
@@ -0,0 +1,35 @@
+module Physics
+
+export g, drops
+
+const g = 9.81
+drops = 0
+
+function fall(t::Float64)::Float64
+    global drops = drops + 1
+    0.5 * g * t * t
+end
+
+end # module
+
+using .Physics
+
+const greeting = "hello"
+const answer = 6 * 7
+counter = 1
+
+function bump(n::Int64)::Int64
+    global counter = counter + n
+    counter
+end
+
+function main()
+    printf(greeting)
+    printf(answer)
+    printf(bump(2))
+    printf(bump(3))
+    printf(g)
+    printf(Physics.fall(2.0))
+    printf(Physics.fall(1.0))
+    printf(drops)
+end
@@ -0,0 +1,10 @@
+include("include/geometry.jl")
+
+using .Geometry
+# found through FARNESE_LOAD_PATH=examples/packages
+using Greetings
+
+function main()
+    printf(area(2.0, 3.0))
+    greet("world")
+end
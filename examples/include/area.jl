@@ -0,0 +1,3 @@
+function area(w::Float64, h::Float64)::Float64
+    w * h
+end
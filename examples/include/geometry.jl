@@ -0,0 +1,7 @@
+module Geometry
+
+export area
+
+include("area.jl")
+
+end # module
@@ -0,0 +1,24 @@
+# let makes a new scope, begin just groups expressions
+
+g = 10
+a = let g = 2, h = g * 3
+  g + h
+end
+
+function main()
+  x = 1
+  y = let x = 5, z = x + 1
+    x * z
+  end
+  println(x, " ", y, " ", g, " ", a)
+  w = begin
+    t = 4
+    t + x
+  end
+  println(w, " ", t)
+  # assigning a variable of the enclosing scope changes it
+  let
+    x = 7
+  end
+  println(x)
+end
@@ -0,0 +1,40 @@
+macro twice(ex)
+  quote
+    $ex
+    $ex
+  end
+end
+
+# y is renamed, so the caller's y is left alone
+macro square(x)
+  quote
+    y = $x
+    y * y
+  end
+end
+
+macro sum_of_squares(a, b)
+  lhs = quote @square($a) end
+  rhs = esc(quote @square($b) end)
+  quote $lhs + $rhs end
+end
+
+@inline function add_one(x::Int64)::Int64
+  x + 1
+end
+
+function main()
+  y = 3
+  @twice println("hi")
+  println(@square(y + 1), " ", y)
+  println(@sum_of_squares(1, 2))
+  z = @show y + 1
+  @show add_one(z) [1, 2] "s"
+  @assert z == 4
+  @assert(y > 0, "y must be positive")
+  try
+    @assert y < 0
+  catch e
+    println(e.msg)
+  end
+end
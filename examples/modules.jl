@@ -0,0 +1,37 @@
+module Geometry
+
+export area, perimeter
+
+function area(w::Float64, h::Float64)::Float64
+    w * h
+end
+
+function perimeter(w::Float64, h::Float64)::Float64
+    2.0 * (w + h)
+end
+
+function diagonal2(w::Float64, h::Float64)::Float64
+    w * w + h * h
+end
+
+end # module
+
+module Shapes
+
+using ..Geometry: area
+
+function square_area(x::Float64)::Float64
+    area(x, x)
+end
+
+end # module
+
+using .Geometry
+import .Shapes
+
+function main()
+    printf(area(2.0, 3.0))
+    printf(perimeter(2.0, 3.0))
+    printf(Geometry.diagonal2(3.0, 4.0))
+    printf(Shapes.square_area(5.0))
+end
@@ -0,0 +1,33 @@
+function main()
+  printf(0x1)
+  printf(" ")
+  printf(0x0001)
+  printf(" ")
+  printf(0b101)
+  printf(" ")
+  printf(0o777)
+  printf(" ")
+  printf(0xdead_beef)
+  printf(" ")
+  printf(0x123456789abcdef01)
+  printf("\n")
+  printf(1_000_000)
+  printf(" ")
+  printf(9223372036854775808)
+  printf(" ")
+  printf(1f0)
+  printf(" ")
+  printf(2.5f-1)
+  printf(" ")
+  printf(1e3)
+  printf("\n")
+  x = 0x10 + 1
+  printf(x)
+  printf(" ")
+  printf(0xff < 0x01)
+  printf(" ")
+  printf(1f0 + 1)
+  printf(" ")
+  printf(1f0 + 2.5)
+  printf("\n")
+end
@@ -0,0 +1,9 @@
+module Greetings
+
+export greet
+
+function greet(name::String)::Nothing
+    printf("\nhello $name\n")
+end
+
+end # module
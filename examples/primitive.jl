@@ -0,0 +1,20 @@
+# primitive types are plain bits, reinterpret reads the same bits as
+# another primitive type of the same size
+
+primitive type Money <: AbstractFloat 64 end
+primitive type Word <: Unsigned 32 end
+
+struct Account
+    balance::Money
+    id::Word
+end
+
+function dollars(m::Money)::Float64
+    reinterpret(Float64, m)
+end
+
+m = reinterpret(Money, 2.5)
+println(typeof(m), " ", dollars(m))
+println(Account(m, reinterpret(Word, 0x0000002a)))
+println(reinterpret(UInt64, 1.0))
+println(reinterpret(Bool, 0x01), " ", Core.bitcast(Int64, 1.0))
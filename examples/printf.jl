@@ -0,0 +1,15 @@
+using Printf
+
+function main()
+  @printf("%d %s\n", 1, "a")
+  @printf "%5.2f|%-4d|%04x\n" 2.5 7 255
+  n::Int32 = 42
+  @printf("%i %o %X %c\n", n, 8, 255, 'z')
+  @printf("%*d|%.*f\n", 6, 12, 3, 1.0)
+  @printf("%s %s %s %s %s\n", 1.5, 0.1, true, 'c', nameof(Int64))
+  @printf("100%%\n")
+
+  s = @sprintf("%x", 255)
+  println(s, " ", length(s))
+  println(@sprintf("%8.3e|%g", 12345.678, 0.0001))
+end
@@ -0,0 +1,18 @@
+# quoted code is an Expr value, $ splices values into it
+
+function main()
+  n = 3
+  ex = :(a + b * $n)
+  println(ex)
+  show(ex)
+  println()
+  println(ex.head, " ", ex.head == :call, " ", ex.args)
+  println(:((a + b) * c))
+  body = quote
+    x = $ex
+    f(x, p.y)
+  end
+  println(body)
+  show(Expr(:call, :g, :x, 1))
+  println()
+end
@@ -0,0 +1,28 @@
+module Counter
+
+export count, total
+
+count = 0
+total = 0.0
+
+function add!(x::Float64)::Float64
+    global count = count + 1
+    global total = total + x
+    total
+end
+
+# runs before anything that uses Counter
+printf("Counter loaded\n")
+add!(1.5)
+
+end # module
+
+using .Counter
+
+a = 1
+printf(a)
+b = a + 41
+printf(b)
+Counter.add!(2.5)
+printf(count)
+printf(total)
@@ -0,0 +1,48 @@
+struct Pair
+  first::Float64
+  second::Float64
+end
+
+struct Labeled
+  label::String
+  pair::Pair
+  value::Any
+end
+
+struct Celsius
+  degrees::Float64
+end
+
+# a show method replaces the default one
+function show(x::Celsius)::Nothing
+  print(x.degrees, "°C")
+end
+
+function main()
+  println(1.0, " ", 0.1, " ", 1.0 / 3.0, " ", 1.0e20, " ", 0.00001)
+  println(1.5f0, " ", 42, " ", true, " ", 'x', " ", nameof(Pair))
+  println("print writes strings as they are")
+  show("show quotes them")
+  println()
+  show('x')
+  print(" ")
+  show(nameof(Pair))
+  println()
+
+  p = Pair(1.0, 2.0)
+  println(p)
+  println(Labeled("origin", Pair(0.0, 0.0), nameof(Float64)))
+  println([1, 2, 3], " ", ["a", "b"], " ", Vector{Int64}(undef, 0))
+  xs = Vector{Any}(undef, 2)
+  xs[1] = 1
+  xs[2] = "two"
+  println(xs)
+  println(Celsius(21.5))
+  println([Celsius(1.0), Celsius(2.0)])
+
+  # boxed values are shown by their runtime type
+  x::Any = p
+  println(x)
+  y::Number = 2.5
+  println(y)
+end
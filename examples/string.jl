@@ -0,0 +1,23 @@
+function main()
+  name = "λ world"
+  x = 42
+  y = 2.5
+  greeting = "Hello, " * name * "!"
+  printf(greeting)
+  printf("\n")
+  printf(length(greeting))
+  printf("\n")
+  printf("x = $x, y = $(y * 2.0), sum = $(x + 1)\n")
+  printf("tab\there \"quoted\" \$x \u{3bb}\x41\n")
+  printf("abc" < "abd")
+  printf("\n")
+  printf("abc" == "abc")
+  printf("\n")
+  printf(x >= 43)
+  printf("\n")
+  printf(y != 2.5)
+  printf("\n")
+  s = string("n=", x, " ok=", x < 50)
+  printf(s)
+  printf("\n")
+end
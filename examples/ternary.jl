@@ -0,0 +1,24 @@
+# c ? a : b is an if, both give the value of the branch taken
+
+function sign(x)
+  if x > 0
+    s = "pos"
+  elseif x < 0
+    s = "neg"
+  else
+    s = "zero"
+  end
+  s
+end
+
+function main()
+  x = 3
+  y = x > 2 ? 10 : 20
+  z = x > 5 ? 1 : x > 2 ? 2 : 3
+  println(y, " ", z, " ", (x == 3 ? "three" : "other"), " ", x < 0 ? 1 : 2.5)
+  println(sign(4), sign(0 - 1), sign(0))
+  if x == 3
+    x = x + 1
+  end
+  println(x)
+end
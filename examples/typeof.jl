@@ -0,0 +1,29 @@
+abstract type Shape end
+
+struct Point <: Shape
+  x::Float64
+  y::Float64
+end
+
+function main()
+  printf(typeof(1.0))
+  printf(" ")
+  printf(supertype(Float64))
+  printf(" ")
+  printf(supertype(supertype(supertype(Int64))))
+  printf(" ")
+  printf(typeof(Int64))
+  printf(" ")
+  printf(supertype(Point))
+  printf(" ")
+  printf(supertype(Shape))
+  printf(" ")
+  names = fieldnames(Point)
+  printf(length(names))
+  printf(names[1])
+  printf(names[2])
+  printf(" ")
+  printf(typeof([1.0, 2.0]))
+  printf(" ")
+  printf(nameof(String))
+end
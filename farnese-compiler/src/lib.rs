@@ -1,31 +1,103 @@
 use farnese_core::{
-    Core, DataType, LLVMAlloca, LLVMPrintf, LLVMValue, MethodHelper, Module, Primitive, Symbol,
+    Array, Core, DataType, FarneseBox, FarneseChar, FarneseException, FarneseExpr, FarneseFormat,
+    FarneseGC, FarneseShow, FarneseString, LLVMAlloca, LLVMPrintf, LLVMType, LLVMValue,
+    MethodHelper, Module, ModuleCache, ModuleError, Primitive, SourceHasher, Symbol,
 };
-use farnese_lexer::ast::{Node, Operator};
+use farnese_lexer::ast::{self, ModulePath, Node, Operator};
 use farnese_lexer::lexer;
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::types::BasicMetadataTypeEnum;
-use inkwell::values::BasicMetadataValueEnum;
-use std::collections::HashMap;
+use inkwell::module::Linkage;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, GlobalValue, IntValue,
+    PointerValue,
+};
+use inkwell::{FloatPredicate, IntPredicate};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+mod macros;
+use macros::MacroExpander;
+
+#[cfg(test)]
+mod test_utils;
+
+/// source of the bundled Base module
+const BASE: &str = include_str!("../../src/base/base.jl");
+/// standard libraries bundled with the compiler, with the macros
+/// they export that the compiler lowers itself
+const STDLIBS: &[(&str, &str, &[&str])] = &[(
+    "Printf",
+    include_str!("../../src/stdlib/Printf.jl"),
+    &["printf", "sprintf"],
+)];
 
 /// a table of modules
 type Modules<'a> = HashMap<Symbol, Module<'a>>;
+/// the module each module was defined in
+type Parents = HashMap<Symbol, Symbol>;
 /// scope
 type Scope<'a> = HashMap<Symbol, (Value<'a>, DataType)>;
 /// a stack of LLVM values
 type Stack<'a> = Vec<(Value<'a>, DataType)>;
+/// the values a branch passes on to where branches join
+type Branch<'a> = (Vec<(Value<'a>, DataType)>, BasicBlock<'a>);
+/// the result of a block along with the scope it ends with
+type ScopedBranch<'a> = ((Value<'a>, DataType), Scope<'a>, BasicBlock<'a>);
 /// basic value type
 type Value<'a> = BasicMetadataValueEnum<'a>;
+/// variables assigned in a try block, kept in memory so the catch
+/// sees the values they had when the exception was thrown
+type TrySlots<'a> = HashMap<Symbol, TrySlot<'a>>;
+
+/// a slot for each type the variable was assigned and the index of
+/// the one written last. Accesses are volatile so the values survive
+/// the longjmp back to the catch
+struct TrySlot<'a> {
+    tag: PointerValue<'a>,
+    slots: Vec<(PointerValue<'a>, DataType)>,
+}
 
 pub struct Compiler<'a> {
+    cache: Option<ModuleCache>,
+    /// files currently being included, innermost last
+    files: Vec<PathBuf>,
+    /// cache keys of the modules compiled so far
+    keys: HashMap<Symbol, u64>,
+    /// directories searched for packages, from FARNESE_LOAD_PATH
+    load_path: Vec<PathBuf>,
+    /// macros defined so far
+    expander: MacroExpander,
+    /// let blocks being compiled, assignments in them are local even
+    /// outside of functions
+    local_scopes: usize,
     modules: Modules<'a>,
+    parents: Parents,
     pub scope: Scope<'a>,
     pub stack: Stack<'a>,
+    /// slots of the try blocks being compiled in the current function
+    try_slots: Vec<TrySlots<'a>>,
+    /// globals without a type annotation, they have the type of
+    /// the first value assigned to them
+    untyped_globals: HashSet<String>,
 }
 
 impl<'a> Compiler<'a> {
     pub fn new(context: &'a Context) -> Self {
+        // precompiled modules go in FARNESE_CACHE_DIR or ~/.farnese/compiled
+        let cache_dir = env::var_os("FARNESE_CACHE_DIR")
+            .map(PathBuf::from)
+            .or_else(|| env::home_dir().map(|x| x.join(".farnese").join("compiled")));
+        Self::with_cache_dir(context, cache_dir)
+    }
+
+    /// a compiler caching modules in cache_dir, None disables the cache
+    pub fn with_cache_dir(context: &'a Context, cache_dir: Option<PathBuf>) -> Self {
         let mut modules = Modules::<'a>::new();
         let scope = Scope::<'a>::new();
         let stack = Stack::<'a>::new();
@@ -33,13 +105,258 @@ impl<'a> Compiler<'a> {
         // setup core
         let mut core = Core::new(&context);
         let core_module = core.bootstrap();
-        let _ = core_module.print_to_file("Core.ll");
+        // Core is built into the compiler so its key identifies the build
+        let build = ModuleCache::build_id(&core_module);
         modules.insert(Symbol::new("Core"), core_module);
+        let keys = HashMap::from([(Symbol::new("Core"), build)]);
+        let cache = cache_dir.map(|x| ModuleCache::new(x, build));
 
-        Self {
+        let load_path = match env::var_os("FARNESE_LOAD_PATH") {
+            Some(x) => env::split_paths(&x).collect(),
+            None => Vec::new(),
+        };
+
+        let mut compiler = Self {
+            cache,
+            files: Vec::new(),
+            keys,
+            load_path,
+            expander: MacroExpander::new(),
+            local_scopes: 0,
             modules: modules,
+            parents: Parents::new(),
             scope: scope,
             stack: stack,
+            try_slots: Vec::new(),
+            untyped_globals: HashSet::new(),
+        };
+
+        // setup base, like in Julia its parent is itself
+        for node in lexer::parse_source(BASE).unwrap() {
+            if let Node::Module { name, exprs } = node {
+                let name = Symbol::new(&name);
+                compiler.compile_module(name.clone(), name, *exprs, context);
+            }
+        }
+        compiler
+    }
+
+    /// a new module with Core linked in and, once it exists, Base
+    /// imported the same way every Julia module implicitly uses Base
+    pub fn create_module(&self, name: &str) -> Module<'a> {
+        let core = self.get_module("Core");
+        let mut module = Module::new(core.get_context(), name);
+        module.link(core).unwrap();
+        if let Some(base) = self.modules.get(&Symbol::new("Base")) {
+            module.import(base, &[]).unwrap_or_else(|e| panic!("{}", e));
+        }
+        module
+    }
+
+    fn compile_array_constructor<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        name: &str,
+        args: Vec<Node>,
+    ) {
+        let datatype = module.resolve_type(name);
+        let array = Array::new(datatype.clone());
+        match args.first() {
+            Some(Node::Symbol(x)) if x == "undef" => {}
+            _ => todo!("Only {}(undef, dims...) constructors are supported", name),
+        }
+        assert!(
+            args.len() as u32 - 1 == array.ndims(),
+            "{} expects {} dimensions",
+            name,
+            array.ndims()
+        );
+        assert!(
+            array.ndims() == 1,
+            "Only one dimensional arrays are supported"
+        );
+        let length = self.compile_index(builder, module, args[1].clone());
+        let ptr = array.emit_ir_new(builder, module, length);
+        self.stack.push((ptr.into(), datatype));
+    }
+
+    fn compile_array_expr<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        elements: Vec<Node>,
+    ) {
+        let elements = elements
+            .into_iter()
+            .map(|x| {
+                self.compile_expr(builder, module, x);
+                self.stack.pop().unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // promote to a common element type, e.g. [1, 2.0] is a Vector{Float64},
+        // anything else is boxed, e.g. [1, "a"] is a Vector{Any}
+        let names = elements.iter().map(|x| x.1.full_name()).collect::<Vec<_>>();
+        let eltype = if names.is_empty() {
+            module.get_type("Float64").clone()
+        } else if names.iter().all(|x| *x == names[0]) {
+            elements[0].1.clone()
+        } else if names
+            .iter()
+            .all(|x| ["Float32", "Float64", "Int16", "Int32", "Int64"].contains(&x.as_str()))
+        {
+            module.get_type("Float64").clone()
+        } else {
+            module.get_type("Any").clone()
+        };
+
+        let vals = elements
+            .iter()
+            .map(|x| {
+                self.convert(builder, module, x.clone(), &eltype)
+                    .try_into()
+                    .unwrap()
+            })
+            .collect::<Vec<BasicValueEnum<'a>>>();
+        let datatype = DataType::new_array_type(&eltype, 1);
+        let array = Array::new(datatype.clone());
+        let ptr = array.emit_ir_literal(builder, module, &vals);
+        self.stack.push((ptr.into(), datatype));
+    }
+
+    /// compiles an array index and converts it to an Int64
+    fn compile_index<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        index: Node,
+    ) -> IntValue<'a> {
+        self.compile_expr(builder, module, index);
+        let index = self.stack.pop().unwrap();
+        let int64 = module.get_type("Int64").clone();
+        self.convert(builder, module, index, &int64)
+            .into_int_value()
+    }
+
+    fn compile_index_expr<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        array: Node,
+        indices: Vec<Node>,
+    ) -> (Array, PointerValue<'a>, IntValue<'a>) {
+        self.compile_expr(builder, module, array);
+        let (ptr, datatype) = self.stack.pop().unwrap();
+        assert!(
+            datatype.name().name() == "Array",
+            "MethodError: no method matching getindex({}, ...)",
+            datatype.full_name()
+        );
+        assert!(indices.len() == 1, "Only linear indexing is supported");
+        let index = self.compile_index(builder, module, indices[0].clone());
+        (Array::new(datatype), ptr.into_pointer_value(), index)
+    }
+
+    /// converts a value to the supplied type, currently only
+    /// numeric promotions and boxing are supported
+    fn convert<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: (Value<'a>, DataType),
+        datatype: &DataType,
+    ) -> Value<'a> {
+        if val.1.full_name() == datatype.full_name() {
+            return val.0;
+        }
+
+        // boxes stay boxed and are checked once they are unboxed
+        let box_helper = FarneseBox::new();
+        match (val.1.is_abstract, datatype.is_abstract) {
+            (true, true) => return val.0,
+            (true, false) => {
+                let ptr = val.0.into_pointer_value();
+                return box_helper
+                    .emit_ir_typeassert(builder, module, ptr, datatype)
+                    .into();
+            }
+            (false, true) if module.is_subtype(&val.1, datatype) => {
+                let ptr =
+                    box_helper.emit_ir_box(builder, module, val.0.try_into().unwrap(), &val.1);
+                return ptr.into();
+            }
+            _ => {}
+        }
+        let ir_type: BasicTypeEnum<'a> = datatype.get_ir_value_type(module).try_into().unwrap();
+        let is_signed = !Self::is_unsigned(&val.1);
+        match (val.0, ir_type) {
+            (Value::IntValue(x), BasicTypeEnum::IntType(y)) => builder
+                .build_int_cast_sign_flag(x, y, is_signed, "")
+                .unwrap()
+                .into(),
+            (Value::IntValue(x), BasicTypeEnum::FloatType(y)) if is_signed => {
+                builder.build_signed_int_to_float(x, y, "").unwrap().into()
+            }
+            (Value::IntValue(x), BasicTypeEnum::FloatType(y)) => builder
+                .build_unsigned_int_to_float(x, y, "")
+                .unwrap()
+                .into(),
+            (Value::FloatValue(x), BasicTypeEnum::FloatType(y)) => {
+                builder.build_float_cast(x, y, "").unwrap().into()
+            }
+            _ => panic!(
+                "MethodError: Cannot convert an object of type {} to an object of type {}",
+                val.1.full_name(),
+                datatype.full_name()
+            ),
+        }
+    }
+
+    fn is_unsigned(datatype: &DataType) -> bool {
+        datatype.supertype.name() == "Unsigned" || datatype.name().name() == "Bool"
+    }
+
+    /// the type both sides of a binary operation are converted to, follows
+    /// Julia in that floats win over integers and an unsigned integer wins
+    /// over a signed one unless the signed one is wider
+    fn promote_type(
+        &self,
+        module: &Module<'a>,
+        lhs: (&Value<'a>, &DataType),
+        rhs: (&Value<'a>, &DataType),
+    ) -> DataType {
+        let width = |x: &Value<'a>| match x {
+            Value::IntValue(x) => x.get_type().get_bit_width(),
+            Value::FloatValue(x) => {
+                if x.get_type() == module.get_context().f32_type() {
+                    32
+                } else {
+                    64
+                }
+            }
+            _ => 0,
+        };
+        let (lhs_width, rhs_width) = (width(lhs.0), width(rhs.0));
+        match (lhs.0, rhs.0) {
+            (Value::FloatValue(_), Value::IntValue(_)) => lhs.1.clone(),
+            (Value::IntValue(_), Value::FloatValue(_)) => rhs.1.clone(),
+            (Value::IntValue(_), Value::IntValue(_))
+                if Self::is_unsigned(lhs.1) != Self::is_unsigned(rhs.1) =>
+            {
+                let (unsigned, signed) = if Self::is_unsigned(lhs.1) {
+                    ((lhs.1, lhs_width), (rhs.1, rhs_width))
+                } else {
+                    ((rhs.1, rhs_width), (lhs.1, lhs_width))
+                };
+                if unsigned.1 >= signed.1 {
+                    unsigned.0.clone()
+                } else {
+                    signed.0.clone()
+                }
+            }
+            _ if rhs_width > lhs_width => rhs.1.clone(),
+            _ => lhs.1.clone(),
         }
     }
 
@@ -53,8 +370,92 @@ impl<'a> Compiler<'a> {
     ) {
         self.compile_expr(builder, module, lhs);
         self.compile_expr(builder, module, rhs);
-        let (rhs, _rhs_type) = self.stack.pop().unwrap();
-        let (lhs, lhs_type) = self.stack.pop().unwrap();
+        let rhs = self.stack.pop().unwrap();
+        let lhs = self.stack.pop().unwrap();
+        let result = self.compile_binary_op(builder, module, op, lhs, rhs);
+        self.stack.push(result);
+    }
+
+    fn compile_binary_op<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        op: Operator,
+        (lhs, lhs_type): (Value<'a>, DataType),
+        (rhs, rhs_type): (Value<'a>, DataType),
+    ) -> (Value<'a>, DataType) {
+        if lhs_type.is_abstract || rhs_type.is_abstract {
+            return self.compile_boxed_binary_op(
+                builder,
+                module,
+                op,
+                (lhs, lhs_type),
+                (rhs, rhs_type),
+            );
+        }
+
+        if lhs_type.name().name() == "String" && rhs_type.name().name() == "String" {
+            let string = FarneseString::new();
+            let (lhs, rhs) = (lhs.into_pointer_value(), rhs.into_pointer_value());
+            let result = match op {
+                Operator::Multiply => (
+                    string.emit_ir_concat(builder, module, lhs, rhs).into(),
+                    lhs_type,
+                ),
+                _ => {
+                    // compare against zero like the int case below
+                    let cmp = string.emit_ir_cmp(builder, module, lhs, rhs);
+                    let zero = cmp.get_type().const_zero();
+                    let predicate = Self::int_predicate(op, true);
+                    (
+                        builder
+                            .build_int_compare(predicate, cmp, zero, "")
+                            .unwrap()
+                            .into(),
+                        module.get_type("Bool").clone(),
+                    )
+                }
+            };
+            return result;
+        }
+
+        // symbols are equal when their hashes are
+        if lhs_type.name().name() == "Symbol" && rhs_type.name().name() == "Symbol" {
+            let predicate = match op {
+                Operator::Equal | Operator::EqualEqual => IntPredicate::EQ,
+                Operator::NotEqual => IntPredicate::NE,
+                _ => panic!("MethodError: no method matching {}(::Symbol, ::Symbol)", op),
+            };
+            let [lhs, rhs] = [lhs, rhs].map(|x| {
+                let ptr = builder
+                    .build_struct_gep(x.into_pointer_value(), 0, "")
+                    .unwrap();
+                builder.build_load(ptr, "").unwrap().into_int_value()
+            });
+            let val = builder.build_int_compare(predicate, lhs, rhs, "").unwrap();
+            return (val.into(), module.get_type("Bool").clone());
+        }
+
+        // promote mixed numeric arithmetic to a common type
+        let (lhs, rhs, lhs_type) = if lhs_type.full_name() != rhs_type.full_name() {
+            let datatype = self.promote_type(module, (&lhs, &lhs_type), (&rhs, &rhs_type));
+            let lhs = self.convert(builder, module, (lhs, lhs_type), &datatype);
+            let rhs = self.convert(builder, module, (rhs, rhs_type), &datatype);
+            (lhs, rhs, datatype)
+        } else {
+            (lhs, rhs, lhs_type)
+        };
+        // like Julia, / on integers gives a Float64
+        let (lhs, rhs, lhs_type) = match (op, lhs) {
+            (Operator::Divide, Value::IntValue(_)) => {
+                let datatype = module.get_type("Float64").clone();
+                let lhs = self.convert(builder, module, (lhs, lhs_type.clone()), &datatype);
+                let rhs = self.convert(builder, module, (rhs, lhs_type), &datatype);
+                (lhs, rhs, datatype)
+            }
+            _ => (lhs, rhs, lhs_type),
+        };
+        let is_signed = !Self::is_unsigned(&lhs_type);
 
         // let result: BasicValueEnum<'a> = match lhs {
         let result: (Value<'a>, DataType) = match lhs {
@@ -64,288 +465,2925 @@ impl<'a> Compiler<'a> {
                         Value::IntValue(builder.build_int_sub(x, y, "").unwrap()),
                         lhs_type,
                     ),
+                    Operator::Multiply => (
+                        Value::IntValue(builder.build_int_mul(x, y, "").unwrap()),
+                        lhs_type,
+                    ),
                     Operator::Plus => (
                         Value::IntValue(builder.build_int_add(x, y, "").unwrap()),
                         lhs_type,
                     ),
+                    Operator::Equal
+                    | Operator::EqualEqual
+                    | Operator::Greater
+                    | Operator::GreaterEqual
+                    | Operator::Less
+                    | Operator::LessEqual
+                    | Operator::NotEqual => (
+                        Value::IntValue(
+                            builder
+                                .build_int_compare(Self::int_predicate(op, is_signed), x, y, "")
+                                .unwrap(),
+                        ),
+                        module.get_type("Bool").clone(),
+                    ),
+                    _ => todo!("Unsupported op {:?}", op),
+                },
+                _ => todo!("Types don't match"),
+            },
+            Value::FloatValue(x) => match rhs {
+                Value::FloatValue(y) => match op {
+                    Operator::Divide => (
+                        Value::FloatValue(builder.build_float_div(x, y, "").unwrap()),
+                        lhs_type,
+                    ),
+                    Operator::Minus => (
+                        Value::FloatValue(builder.build_float_sub(x, y, "").unwrap()),
+                        lhs_type,
+                    ),
+                    Operator::Multiply => (
+                        Value::FloatValue(builder.build_float_mul(x, y, "").unwrap()),
+                        lhs_type,
+                    ),
+                    Operator::Plus => (
+                        Value::FloatValue(builder.build_float_add(x, y, "").unwrap()),
+                        lhs_type,
+                    ),
+                    Operator::Equal
+                    | Operator::EqualEqual
+                    | Operator::Greater
+                    | Operator::GreaterEqual
+                    | Operator::Less
+                    | Operator::LessEqual
+                    | Operator::NotEqual => (
+                        Value::IntValue(
+                            builder
+                                .build_float_compare(Self::float_predicate(op), x, y, "")
+                                .unwrap(),
+                        ),
+                        module.get_type("Bool").clone(),
+                    ),
                     _ => todo!("Unsupported op {:?}", op),
                 },
                 _ => todo!("Types don't match"),
             },
             _ => todo!("Not supported yet"),
         };
-        self.stack.push(result);
+        result
     }
 
-    pub fn compile_expr<'b>(
-        &mut self,
+    /// unboxes the first boxed operand by switching on its tag, one
+    /// branch per number type it could hold
+    fn compile_boxed_binary_op<'b>(
+        &self,
         builder: &'b Builder<'a>,
-        module: &mut Module<'a>,
-        expr: Node,
-    ) {
-        match expr {
-            Node::AbstractType { name, supertype } => {
-                let datatype = DataType::new_abstract_type(&name, &supertype);
-                module.insert_type(datatype);
-            }
-            Node::AssignmentExpr { identifier, value } => {
-                self.compile_expr(&builder, module, *value);
-                let prev_val_ptr = self.stack.pop().unwrap();
+        module: &Module<'a>,
+        op: Operator,
+        lhs: (Value<'a>, DataType),
+        rhs: (Value<'a>, DataType),
+    ) -> (Value<'a>, DataType) {
+        let context = module.get_context();
+        let box_helper = FarneseBox::new();
+        let is_lhs = lhs.1.is_abstract;
+        let boxed = if is_lhs { &lhs } else { &rhs };
+        let ptr = boxed.0.into_pointer_value();
+        let tag = box_helper.emit_ir_typeof(builder, ptr);
 
-                self.scope.insert(Symbol::new(&identifier), prev_val_ptr);
-            }
-            Node::BinaryExpr { op, lhs, rhs } => {
-                self.compile_binary_expr(&builder, module, op, *lhs, *rhs)
-            }
-            Node::Empty => {
-                // do nothing
-            }
-            Node::Exports { symbols } => {
-                for export in symbols.iter() {
-                    // module.push_export(export);
-                    match export {
-                        Node::Symbol(x) => module.push_export(Symbol::new(x)),
-                        _ => todo!(),
-                    }
-                }
-            }
-            Node::Function {
-                name,
-                args,
-                return_type,
-                body,
-            } => self.compile_function(module, &name, &args, &return_type, &body),
-            Node::MethodCall { name, args } => {
-                self.compile_method_call(&builder, module, &name, args)
-            }
-            Node::ParenthesesExpr { expr } => {
-                self.compile_expr(&builder, module, *expr);
-            }
-            Node::Primitive(x) => {
-                let x: Primitive = x.into();
-                // let val = x.emit_ir_value(module);
-                let val = match x {
-                    Primitive::String(_) => x.emit_ir_alloca(&builder, module).into(),
-                    _ => x.emit_ir_value(module),
-                };
-                let datatype = x.get_datatype();
-                self.stack.push((val.into(), datatype));
-            }
-            Node::PrimitiveType {
-                name,
-                supertype,
-                bits,
-            } => {
-                let datatype = DataType::new_primitive_type(&name, &supertype, bits);
-                module.insert_type(datatype);
-            }
-            Node::StructType {
-                name,
-                supertype,
-                field_names,
-                field_types,
-            } => {
-                // hack for now TODO refactor this
-                let field_names = field_names
-                    .iter()
-                    .map(|x| Symbol::new(x))
-                    .collect::<Vec<_>>();
-                let field_types = Box::new(
-                    field_types
-                        .iter()
-                        .map(|x| module.get_type(x).clone())
-                        .collect(),
-                );
-                let datatype = DataType::new(
-                    Symbol::new(&name),
-                    Symbol::new(&supertype),
-                    false,
-                    false,
-                    false,
-                    field_names,
-                    field_types,
-                );
-                module.insert_type(datatype);
-            }
-            Node::Symbol(x) => {
-                // let val = self
-                //     .scope
-                //     .get(&Symbol::new(&x))
-                //     .expect(format!("Symbol not found: {}", x).as_str());
-                // self.stack.push(val.clone());
-                let x = Symbol::new(&x);
-                let val = x.emit_ir_alloca(&builder, module);
-                let datatype = self.modules.get(&Symbol::new("Core"))
-                    .unwrap()
-                    .get_type("Symbol");
-                self.stack.push((val.into(), datatype.clone()));
-            }
-            _ => todo!("Unsupported type {:?}", expr),
+        let number = module.get_type("Number");
+        let mut datatypes = module
+            .get_types()
+            .values()
+            .filter(|x| !x.is_abstract && module.is_subtype(x, &boxed.1))
+            .filter(|x| module.is_subtype(x, number))
+            .collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let mut results = Vec::new();
+        for datatype in datatypes {
+            let found = context.append_basic_block(func, "");
+            let next = context.append_basic_block(func, "");
+            let is_type = builder
+                .build_int_compare(IntPredicate::EQ, tag, datatype.emit_ir_object(module), "")
+                .unwrap();
+            let _ = builder.build_conditional_branch(is_type, found, next);
+
+            builder.position_at_end(found);
+            let val = box_helper.emit_ir_unbox(builder, module, ptr, datatype);
+            let val = (val.into(), datatype.clone());
+            let result = match is_lhs {
+                true => self.compile_binary_op(builder, module, op, val, rhs.clone()),
+                false => self.compile_binary_op(builder, module, op, lhs.clone(), val),
+            };
+            results.push((vec![result], builder.get_insert_block().unwrap()));
+            builder.position_at_end(next);
         }
+
+        // none of the number types, the error shows the runtime types
+        let exception = FarneseException::new();
+        let args =
+            [&lhs, &rhs].map(|(val, datatype)| ((*val).try_into().unwrap(), datatype.clone()));
+        let e = exception.emit_ir_method_error(builder, module, &op.to_string(), &args);
+        exception.emit_ir_throw(builder, module, e);
+
+        self.compile_join(builder, module, results).pop().unwrap()
     }
 
-    fn compile_function<'b>(
-        &mut self,
-        module: &mut Module<'a>,
-        name: &str,
-        args: &Vec<Node>,
-        return_type: &str,
-        body: &Box<Vec<Node>>,
-    ) {
+    /// branches from the end of every branch to a new block where their
+    /// values are joined with phis, values that differ in type between
+    /// branches are boxed
+    fn compile_join<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        branches: Vec<Branch<'a>>,
+    ) -> Vec<(Value<'a>, DataType)> {
         let context = module.get_context();
-        let builder = context.create_builder();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let done = context.append_basic_block(func, "");
+        // when no branch gets here the single value is never used
+        let count = branches.first().map_or(1, |x| x.0.len());
+        let datatypes = (0..count)
+            .map(
+                |n| match branches.iter().all(|x| x.0[n].1 == branches[0].0[n].1) {
+                    true if !branches.is_empty() => branches[0].0[n].1.clone(),
+                    _ => module.get_type("Any").clone(),
+                },
+            )
+            .collect::<Vec<_>>();
 
-        // handle main as a special case
-        if name == "main" {
-            let return_type = context.i32_type();
-            let func = return_type.fn_type(&[], false);
-            let func = module.add_function(&name, func, None);
-            let entry = context.append_basic_block(func, "entry");
-            builder.position_at_end(entry);
-            for expr in (*body).iter() {
-                self.compile_expr(&builder, module, expr.clone())
-            }
-            let return_val = return_type.const_int(0, false);
-            let _ = builder.build_return(Some(&return_val));
-            return;
+        let mut incoming = Vec::new();
+        for (vals, block) in branches {
+            builder.position_at_end(block);
+            let vals = vals
+                .into_iter()
+                .zip(datatypes.iter())
+                .map(|(val, datatype)| {
+                    let val = self.convert(builder, module, val, datatype);
+                    BasicValueEnum::try_from(val).unwrap()
+                })
+                .collect::<Vec<_>>();
+            incoming.push((vals, builder.get_insert_block().unwrap()));
+            let _ = builder.build_unconditional_branch(done);
         }
 
-        let mut arg_names = Vec::<Symbol>::new();
-        let mut arg_types = Vec::<DataType>::new();
-        let _ = args
-            .iter()
-            .map(|arg| match arg {
-                Node::FunctionArg { name, arg_type } => {
-                    arg_names.push(Symbol::new(name));
-                    arg_types.push(module.get_type(arg_type).clone());
+        builder.position_at_end(done);
+        datatypes
+            .into_iter()
+            .enumerate()
+            .map(|(n, datatype)| {
+                let ir_type: BasicTypeEnum<'a> =
+                    datatype.get_ir_value_type(module).try_into().unwrap();
+                let phi = builder.build_phi(ir_type, "").unwrap();
+                for (vals, block) in incoming.iter() {
+                    phi.add_incoming(&[(&vals[n], *block)]);
                 }
-                _ => panic!("Shouldn't happen"),
+                (phi.as_basic_value().into(), datatype)
             })
-            .collect::<Vec<_>>();
-        let method_name = name.to_owned()
-            + "_"
-            + &arg_types
-                .iter()
-                .map(|x| x.name().name())
-                .collect::<Vec<_>>()
-                .join("_");
-
-        // TODO need to figure out how to infer return type
-        let return_type = module.get_type(&return_type);
+            .collect()
+    }
 
-        // setup field types and return type
-        let arg_types: Vec<_> = arg_types
+    /// like compile_join but also joins the variables the branches
+    /// assigned, the ones of the enclosing scope that any branch changed
+    /// and new ones that every branch defines
+    fn compile_join_scopes<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        outer: &Scope<'a>,
+        branches: Vec<ScopedBranch<'a>>,
+    ) -> (Value<'a>, DataType) {
+        let is_joined = |name: &Symbol| {
+            let mut scopes = branches.iter().map(|(_, scope, _)| scope);
+            match outer.get(name) {
+                Some((val, datatype)) => {
+                    scopes.any(|scope| scope[name].0 != *val || scope[name].1 != *datatype)
+                }
+                None => scopes.all(|scope| scope.contains_key(name)),
+            }
+        };
+        let mut names = branches
             .iter()
-            .map(|x| x.get_ir_value_type(module).try_into().unwrap())
-            .collect();
+            .flat_map(|(_, scope, _)| scope.keys())
+            .filter(|name| is_joined(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort_by(|a, b| a.name().cmp(b.name()));
+        names.dedup();
+        let branches = branches
+            .into_iter()
+            .map(|(result, scope, block)| {
+                let mut vals = vec![result];
+                vals.extend(names.iter().map(|x| scope[x].clone()));
+                (vals, block)
+            })
+            .collect::<Vec<_>>();
+        let mut vals = self.compile_join(builder, module, branches);
+        let result = vals.remove(0);
+        self.scope = outer.clone();
+        for (name, val) in names.into_iter().zip(vals) {
+            self.scope.insert(name, val);
+        }
+        result
+    }
+
+    /// the value of a condition, a boxed value has to hold a Bool
+    fn compile_condition<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        condition: Node,
+    ) -> IntValue<'a> {
+        self.compile_expr(builder, module, condition);
+        let val = self.stack.pop().unwrap();
+        if !val.1.is_abstract && val.1.name().name() != "Bool" {
+            panic!(
+                "TypeError: non-boolean ({}) used in boolean context",
+                val.1.full_name()
+            );
+        }
+        let datatype = module.get_type("Bool").clone();
+        self.convert(builder, module, val, &datatype)
+            .into_int_value()
+    }
+
+    /// branches on the condition, the value of the if is the value of
+    /// the block that ran, nothing when there's no else
+    fn compile_if<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        condition: Node,
+        if_block: &[Node],
+        else_block: &[Node],
+    ) -> (Value<'a>, DataType) {
+        let context = module.get_context();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let val = self.compile_condition(builder, module, condition);
+        let then = context.append_basic_block(func, "then");
+        let otherwise = context.append_basic_block(func, "else");
+        let _ = builder.build_conditional_branch(val, then, otherwise);
+
+        let outer = self.scope.clone();
+        let mut branches = Vec::new();
+        for (block, exprs) in [(then, if_block), (otherwise, else_block)] {
+            builder.position_at_end(block);
+            let result = self.compile_block(builder, module, exprs);
+            branches.push((
+                result,
+                self.scope.clone(),
+                builder.get_insert_block().unwrap(),
+            ));
+            self.scope = outer.clone();
+        }
+        let result = self.compile_join_scopes(builder, module, &outer, branches);
+
+        // code after the if continues in the last block
+        let done = builder.get_insert_block().unwrap();
+        let last = func.get_last_basic_block().unwrap();
+        if last != done {
+            let _ = done.move_after(last);
+        }
+        result
+    }
+
+    /// lowers try/catch/finally to setjmp, a throw returns to the catch
+    /// block. Finally blocks are compiled twice, once for leaving the try
+    /// normally and once before an exception is passed on. Variables
+    /// assigned in the blocks are joined like the result, the ones an
+    /// exception can interrupt are read back from try slots
+    fn compile_try<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        try_block: &[Node],
+        catch_name: Option<String>,
+        catch_block: Option<&Vec<Node>>,
+        finally_block: Option<&Vec<Node>>,
+    ) {
+        let context = module.get_context();
+        let exception = FarneseException::new();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let outer = self.scope.clone();
+        // each branch ends with its result and scope
+        let mut branches = Vec::new();
+        // blocks an exception leaves the try from, with the exception,
+        // the scope there and the slots of what was assigned since
+        let mut rethrows = Vec::new();
+
+        let slots = self.compile_try_slots(builder, module, try_block, None);
+        self.try_slots.push(slots);
+        let (handler, is_thrown) = exception.emit_ir_try(builder, module);
+        let body = context.append_basic_block(func, "try");
+        let caught = context.append_basic_block(func, "catch");
+        let _ = builder.build_conditional_branch(is_thrown, caught, body);
+
+        builder.position_at_end(body);
+        let result = self.compile_block(builder, module, try_block);
+        exception.emit_ir_try_leave(builder, module, handler);
+        branches.push((
+            result,
+            self.scope.clone(),
+            builder.get_insert_block().unwrap(),
+        ));
+        let slots = self.try_slots.pop().unwrap();
+        self.scope = outer.clone();
+
+        builder.position_at_end(caught);
+        let e = exception.emit_ir_catch(builder, module, handler);
+        self.load_try_slots(builder, module, &slots);
+        let caught = self.scope.clone();
+        match catch_block {
+            Some(catch_block) => {
+                let catch_name = catch_name.map(|x| Symbol::new(&x));
+                // exceptions thrown while catching still run finally
+                let handler = finally_block.map(|_| {
+                    let slots =
+                        self.compile_try_slots(builder, module, catch_block, catch_name.as_ref());
+                    self.try_slots.push(slots);
+                    let (handler, is_thrown) = exception.emit_ir_try(builder, module);
+                    let body = context.append_basic_block(func, "catch");
+                    let rethrow = context.append_basic_block(func, "finally");
+                    let _ = builder.build_conditional_branch(is_thrown, rethrow, body);
+                    builder.position_at_end(rethrow);
+                    let e = exception.emit_ir_catch(builder, module, handler);
+                    builder.position_at_end(body);
+                    (handler, rethrow, e)
+                });
+                // the exception is only visible in the catch block
+                if let Some(name) = catch_name.as_ref() {
+                    let any = module.get_type("Any").clone();
+                    self.scope.insert(name.clone(), (e.into(), any));
+                }
+                let result = self.compile_block(builder, module, catch_block);
+                if let Some((handler, rethrow, e)) = handler {
+                    exception.emit_ir_try_leave(builder, module, handler);
+                    let slots = self.try_slots.pop().unwrap();
+                    rethrows.push((rethrow, e, caught.clone(), slots));
+                }
+                if let Some(name) = catch_name {
+                    self.scope.remove(&name);
+                    if let Some(val) = caught.get(&name) {
+                        self.scope.insert(name, val.clone());
+                    }
+                }
+                branches.push((
+                    result,
+                    self.scope.clone(),
+                    builder.get_insert_block().unwrap(),
+                ));
+                self.scope = outer.clone();
+            }
+            None => rethrows.push((
+                builder.get_insert_block().unwrap(),
+                e,
+                caught,
+                TrySlots::new(),
+            )),
+        }
+
+        let result = self.compile_join_scopes(builder, module, &outer, branches);
+
+        if let Some(finally_block) = finally_block {
+            let _ = self.compile_block(builder, module, finally_block);
+        }
+        let done = builder.get_insert_block().unwrap();
+        let scope = self.scope.clone();
+        for (block, e, caught, slots) in rethrows {
+            builder.position_at_end(block);
+            if let Some(finally_block) = finally_block {
+                self.scope = caught;
+                self.load_try_slots(builder, module, &slots);
+                let _ = self.compile_block(builder, module, finally_block);
+            }
+            exception.emit_ir_throw(builder, module, e);
+        }
+        self.scope = scope;
+
+        // code after the try continues in the last block
+        let last = func.get_last_basic_block().unwrap();
+        if last != done {
+            let _ = done.move_after(last);
+        }
+        builder.position_at_end(done);
+        self.stack.push(result);
+    }
+
+    /// slots for the variables of the current scope that exprs assign,
+    /// holding their values from here on. hidden is a name that exprs
+    /// see as a different variable
+    fn compile_try_slots<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        exprs: &[Node],
+        hidden: Option<&Symbol>,
+    ) -> TrySlots<'a> {
+        let mut names = macros::assigned_names(exprs)
+            .into_iter()
+            .map(|x| Symbol::new(&x))
+            .filter(|x| self.scope.contains_key(x) && Some(x) != hidden)
+            .collect::<Vec<_>>();
+        names.sort_by(|a, b| a.name().cmp(b.name()));
+        let i8_type = module.get_context().i8_type();
+        let mut slots = TrySlots::new();
+        for name in names {
+            let tag = self.build_entry_alloca(builder, i8_type.into());
+            let mut slot = TrySlot {
+                tag,
+                slots: Vec::new(),
+            };
+            self.store_try_slot(builder, module, &mut slot, self.scope[&name].clone());
+            slots.insert(name, slot);
+        }
+        slots
+    }
+
+    /// an alloca in the entry block of the current function, so loops
+    /// don't grow the stack
+    fn build_entry_alloca<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        ir_type: BasicTypeEnum<'a>,
+    ) -> PointerValue<'a> {
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let entry = func.get_first_basic_block().unwrap();
+        let alloca = func.get_type().get_context().create_builder();
+        match entry.get_first_instruction() {
+            Some(x) => alloca.position_before(&x),
+            None => alloca.position_at_end(entry),
+        }
+        alloca.build_alloca(ir_type, "").unwrap()
+    }
+
+    /// writes val to the slot for its type and marks that slot as the
+    /// current one
+    fn store_try_slot<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        slot: &mut TrySlot<'a>,
+        val: (Value<'a>, DataType),
+    ) {
+        let n = match slot.slots.iter().position(|x| x.1 == val.1) {
+            Some(n) => n,
+            None => {
+                let ir_type = val.1.get_ir_value_type(module).try_into().unwrap();
+                let ptr = self.build_entry_alloca(builder, ir_type);
+                slot.slots.push((ptr, val.1.clone()));
+                slot.slots.len() - 1
+            }
+        };
+        let val = BasicValueEnum::try_from(val.0).unwrap();
+        let store = builder.build_store(slot.slots[n].0, val).unwrap();
+        let _ = store.set_volatile(true);
+        let tag = module.get_context().i8_type().const_int(n as u64, false);
+        let store = builder.build_store(slot.tag, tag).unwrap();
+        let _ = store.set_volatile(true);
+    }
+
+    /// updates every try slot of a variable that is assigned
+    fn compile_try_assign<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &Symbol,
+        val: &(Value<'a>, DataType),
+    ) {
+        let mut try_slots = std::mem::take(&mut self.try_slots);
+        for slots in try_slots.iter_mut() {
+            if let Some(slot) = slots.get_mut(name) {
+                self.store_try_slot(builder, module, slot, val.clone());
+            }
+        }
+        self.try_slots = try_slots;
+    }
+
+    /// puts the values last written to the slots in scope, a variable
+    /// assigned with different types is boxed
+    fn load_try_slots<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        slots: &TrySlots<'a>,
+    ) {
+        let context = module.get_context();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let gc = FarneseGC::new();
+        let mut names = slots.keys().collect::<Vec<_>>();
+        names.sort_by(|a, b| a.name().cmp(b.name()));
+        for name in names {
+            let load = |ptr: PointerValue<'a>| {
+                let val = builder.build_load(ptr, "").unwrap();
+                let _ = val.as_instruction_value().unwrap().set_volatile(true);
+                val
+            };
+            let slot = &slots[name];
+            let val = match slot.slots.as_slice() {
+                [(ptr, datatype)] => (load(*ptr).into(), datatype.clone()),
+                _ => {
+                    let current = builder.get_insert_block().unwrap();
+                    let tag = load(slot.tag).into_int_value();
+                    let other = context.append_basic_block(func, "");
+                    let mut cases = Vec::new();
+                    let mut branches = Vec::new();
+                    for (n, (ptr, datatype)) in slot.slots.iter().enumerate() {
+                        let block = context.append_basic_block(func, "");
+                        builder.position_at_end(block);
+                        branches.push((vec![(load(*ptr).into(), datatype.clone())], block));
+                        cases.push((tag.get_type().const_int(n as u64, false), block));
+                    }
+                    builder.position_at_end(current);
+                    let _ = builder.build_switch(tag, other, &cases);
+                    builder.position_at_end(other);
+                    let _ = builder.build_unreachable();
+                    self.compile_join(builder, module, branches).pop().unwrap()
+                }
+            };
+            // the catch dropped the roots made in the try
+            if val.1.is_gc_tracked() {
+                gc.emit_ir_push_root(builder, module, val.0.into_pointer_value());
+            }
+            self.scope.insert(name.clone(), val);
+        }
+    }
+
+    /// a throw never returns, code following it goes into a block
+    /// without predecessors
+    fn compile_after_throw<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+    ) -> (Value<'a>, DataType) {
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let block = module.get_context().append_basic_block(func, "");
+        builder.position_at_end(block);
+        let datatype = module.get_type("Nothing").clone();
+        (Primitive::Nothing.emit_ir_value(module).into(), datatype)
+    }
+
+    /// div and rem of two integers, throws a DivideError for a zero divisor
+    fn compile_int_division<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        arg_vals: &[(Value<'a>, DataType)],
+    ) -> (Value<'a>, DataType) {
+        let (lhs, rhs) = (&arg_vals[0], &arg_vals[1]);
+        let datatype = match lhs.1 == rhs.1 {
+            true => lhs.1.clone(),
+            false => self.promote_type(module, (&lhs.0, &lhs.1), (&rhs.0, &rhs.1)),
+        };
+        let x = self
+            .convert(builder, module, lhs.clone(), &datatype)
+            .into_int_value();
+        let y = self
+            .convert(builder, module, rhs.clone(), &datatype)
+            .into_int_value();
+
+        let context = module.get_context();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let error = context.append_basic_block(func, "");
+        let ok = context.append_basic_block(func, "");
+        let is_zero = builder
+            .build_int_compare(IntPredicate::EQ, y, y.get_type().const_zero(), "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_zero, error, ok);
+
+        builder.position_at_end(error);
+        let exception = FarneseException::new();
+        let e = exception.emit_ir_new(builder, module, "DivideError", &[]);
+        exception.emit_ir_throw(builder, module, e);
+
+        builder.position_at_end(ok);
+        let is_signed = !Self::is_unsigned(&datatype);
+        let val = match (name, is_signed) {
+            ("div", true) => builder.build_int_signed_div(x, y, ""),
+            ("div", false) => builder.build_int_unsigned_div(x, y, ""),
+            (_, true) => builder.build_int_signed_rem(x, y, ""),
+            (_, false) => builder.build_int_unsigned_rem(x, y, ""),
+        };
+        (val.unwrap().into(), datatype)
+    }
+
+    /// the default constructor of a struct, arguments are converted
+    /// to the field types
+    fn compile_new<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        arg_vals: &[(Value<'a>, DataType)],
+    ) -> (Value<'a>, DataType) {
+        let datatype = module.get_type(name).clone();
+        if arg_vals.len() != datatype.field_types.len() {
+            let signature = arg_vals
+                .iter()
+                .map(|x| format!("::{}", x.1.full_name()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            panic!("MethodError: no method matching {}({})", name, signature);
+        }
+        let vals = arg_vals
+            .iter()
+            .zip(datatype.field_types.iter())
+            .map(|(val, field_type)| {
+                let val = self.convert(builder, module, val.clone(), field_type);
+                BasicValueEnum::try_from(val).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let ptr = datatype.emit_ir_new(builder, module, &vals);
+        (ptr.into(), datatype)
+    }
+
+    /// reads a field, boxed values are matched against every struct
+    /// type with such a field at runtime
+    fn compile_getfield<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        (val, datatype): (Value<'a>, DataType),
+        field: &str,
+    ) -> (Value<'a>, DataType) {
+        if !datatype.is_abstract {
+            let ptr = match datatype.is_struct() {
+                true => val.into_pointer_value(),
+                false => panic!("type {} has no field {}", datatype.full_name(), field),
+            };
+            return match datatype.emit_ir_getfield(builder, ptr, field) {
+                Some((val, datatype)) => (val.into(), datatype),
+                None => panic!("type {} has no field {}", datatype.full_name(), field),
+            };
+        }
+
+        let context = module.get_context();
+        let box_helper = FarneseBox::new();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let ptr = val.into_pointer_value();
+        let tag = box_helper.emit_ir_typeof(builder, ptr);
+        let mut datatypes = module
+            .get_types()
+            .values()
+            .filter(|x| x.is_struct() && module.is_subtype(x, &datatype))
+            .filter(|x| x.field_names.iter().any(|y| y.name() == field))
+            .collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+
+        let mut branches = Vec::new();
+        for struct_type in datatypes {
+            let found = context.append_basic_block(func, "");
+            let next = context.append_basic_block(func, "");
+            let is_type = builder
+                .build_int_compare(
+                    IntPredicate::EQ,
+                    tag,
+                    struct_type.emit_ir_object(module),
+                    "",
+                )
+                .unwrap();
+            let _ = builder.build_conditional_branch(is_type, found, next);
+
+            builder.position_at_end(found);
+            let struct_ptr = box_helper
+                .emit_ir_unbox(builder, module, ptr, struct_type)
+                .into_pointer_value();
+            let (val, field_type) = struct_type
+                .emit_ir_getfield(builder, struct_ptr, field)
+                .unwrap();
+            branches.push((
+                vec![(val.into(), field_type)],
+                builder.get_insert_block().unwrap(),
+            ));
+            builder.position_at_end(next);
+        }
+
+        let exception = FarneseException::new();
+        let e = exception.emit_ir_field_error(builder, module, tag, field);
+        exception.emit_ir_throw(builder, module, e);
+        self.compile_join(builder, module, branches).pop().unwrap()
+    }
+
+    fn int_predicate(op: Operator, is_signed: bool) -> IntPredicate {
+        match (op, is_signed) {
+            (Operator::Equal | Operator::EqualEqual, _) => IntPredicate::EQ,
+            (Operator::Greater, true) => IntPredicate::SGT,
+            (Operator::Greater, false) => IntPredicate::UGT,
+            (Operator::GreaterEqual, true) => IntPredicate::SGE,
+            (Operator::GreaterEqual, false) => IntPredicate::UGE,
+            (Operator::Less, true) => IntPredicate::SLT,
+            (Operator::Less, false) => IntPredicate::ULT,
+            (Operator::LessEqual, true) => IntPredicate::SLE,
+            (Operator::LessEqual, false) => IntPredicate::ULE,
+            (Operator::NotEqual, _) => IntPredicate::NE,
+            _ => todo!("Unsupported comparison {:?}", op),
+        }
+    }
+
+    /// NaN compares unequal to everything, hence UNE for !=
+    fn float_predicate(op: Operator) -> FloatPredicate {
+        match op {
+            Operator::Equal | Operator::EqualEqual => FloatPredicate::OEQ,
+            Operator::Greater => FloatPredicate::OGT,
+            Operator::GreaterEqual => FloatPredicate::OGE,
+            Operator::Less => FloatPredicate::OLT,
+            Operator::LessEqual => FloatPredicate::OLE,
+            Operator::NotEqual => FloatPredicate::UNE,
+            _ => todo!("Unsupported comparison {:?}", op),
+        }
+    }
+
+    /// string(xs...) converts each argument and concatenates them,
+    /// this is also what string interpolation lowers to
+    fn compile_string<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        args: &[(Value<'a>, DataType)],
+    ) -> (Value<'a>, DataType) {
+        let string = FarneseString::new();
+        let int64 = module.get_type("Int64").clone();
+        let float64 = module.get_type("Float64").clone();
+        let pieces = args
+            .iter()
+            .map(|x| match (x.0, x.1.name().name()) {
+                (_, "String") => x.0.into_pointer_value(),
+                (_, "Bool") => {
+                    let true_str = string.emit_ir_literal(module, "true");
+                    let false_str = string.emit_ir_literal(module, "false");
+                    builder
+                        .build_select(x.0.into_int_value(), true_str, false_str, "")
+                        .unwrap()
+                        .into_pointer_value()
+                }
+                (_, "Nothing") => string.emit_ir_literal(module, "nothing"),
+                (_, "Char") => {
+                    FarneseChar::new().emit_ir_to_string(builder, module, x.0.into_int_value())
+                }
+                (Value::IntValue(_), "Int128" | "UInt128") => panic!(
+                    "MethodError: no method matching string(::{})",
+                    x.1.full_name()
+                ),
+                (Value::IntValue(_), _) => {
+                    let val = self.convert(builder, module, x.clone(), &int64);
+                    string.emit_ir_from_int64(builder, module, val.into_int_value())
+                }
+                (Value::FloatValue(_), _) => {
+                    let val = self.convert(builder, module, x.clone(), &float64);
+                    string.emit_ir_from_float64(builder, module, val.into_float_value())
+                }
+                _ => panic!(
+                    "MethodError: no method matching string(::{})",
+                    x.1.full_name()
+                ),
+            })
+            .collect::<Vec<_>>();
+        let result = match pieces.split_first() {
+            Some((first, rest)) => rest.iter().fold(*first, |acc, x| {
+                string.emit_ir_concat(builder, module, acc, *x)
+            }),
+            None => string.emit_ir_literal(module, ""),
+        };
+        (result.into(), module.get_type("String").clone())
+    }
+
+    pub fn compile_expr<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        expr: Node,
+    ) {
+        // variables and assigned values are rooted already and
+        // literals are static
+        let is_fresh = !matches!(
+            expr,
+            Node::AssignmentExpr { .. }
+                | Node::Primitive(_)
+                | Node::SetIndexExpr { .. }
+                | Node::Symbol(_)
+        );
+        let depth = self.stack.len();
+        self.compile_node(builder, module, expr);
+        // new heap values are rooted until the function returns, the
+        // rest of the statement might call into a safepoint
+        match self.stack.last() {
+            Some((val, datatype))
+                if is_fresh && self.stack.len() > depth && datatype.is_gc_tracked() =>
+            {
+                FarneseGC::new().emit_ir_push_root(builder, module, val.into_pointer_value());
+            }
+            _ => {}
+        }
+    }
+
+    fn compile_node<'b>(&mut self, builder: &'b Builder<'a>, module: &mut Module<'a>, expr: Node) {
+        match expr {
+            Node::AbstractType { name, supertype } => {
+                self.check_supertype(module, &name, &supertype);
+                let mut datatype = DataType::new_abstract_type(&name, &supertype);
+                datatype.module = module.name();
+                if !self.is_redefinition(module, &datatype) {
+                    datatype.emit_ir_type(module);
+                    module.insert_type(datatype);
+                }
+                self.push_nothing(module);
+            }
+            Node::ArrayExpr { elements } => self.compile_array_expr(builder, module, *elements),
+            // the value of an assignment is the value assigned, so
+            // a = b = 0 sets both
+            Node::AssignmentExpr {
+                identifier,
+                datatype,
+                value,
+            } => {
+                self.compile_expr(builder, module, *value);
+                let val = self.stack.pop().unwrap();
+                let target = Node::Symbol(identifier);
+                self.compile_assign(builder, module, target, datatype.as_deref(), val.clone());
+                self.stack.push(val);
+            }
+            Node::BinaryExpr { op, lhs, rhs } => {
+                self.compile_binary_expr(&builder, module, op, *lhs, *rhs)
+            }
+            Node::Block { exprs } => {
+                let val = self.compile_block(builder, module, &exprs);
+                self.stack.push(val);
+            }
+            Node::Broadcast { expr } => {
+                let val = self.compile_broadcast(builder, module, *expr);
+                self.stack.push(val);
+            }
+            Node::ConstExpr { expr } => match *expr {
+                Node::AssignmentExpr {
+                    identifier,
+                    datatype,
+                    value,
+                } => {
+                    assert!(
+                        self.is_top_level(builder, module),
+                        "syntax: unsupported `const` declaration on local variable {}",
+                        identifier
+                    );
+                    self.compile_expr(builder, module, *value);
+                    let val = self.stack.pop().unwrap();
+                    let datatype = datatype.as_deref();
+                    self.compile_global(builder, module, &identifier, datatype, val, true);
+                    self.push_nothing(module);
+                }
+                _ => panic!("syntax: expected assignment after \"const\""),
+            },
+            Node::Empty => {
+                // do nothing, comments don't have a value
+            }
+            Node::Exports { symbols } => {
+                for export in symbols.iter() {
+                    // module.push_export(export);
+                    match export {
+                        Node::Symbol(x) => module.push_export(Symbol::new(x)),
+                        _ => todo!(),
+                    }
+                }
+                self.push_nothing(module);
+            }
+            Node::Function {
+                name,
+                args,
+                return_type,
+                body,
+            } => {
+                self.compile_function(module, &name, &args, &return_type, &body);
+                self.push_nothing(module);
+            }
+            Node::GlobalExpr { expr } => match *expr {
+                Node::AssignmentExpr {
+                    identifier,
+                    datatype,
+                    value,
+                } => {
+                    self.compile_expr(builder, module, *value);
+                    let val = self.stack.pop().unwrap();
+                    let datatype = datatype.as_deref();
+                    self.compile_global(builder, module, &identifier, datatype, val, false);
+                    self.push_nothing(module);
+                }
+                expr => self.compile_expr(builder, module, expr),
+            },
+            Node::MultipleAssignment { targets, values } => {
+                self.compile_multiple_assignment(builder, module, *targets, *values);
+                self.push_nothing(module);
+            }
+            Node::IfExpr {
+                condition,
+                if_block,
+                else_block,
+            } => {
+                let val = self.compile_if(builder, module, *condition, &if_block, &else_block);
+                self.stack.push(val);
+            }
+            Node::ImportExpr { path, names } => {
+                self.compile_import(module, &path, &names, false);
+                self.push_nothing(module);
+            }
+            Node::IndexExpr { array, indices } => {
+                let (array, ptr, index) =
+                    self.compile_index_expr(builder, module, *array, *indices);
+                let val = array.emit_ir_getindex(builder, module, ptr, index);
+                self.stack.push((val.into(), array.eltype(module)));
+            }
+            Node::Let { bindings, exprs } => {
+                let val = self.compile_let(builder, module, &bindings, &exprs);
+                self.stack.push(val);
+            }
+            Node::Macro { name, args, body } => {
+                assert!(
+                    self.is_top_level(builder, module),
+                    "syntax: macro definition not allowed inside a local scope"
+                );
+                self.expander.define(&module.name(), &name, &args, &body);
+                self.push_nothing(module);
+            }
+            Node::MacroCall { name, args } => {
+                self.compile_macro_call(builder, module, &name, *args)
+            }
+            Node::MethodCall { name, args } if name == "include" => {
+                let file_name = match args.as_slice() {
+                    [Node::Primitive(ast::Primitive::String(x))] => x.clone(),
+                    _ => panic!("MethodError: include expects a single String literal"),
+                };
+                self.include(module, &file_name);
+                self.push_nothing(module);
+            }
+            Node::MethodCall { name, args } => {
+                self.compile_method_call(&builder, module, &name, args)
+            }
+            Node::Interpolation { .. } | Node::InterpolatedAssignment { .. } => {
+                panic!("syntax: \"$\" expression outside quote")
+            }
+            Node::Quote { exprs } => {
+                let result = self.compile_quote(builder, module, exprs[0].clone());
+                self.stack.push(result);
+            }
+            Node::ParenthesesExpr { expr } => {
+                self.compile_expr(&builder, module, *expr);
+            }
+            Node::Primitive(x) => {
+                let x: Primitive = x.into();
+                // let val = x.emit_ir_value(module);
+                let val = match x {
+                    Primitive::String(_) => x.emit_ir_alloca(&builder, module).into(),
+                    _ => x.emit_ir_value(module),
+                };
+                let datatype = x.get_datatype();
+                self.stack.push((val.into(), datatype));
+            }
+            Node::PrimitiveType {
+                name,
+                supertype,
+                bits,
+            } => {
+                self.check_supertype(module, &name, &supertype);
+                assert!(
+                    bits > 0 && bits % 8 == 0,
+                    "invalid number of bits in primitive type {}",
+                    name
+                );
+                let mut datatype = DataType::new_primitive_type(&name, &supertype, bits);
+                datatype.module = module.name();
+                // LLVM only has floats of these sizes
+                let is_float = module
+                    .get_types()
+                    .get(&Symbol::new("AbstractFloat"))
+                    .is_some_and(|x| module.is_subtype(&datatype, x));
+                assert!(
+                    !is_float || matches!(bits, 16 | 32 | 64),
+                    "invalid number of bits in primitive type {}: floating point types have 16, 32 or 64 bits",
+                    name
+                );
+                if !self.is_redefinition(module, &datatype) {
+                    datatype.emit_ir_type(module);
+                    module.insert_type(datatype);
+                }
+                self.push_nothing(module);
+            }
+            Node::SetIndexExpr {
+                array,
+                indices,
+                value,
+            } => {
+                self.compile_expr(builder, module, *value);
+                let val = self.stack.pop().unwrap();
+                let target = Node::IndexExpr { array, indices };
+                self.compile_assign(builder, module, target, None, val.clone());
+                self.stack.push(val);
+            }
+            Node::StructType {
+                name,
+                supertype,
+                is_mutable,
+                field_names,
+                field_types,
+            } => {
+                self.check_supertype(module, &name, &supertype);
+                // hack for now TODO refactor this
+                let field_names = field_names
+                    .iter()
+                    .map(|x| Symbol::new(x))
+                    .collect::<Vec<_>>();
+                let field_types = Box::new(
+                    field_types
+                        .iter()
+                        .map(|x| module.get_type(x).clone())
+                        .collect(),
+                );
+                let mut datatype = DataType::new(
+                    Symbol::new(&name),
+                    Symbol::new(&supertype),
+                    false,
+                    is_mutable,
+                    false,
+                    field_names,
+                    field_types,
+                );
+                datatype.module = module.name();
+                if !self.is_redefinition(module, &datatype) {
+                    datatype.emit_ir_type(module);
+                    module.insert_type(datatype);
+                }
+                self.push_nothing(module);
+            }
+            Node::Symbol(x) => {
+                // variables first, everything else is a symbol literal
+                let x = Symbol::new(&x);
+                if let Some(val) = self.scope.get(&x) {
+                    self.stack.push(val.clone());
+                    return;
+                }
+                // field access on a variable, e.g. e.msg
+                if let Some((root, fields)) = x.name().split_once('.')
+                    && let Some(val) = self.scope.get(&Symbol::new(root)).cloned()
+                {
+                    let val = fields.split('.').fold(val, |val, field| {
+                        self.compile_getfield(builder, module, val, field)
+                    });
+                    self.stack.push(val);
+                    return;
+                }
+                if let Some(val) = self.compile_global_load(builder, module, &x) {
+                    self.stack.push(val);
+                    return;
+                }
+                // and on a global
+                if let Some((root, fields)) = x.name().split_once('.')
+                    && let Some(val) = self.compile_global_load(builder, module, &Symbol::new(root))
+                {
+                    let val = fields.split('.').fold(val, |val, field| {
+                        self.compile_getfield(builder, module, val, field)
+                    });
+                    self.stack.push(val);
+                    return;
+                }
+                if let Some(datatype) = module.get_types().get(&x) {
+                    let val = datatype.emit_ir_object(module);
+                    self.stack
+                        .push((val.into(), module.get_type("DataType").clone()));
+                    return;
+                }
+                let val = x.emit_ir_alloca(&builder, module);
+                let datatype = self
+                    .modules
+                    .get(&Symbol::new("Core"))
+                    .unwrap()
+                    .get_type("Symbol");
+                self.stack.push((val.into(), datatype.clone()));
+            }
+            Node::TryExpr {
+                try_block,
+                catch_name,
+                catch_block,
+                finally_block,
+            } => self.compile_try(
+                builder,
+                module,
+                &try_block,
+                catch_name,
+                catch_block.as_deref(),
+                finally_block.as_deref(),
+            ),
+            Node::UsingExpr { path, names } => {
+                self.compile_import(module, &path, &names, true);
+                self.push_nothing(module);
+            }
+            _ => todo!("Unsupported type {:?}", expr),
+        }
+    }
+
+    /// f.(x) and x .+ y apply a call elementwise over arrays, nested
+    /// dotted calls fuse into one loop so no temporary arrays are made.
+    /// Everything else is evaluated once before the loop and values
+    /// other than arrays are the same for every element. x .= y writes
+    /// the elements into x instead of a new array
+    fn compile_broadcast<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        expr: Node,
+    ) -> (Value<'a>, DataType) {
+        let (dest, expr) = match expr {
+            Node::AssignmentExpr {
+                identifier, value, ..
+            } => {
+                self.compile_expr(builder, module, Node::Symbol(identifier));
+                let dest = self.stack.pop().unwrap();
+                assert!(
+                    dest.1.name().name() == "Array",
+                    "MethodError: no method matching copyto!({}, ...)",
+                    dest.1.full_name()
+                );
+                (Some(dest), *value)
+            }
+            Node::SetIndexExpr { .. } => {
+                panic!("broadcast assignment to an indexed array is not supported yet")
+            }
+            expr => (
+                None,
+                Node::Broadcast {
+                    expr: Box::new(expr),
+                },
+            ),
+        };
+        let mut args = Vec::new();
+        let kernel = self.compile_broadcast_args(builder, module, expr, &mut args);
+        let names = (1..=args.len())
+            .map(|n| Symbol::new(&format!("##broadcast#{}", n)))
+            .collect::<Vec<_>>();
+
+        let arrays = dest
+            .iter()
+            .chain(args.iter())
+            .filter(|x| x.1.name().name() == "Array")
+            .map(|x| (Array::new(x.1.clone()), x.0.into_pointer_value()))
+            .collect::<Vec<_>>();
+        // without arrays it's a plain call
+        if arrays.is_empty() {
+            for (name, arg) in names.iter().zip(args) {
+                self.scope.insert(name.clone(), arg);
+            }
+            self.compile_expr(builder, module, kernel);
+            for name in names.iter() {
+                self.scope.remove(name);
+            }
+            return self.stack.pop().unwrap();
+        }
+
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let length = arrays[0].0.emit_ir_length(builder, arrays[0].1);
+        for (array, ptr) in arrays.iter().skip(1) {
+            let other = array.emit_ir_length(builder, *ptr);
+            let mismatch = context.append_basic_block(func, "");
+            let ok = context.append_basic_block(func, "");
+            let is_equal = builder
+                .build_int_compare(IntPredicate::EQ, length, other, "")
+                .unwrap();
+            let _ = builder.build_conditional_branch(is_equal, ok, mismatch);
+
+            builder.position_at_end(mismatch);
+            let string = module.get_type("String").clone();
+            let int64 = module.get_type("Int64").clone();
+            let literal = |x: &str| {
+                let val = FarneseString::new().emit_ir_literal(module, x);
+                (val.into(), string.clone())
+            };
+            let msg = self.compile_string(
+                builder,
+                module,
+                &[
+                    literal("arrays could not be broadcast to a common size; got a dimension with lengths "),
+                    (length.into(), int64.clone()),
+                    literal(" and "),
+                    (other.into(), int64),
+                ],
+            );
+            let exception = FarneseException::new();
+            let e = exception.emit_ir_new(
+                builder,
+                module,
+                "DimensionMismatch",
+                &[msg.0.try_into().unwrap()],
+            );
+            exception.emit_ir_throw(builder, module, e);
+            builder.position_at_end(ok);
+        }
+
+        // the result array is allocated once the element type is known,
+        // in a block ahead of the loop
+        let alloc = context.append_basic_block(func, "");
+        let cond = context.append_basic_block(func, "");
+        let body = context.append_basic_block(func, "");
+        let done = context.append_basic_block(func, "");
+        let _ = builder.build_unconditional_branch(alloc);
+
+        builder.position_at_end(cond);
+        let phi = builder.build_phi(i64_type, "").unwrap();
+        let index = phi.as_basic_value().into_int_value();
+        let in_range = builder
+            .build_int_compare(IntPredicate::SLE, index, length, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(in_range, body, done);
+
+        // roots made by the call only live for one element
+        builder.position_at_end(body);
+        let gc = FarneseGC::new();
+        let depth = gc.emit_ir_frame(builder, module);
+        for (name, arg) in names.iter().zip(args) {
+            let val = match arg.1.name().name() {
+                "Array" => {
+                    let array = Array::new(arg.1);
+                    let ptr = arg.0.into_pointer_value();
+                    let ptr = array.emit_ir_element_ptr(builder, module, ptr, index, false);
+                    (
+                        builder.build_load(ptr, "").unwrap().into(),
+                        array.eltype(module),
+                    )
+                }
+                _ => arg,
+            };
+            self.scope.insert(name.clone(), val);
+        }
+        self.compile_expr(builder, module, kernel);
+        let val = self.stack.pop().unwrap();
+        for name in names.iter() {
+            self.scope.remove(name);
+        }
+        let body_end = builder.get_insert_block().unwrap();
+
+        let (array, ptr) = match dest {
+            Some((ptr, datatype)) => (Array::new(datatype), ptr.into_pointer_value()),
+            None => {
+                builder.position_at_end(alloc);
+                let array = Array::new(DataType::new_array_type(&val.1, 1));
+                let ptr = array.emit_ir_new(builder, module, length);
+                gc.emit_ir_push_root(builder, module, ptr);
+                (array, ptr)
+            }
+        };
+        builder.position_at_end(alloc);
+        let _ = builder.build_unconditional_branch(cond);
+
+        builder.position_at_end(body_end);
+        let val = self.convert(builder, module, val, &array.eltype(module));
+        let element = array.emit_ir_element_ptr(builder, module, ptr, index, false);
+        let _ = builder.build_store(element, BasicValueEnum::try_from(val).unwrap());
+        gc.emit_ir_pop_frame(builder, module, depth);
+        gc.emit_ir_safepoint(builder, module);
+        let next = builder
+            .build_int_add(index, i64_type.const_int(1, false), "")
+            .unwrap();
+        let _ = builder.build_unconditional_branch(cond);
+        let body_end = builder.get_insert_block().unwrap();
+        phi.add_incoming(&[(&i64_type.const_int(1, false), alloc), (&next, body_end)]);
+
+        builder.position_at_end(done);
+        (ptr.into(), array.datatype().clone())
+    }
+
+    /// the elementwise call of a broadcast, with the arguments that
+    /// aren't dotted themselves replaced by the names their values
+    /// are bound to in the loop
+    fn compile_broadcast_args<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        expr: Node,
+        args: &mut Vec<(Value<'a>, DataType)>,
+    ) -> Node {
+        let mut lower = |x: Node| Box::new(self.compile_broadcast_args(builder, module, x, args));
+        match expr {
+            Node::Broadcast { expr } => match *expr {
+                Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
+                    op,
+                    lhs: lower(*lhs),
+                    rhs: lower(*rhs),
+                },
+                Node::MethodCall { name, args } => Node::MethodCall {
+                    name,
+                    args: Box::new(args.into_iter().map(|x| *lower(x)).collect()),
+                },
+                x => panic!("syntax: invalid broadcast {}", macros::unparse(&x)),
+            },
+            Node::ParenthesesExpr { expr } if matches!(*expr, Node::Broadcast { .. }) => {
+                *lower(*expr)
+            }
+            // literals are the same constant for every element
+            Node::Primitive(_) => expr,
+            expr => {
+                self.compile_expr(builder, module, expr);
+                args.push(self.stack.pop().unwrap());
+                Node::Symbol(format!("##broadcast#{}", args.len()))
+            }
+        }
+    }
+
+    /// reinterprets the bits of a primitive value as another primitive
+    /// type of the same size, Bool is an i1 but takes up a byte. Boxed
+    /// values are checked when the code runs
+    fn compile_bitcast<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: (Value<'a>, DataType),
+        datatype: &DataType,
+    ) -> Value<'a> {
+        assert!(
+            datatype.is_primitive,
+            "bitcast: target type not a leaf primitive type"
+        );
+        if val.1.is_abstract {
+            return self.compile_boxed_bitcast(builder, module, val, datatype);
+        }
+        // string literals are typed as primitives, the declared type isn't
+        let val_type = module.get_types().get(val.1.name());
+        let val_type = val_type.filter(|x| x.is_primitive);
+        let val_type = val_type.expect("bitcast: value not a primitive type");
+        assert!(
+            val_type.bits == datatype.bits,
+            "bitcast: argument size does not match size of target type"
+        );
+        let context = module.get_context();
+        let int_type = context.custom_width_int_type(datatype.bits);
+        let val: BasicValueEnum<'a> = val.0.try_into().unwrap();
+        let val = match val {
+            BasicValueEnum::IntValue(x) if x.get_type().get_bit_width() == 1 => {
+                builder.build_int_z_extend(x, int_type, "").unwrap().into()
+            }
+            _ => val,
+        };
+        let ir_type: BasicTypeEnum<'a> = datatype.get_ir_value_type(module).try_into().unwrap();
+        let val = match ir_type {
+            BasicTypeEnum::IntType(x) if x.get_bit_width() == 1 => {
+                let val = builder.build_bit_cast(val, int_type, "").unwrap();
+                builder
+                    .build_int_truncate(val.into_int_value(), x, "")
+                    .unwrap()
+                    .into()
+            }
+            _ => builder.build_bit_cast(val, ir_type, "").unwrap(),
+        };
+        val.into()
+    }
+
+    /// unboxes the value by switching on its tag, one branch per
+    /// primitive type of the right size it could hold
+    fn compile_boxed_bitcast<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: (Value<'a>, DataType),
+        datatype: &DataType,
+    ) -> Value<'a> {
+        let context = module.get_context();
+        let box_helper = FarneseBox::new();
+        let ptr = val.0.into_pointer_value();
+        let tag = box_helper.emit_ir_typeof(builder, ptr);
+
+        let mut datatypes = module
+            .get_types()
+            .values()
+            .filter(|x| x.is_primitive && !x.is_abstract && x.bits == datatype.bits)
+            .filter(|x| module.is_subtype(x, &val.1))
+            .collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let mut results = Vec::new();
+        for val_type in datatypes {
+            let found = context.append_basic_block(func, "");
+            let next = context.append_basic_block(func, "");
+            let is_type = builder
+                .build_int_compare(IntPredicate::EQ, tag, val_type.emit_ir_object(module), "")
+                .unwrap();
+            let _ = builder.build_conditional_branch(is_type, found, next);
+
+            builder.position_at_end(found);
+            let val = box_helper.emit_ir_unbox(builder, module, ptr, val_type);
+            let val =
+                self.compile_bitcast(builder, module, (val.into(), val_type.clone()), datatype);
+            results.push((
+                vec![(val, datatype.clone())],
+                builder.get_insert_block().unwrap(),
+            ));
+            builder.position_at_end(next);
+        }
+
+        // anything else isn't a primitive of the same size
+        let exception = FarneseException::new();
+        let msg = "bitcast: argument size does not match size of target type";
+        let msg = FarneseString::new().emit_ir_literal(module, msg);
+        let e = exception.emit_ir_new(builder, module, "ErrorException", &[msg.into()]);
+        exception.emit_ir_throw(builder, module, e);
+
+        self.compile_join(builder, module, results).pop().unwrap().0
+    }
+
+    /// the supertype of a new type has to be an abstract type
+    fn check_supertype(&self, module: &Module<'a>, name: &str, supertype: &str) {
+        match module.get_types().get(&Symbol::new(supertype)) {
+            Some(x) if x.is_abstract => {}
+            Some(_) => panic!(
+                "invalid subtyping in definition of {}: can only subtype abstract types.",
+                name
+            ),
+            None => panic!("UndefVarError: `{}` not defined", supertype),
+        }
+    }
+
+    /// whether the module already defines the type, which is only
+    /// allowed when nothing changes. A type of the same name from
+    /// another module, Core included, is shadowed
+    fn is_redefinition(&self, module: &Module<'a>, datatype: &DataType) -> bool {
+        let name = datatype.name();
+        match module.get_types().get(name) {
+            Some(x) if x.module == module.name() => {
+                let field_types = |x: &DataType| {
+                    x.field_types
+                        .iter()
+                        .map(|x| x.full_name())
+                        .collect::<Vec<_>>()
+                };
+                let is_same = x.is_abstract == datatype.is_abstract
+                    && x.is_mutable == datatype.is_mutable
+                    && x.is_primitive == datatype.is_primitive
+                    && x.bits == datatype.bits
+                    && x.supertype == datatype.supertype
+                    && x.field_names == datatype.field_names
+                    && field_types(x) == field_types(datatype);
+                assert!(
+                    is_same,
+                    "invalid redefinition of constant {}.{}",
+                    module.name(),
+                    name
+                );
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// stores a value in a variable, a field or an element, variables
+    /// outside of functions are globals
+    fn compile_assign<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        target: Node,
+        datatype: Option<&str>,
+        val: (Value<'a>, DataType),
+    ) {
+        match target {
+            Node::IndexExpr { array, indices } => {
+                let (array, ptr, index) =
+                    self.compile_index_expr(builder, module, *array, *indices);
+                let val = self.convert(builder, module, val, &array.eltype(module));
+                array.emit_ir_setindex(builder, module, ptr, index, val.try_into().unwrap());
+            }
+            // p.x = 1 sets the field x of p
+            Node::Symbol(name) if name.contains('.') => {
+                let (object, field) = name.rsplit_once('.').unwrap();
+                self.compile_expr(builder, module, Node::Symbol(object.to_string()));
+                let object = self.stack.pop().unwrap();
+                self.compile_setfield(builder, module, object, field, val);
+            }
+            Node::Symbol(name) if self.is_top_level(builder, module) => {
+                self.compile_global(builder, module, &name, datatype, val, false);
+            }
+            Node::Symbol(name) => {
+                let name = Symbol::new(&name);
+                // declared types stick, so x::Any stays boxed on reassignment
+                let datatype = match (datatype, self.scope.get(&name)) {
+                    (Some(datatype), _) => Some(module.resolve_type(datatype)),
+                    (None, Some((_, datatype))) if datatype.is_abstract => Some(datatype.clone()),
+                    _ => None,
+                };
+                let val = match datatype {
+                    Some(datatype) => (self.convert(builder, module, val, &datatype), datatype),
+                    None => val,
+                };
+                self.compile_try_assign(builder, module, &name, &val);
+                self.scope.insert(name, val);
+            }
+            x => panic!(
+                "syntax: invalid assignment location \"{}\"",
+                macros::unparse(&x)
+            ),
+        }
+    }
+
+    /// every value is computed before anything is assigned, so a, b = b, a
+    /// swaps. A single array on the right is destructured by index
+    fn compile_multiple_assignment<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        targets: Vec<Node>,
+        values: Vec<Node>,
+    ) {
+        let mut vals = values
+            .into_iter()
+            .map(|x| {
+                self.compile_expr(builder, module, x);
+                self.stack.pop().unwrap()
+            })
+            .collect::<Vec<_>>();
+        if vals.len() == 1 && vals[0].1.name().name() == "Array" {
+            let (array, ptr) = vals.pop().map(|x| (Array::new(x.1), x.0)).unwrap();
+            let ptr = ptr.into_pointer_value();
+            let i64_type = module.get_context().i64_type();
+            for n in 1..=targets.len() {
+                let index = i64_type.const_int(n as u64, false);
+                let val = array.emit_ir_getindex(builder, module, ptr, index);
+                vals.push((val.into(), array.eltype(module)));
+            }
+        }
+        if vals.len() < targets.len() {
+            let types = vals
+                .iter()
+                .map(|x| x.1.full_name())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let types = match vals.len() {
+                1 => types,
+                _ => format!("Tuple{{{}}}", types),
+            };
+            panic!(
+                "BoundsError: attempt to access {} at index [{}]",
+                types,
+                vals.len() + 1
+            );
+        }
+        for (target, val) in targets.into_iter().zip(vals) {
+            self.compile_assign(builder, module, target, None, val);
+        }
+    }
+
+    /// sets a field of a mutable struct, boxed values are matched
+    /// against every struct type with such a field at runtime
+    fn compile_setfield<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        (object, datatype): (Value<'a>, DataType),
+        field: &str,
+        val: (Value<'a>, DataType),
+    ) {
+        let immutable_error = |datatype: &DataType| {
+            format!(
+                "setfield!: immutable struct of type {} cannot be changed",
+                datatype.full_name()
+            )
+        };
+        if !datatype.is_abstract {
+            let field_type = match datatype.is_struct() {
+                true => datatype.field_type(field),
+                false => None,
+            };
+            let Some(field_type) = field_type else {
+                panic!("type {} has no field {}", datatype.full_name(), field);
+            };
+            if !datatype.is_mutable {
+                panic!("{}", immutable_error(&datatype));
+            }
+            let val = self.convert(builder, module, val, field_type);
+            let ptr = object.into_pointer_value();
+            datatype.emit_ir_setfield(builder, ptr, field, val.try_into().unwrap());
+            return;
+        }
+
+        let context = module.get_context();
+        let box_helper = FarneseBox::new();
+        let exception = FarneseException::new();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let ptr = object.into_pointer_value();
+        let tag = box_helper.emit_ir_typeof(builder, ptr);
+        let mut datatypes = module
+            .get_types()
+            .values()
+            .filter(|x| x.is_struct() && module.is_subtype(x, &datatype))
+            .filter(|x| x.field_type(field).is_some())
+            .collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+
+        let mut branches = Vec::new();
+        for struct_type in datatypes {
+            let found = context.append_basic_block(func, "");
+            let next = context.append_basic_block(func, "");
+            let is_type = builder
+                .build_int_compare(
+                    IntPredicate::EQ,
+                    tag,
+                    struct_type.emit_ir_object(module),
+                    "",
+                )
+                .unwrap();
+            let _ = builder.build_conditional_branch(is_type, found, next);
+
+            builder.position_at_end(found);
+            if struct_type.is_mutable {
+                let struct_ptr = box_helper
+                    .emit_ir_unbox(builder, module, ptr, struct_type)
+                    .into_pointer_value();
+                let field_type = struct_type.field_type(field).unwrap();
+                let val = self.convert(builder, module, val.clone(), field_type);
+                struct_type.emit_ir_setfield(builder, struct_ptr, field, val.try_into().unwrap());
+                branches.push((vec![], builder.get_insert_block().unwrap()));
+            } else {
+                let msg =
+                    FarneseString::new().emit_ir_literal(module, &immutable_error(struct_type));
+                let e = exception.emit_ir_new(builder, module, "ErrorException", &[msg.into()]);
+                exception.emit_ir_throw(builder, module, e);
+            }
+            builder.position_at_end(next);
+        }
+
+        let e = exception.emit_ir_field_error(builder, module, tag, field);
+        exception.emit_ir_throw(builder, module, e);
+        let _ = self.compile_join(builder, module, branches);
+    }
+
+    /// defines or redefines a module level binding, values that are
+    /// only known at runtime get stored when the statement runs
+    fn compile_global<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        name: &str,
+        datatype: Option<&str>,
+        val: (Value<'a>, DataType),
+        is_const: bool,
+    ) {
+        let val = match datatype.map(|x| module.resolve_type(x)) {
+            Some(datatype) => (self.convert(builder, module, val, &datatype), datatype),
+            None => val,
+        };
+
+        let (global, val) = match module.get_module_global(name) {
+            Some(_) if is_const => {
+                panic!("cannot declare {} constant; it already has a value", name)
+            }
+            Some((_, _, true)) => panic!("invalid redefinition of constant {}", name),
+            Some((global, _, _)) if !self.is_own_global(module, &global, name) => {
+                panic!("cannot assign a value to imported variable {}", name)
+            }
+            Some((global, datatype, false)) => {
+                let ir_name = global.get_name().to_str().unwrap();
+                assert!(
+                    !self.untyped_globals.contains(ir_name)
+                        || datatype.is_abstract
+                        || val.1 == datatype,
+                    "cannot assign a value of type {} to global {} of type {}, declare it as {}::Any to hold values of different types",
+                    val.1.full_name(),
+                    ir_name,
+                    datatype.full_name(),
+                    name
+                );
+                (global, self.convert(builder, module, val, &datatype))
+            }
+            None => {
+                let global = module.insert_global(Symbol::new(name), val.1.clone(), is_const);
+                if datatype.is_none() {
+                    let ir_name = global.get_name().to_str().unwrap().to_owned();
+                    self.untyped_globals.insert(ir_name);
+                }
+                if val.1.is_gc_tracked() {
+                    FarneseGC::new().emit_ir_add_global(builder, module, global.as_pointer_value());
+                }
+                // the first value becomes the initializer when it is
+                // known at compile time
+                let val: BasicValueEnum<'a> = val.0.try_into().unwrap();
+                let is_constant = match val {
+                    BasicValueEnum::FloatValue(x) => x.is_const(),
+                    BasicValueEnum::IntValue(x) => x.is_const(),
+                    BasicValueEnum::PointerValue(x) => x.is_const(),
+                    BasicValueEnum::StructValue(x) => x.is_const(),
+                    _ => false,
+                };
+                if is_constant {
+                    global.set_initializer(&val);
+                    return;
+                }
+                global.set_constant(false);
+                (global, val.into())
+            }
+        };
+        let val: BasicValueEnum<'a> = val.try_into().unwrap();
+        let _ = builder.build_store(global.as_pointer_value(), val);
+    }
+
+    /// globals are named Module.name, anything else was imported
+    fn is_own_global(&self, module: &Module<'a>, global: &GlobalValue<'a>, name: &str) -> bool {
+        global.get_name().to_str().unwrap() == format!("{}.{}", module.name(), name)
+    }
+
+    /// top level statements of a module are compiled into its
+    /// Module.__init__ function, which runs before main
+    fn init_builder(&self, module: &Module<'a>) -> Builder<'a> {
+        let context = module.get_context();
+        let name = format!("{}.__init__", module.name());
+        let func = match module.module().get_function(&name) {
+            Some(x) => x,
+            None => {
+                let func = context.void_type().fn_type(&[], false);
+                let func = module.add_function(&name, func, None);
+                let entry = context.append_basic_block(func, "entry");
+                let builder = context.create_builder();
+                builder.position_at_end(entry);
+                FarneseException::new().emit_ir_enter(&builder, module, "top-level scope");
+                func
+            }
+        };
+        let builder = context.create_builder();
+        builder.position_at_end(init_block(func).unwrap());
+        builder
+    }
+
+    fn is_top_level(&self, builder: &Builder<'a>, module: &Module<'a>) -> bool {
+        if self.local_scopes > 0 {
+            return false;
+        }
+        let func = builder.get_insert_block().and_then(|x| x.get_parent());
+        func.map(|x| x.get_name().to_str().unwrap() == format!("{}.__init__", module.name()))
+            .unwrap_or(false)
+    }
+
+    /// closes off the __init__ of a module and defines the default show
+    /// of its structs once all of its top level statements have been compiled
+    fn finish_init(&self, module: &Module<'a>) {
+        // boxed structs are shown through their type object even if
+        // show was never called on them directly
+        let mut datatypes = module
+            .get_types()
+            .values()
+            .filter(|x| x.is_struct())
+            .collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+        for datatype in datatypes {
+            self.compile_default_show(module, datatype);
+        }
+
+        let name = format!("{}.__init__", module.name());
+        if let Some(block) = module.module().get_function(&name).and_then(init_block) {
+            let builder = module.create_builder();
+            builder.position_at_end(block);
+            // initializers run before main, so nothing is rooted yet
+            let depth = module.get_context().i64_type().const_zero();
+            FarneseGC::new().emit_ir_pop_frame(&builder, module, depth);
+            FarneseException::new().emit_ir_leave(&builder, module);
+            let _ = builder.build_return(None);
+        }
+    }
+
+    /// runs the __init__ of every module at the start of main, modules
+    /// come after the ones they depend on. A script without a main
+    /// gets one that only runs the initializers
+    pub fn emit_initializers(&self, module: &mut Module<'a>) {
+        self.finish_init(module);
+        let mut order = Vec::<Symbol>::new();
+        self.initialization_order(module, &mut order);
+
+        let context = module.get_context();
+        let main = match module.module().get_function("main") {
+            Some(x) => x,
+            None => {
+                let func = context.i32_type().fn_type(&[], false);
+                let func = module.add_function("main", func, None);
+                let builder = context.create_builder();
+                builder.position_at_end(context.append_basic_block(func, "entry"));
+                let _ = builder.build_return(Some(&context.i32_type().const_zero()));
+                func
+            }
+        };
+        let entry = main.get_first_basic_block().unwrap();
+        let builder = context.create_builder();
+        match entry.get_first_instruction() {
+            Some(x) => builder.position_before(&x),
+            None => builder.position_at_end(entry),
+        }
+        for name in order {
+            let init = format!("{}.__init__", name);
+            if let Some(func) = module.module().get_function(&init) {
+                let _ = builder.build_call(func, &[], "");
+            }
+        }
+    }
+
+    /// depth first so dependencies come before the modules using them
+    fn initialization_order(&self, module: &Module<'a>, order: &mut Vec<Symbol>) {
+        for name in module.get_dependencies() {
+            // a module replacing e.g. Base depends on the one it replaces
+            if order.contains(name) || name.name() == "Core" || *name == module.name() {
+                continue;
+            }
+            if let Some(dependency) = self.modules.get(name) {
+                self.initialization_order(dependency, order);
+            }
+        }
+        if !order.contains(&module.name()) {
+            order.push(module.name());
+        }
+    }
+
+    /// module level bindings, including qualified ones like Foo.x,
+    /// constants are used directly when their value is known
+    fn compile_global_load<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        name: &Symbol,
+    ) -> Option<(Value<'a>, DataType)> {
+        if module.get_module_global(name.name()).is_none() {
+            let (owner, short_name) = name.name().rsplit_once('.')?;
+            let owner = Symbol::new(owner);
+            let (ir_name, datatype, is_const) = self
+                .modules
+                .get(&owner)
+                .filter(|_| module.get_dependencies().contains(&owner))?
+                .get_globals()
+                .get(&Symbol::new(short_name))?
+                .clone();
+            module.insert_global_declaration(name.clone(), ir_name, datatype, is_const);
+        }
+        let (global, datatype, is_const) = module.get_module_global(name.name())?;
+        let val = match global.get_initializer() {
+            // consts set at runtime aren't LLVM constants
+            Some(x) if is_const && global.is_constant() => x,
+            _ => builder.build_load(global.as_pointer_value(), "").unwrap(),
+        };
+        Some((val.into(), datatype))
+    }
+
+    /// using Foo brings in all exports of Foo, import Foo only makes
+    /// Foo itself available for qualified calls like Foo.bar(x).
+    /// Macros are brought in by the expander
+    fn compile_import(
+        &mut self,
+        module: &mut Module<'a>,
+        path: &ModulePath,
+        names: &[String],
+        using: bool,
+    ) {
+        if path.level == 0 && !self.modules.contains_key(&Symbol::new(&path.names[0])) {
+            self.load_package(module, &path.names[0]);
+        }
+        let name = self
+            .resolve_module(&module.name(), path)
+            .unwrap_or_else(|e| panic!("{}", e));
+        let other = self.modules.get(&name).unwrap();
+        let names = names.iter().map(|x| Symbol::new(x)).collect::<Vec<_>>();
+        if names.is_empty() && !using {
+            module.push_dependency(name);
+            return;
+        }
+        self.expander
+            .import(&module.name(), &name, &names, other.get_exports())
+            .unwrap_or_else(|e| panic!("{}", e));
+        let (macros, names): (Vec<_>, Vec<_>) =
+            names.into_iter().partition(|x| x.name().starts_with('@'));
+        if names.is_empty() && !macros.is_empty() {
+            module.push_dependency(name);
+            return;
+        }
+        module
+            .import(other, &names)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    /// finds a module by path, leading dots walk up from the
+    /// current module like in Julia
+    fn resolve_module(&self, current: &Symbol, path: &ModulePath) -> Result<Symbol, ModuleError> {
+        let name = Symbol::new(&path.names[0]);
+        let mut scope = current.clone();
+        for _ in 1..path.level {
+            scope = match self.parents.get(&scope) {
+                Some(x) => x.clone(),
+                None => return Err(ModuleError::UndefVar(name, scope)),
+            };
+        }
+        let found = match path.level {
+            0 => self.modules.contains_key(&name),
+            _ => self.parents.get(&name) == Some(&scope),
+        };
+        if !found {
+            return Err(ModuleError::UndefVar(name, scope));
+        }
+        // the rest of the path names submodules, e.g. A.B
+        path.names[1..].iter().try_fold(name, |scope, x| {
+            let name = Symbol::new(x);
+            match self.parents.get(&name) == Some(&scope) {
+                true => Ok(name),
+                false => Err(ModuleError::UndefVar(name, scope)),
+            }
+        })
+    }
+
+    /// links in the code of every module the given module depends
+    /// on, directly or through other modules
+    pub fn link_dependencies(&self, module: &mut Module<'a>) -> Result<(), ModuleError> {
+        // Core is linked into every module when it is created
+        let mut linked = vec![module.name(), Symbol::new("Core")];
+        let mut pending = module.get_dependencies().clone();
+        while let Some(name) = pending.pop() {
+            if linked.contains(&name) {
+                continue;
+            }
+            let dependency = self.modules.get(&name).unwrap();
+            module.link_in_module(dependency)?;
+            pending.extend(dependency.get_dependencies().iter().cloned());
+            linked.push(name);
+        }
+        Ok(())
+    }
+
+    /// compiles a sequence of expressions, only the value of the
+    /// last one is kept and an empty block evaluates to nothing
+    fn compile_block<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        exprs: &[Node],
+    ) -> (Value<'a>, DataType) {
+        let mut result = None;
+        for expr in exprs.iter() {
+            let depth = self.stack.len();
+            self.compile_expr(builder, module, expr.clone());
+            if self.stack.len() > depth {
+                result = self.stack.pop();
+            }
+            self.stack.truncate(depth);
+            // in between statements only rooted values are live
+            if !is_declaration(expr) {
+                FarneseGC::new().emit_ir_safepoint(builder, module);
+            }
+        }
+        match result {
+            Some(x) => x,
+            None => {
+                self.push_nothing(module);
+                self.stack.pop().unwrap()
+            }
+        }
+    }
+
+    /// bindings are made in order in a new scope, so each can use the
+    /// ones before it, and are gone after the block. Variables of the
+    /// enclosing scope that the block assigns keep their new values
+    fn compile_let<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        bindings: &[Node],
+        exprs: &[Node],
+    ) -> (Value<'a>, DataType) {
+        let outer = self.scope.clone();
+        self.local_scopes += 1;
+        let names = bindings
+            .iter()
+            .map(|binding| match binding {
+                Node::AssignmentExpr { identifier, .. } => Symbol::new(identifier),
+                x => panic!("syntax: invalid let syntax {}", macros::unparse(x)),
+            })
+            .collect::<Vec<_>>();
+        // the bindings shadow variables kept in try slots
+        let shadowed = self
+            .try_slots
+            .iter_mut()
+            .map(|slots| {
+                names
+                    .iter()
+                    .filter_map(|x| slots.remove_entry(x))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for binding in bindings.iter() {
+            self.compile_expr(builder, module, binding.clone());
+            self.stack.pop();
+        }
+        let result = self.compile_block(builder, module, exprs);
+        self.local_scopes -= 1;
+        for (slots, shadowed) in self.try_slots.iter_mut().zip(shadowed) {
+            slots.extend(shadowed);
+        }
+
+        let inner = std::mem::replace(&mut self.scope, outer);
+        for (name, val) in inner {
+            if self.scope.contains_key(&name) && !names.contains(&name) {
+                self.scope.insert(name, val);
+            }
+        }
+        result
+    }
+
+    fn push_nothing(&mut self, module: &Module<'a>) {
+        let datatype = module.get_type("Nothing").clone();
+        let val = Primitive::Nothing.emit_ir_value(module);
+        self.stack.push((val.into(), datatype));
+    }
+
+    fn compile_function<'b>(
+        &mut self,
+        module: &mut Module<'a>,
+        name: &str,
+        args: &Vec<Node>,
+        return_type: &str,
+        body: &Box<Vec<Node>>,
+    ) -> FunctionValue<'a> {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        // try slots belong to the function that encloses the definition
+        let try_slots = std::mem::take(&mut self.try_slots);
+
+        // handle main as a special case
+        if name == "main" {
+            let return_type = context.i32_type();
+            let func = return_type.fn_type(&[], false);
+            let func = module.add_function(&name, func, None);
+            let entry = context.append_basic_block(func, "entry");
+            builder.position_at_end(entry);
+            let gc = FarneseGC::new();
+            let exception = FarneseException::new();
+            exception.emit_ir_enter(&builder, module, "main()");
+            let depth = gc.emit_ir_frame(&builder, module);
+            let _ = self.compile_block(&builder, module, body);
+            gc.emit_ir_pop_frame(&builder, module, depth);
+            exception.emit_ir_leave(&builder, module);
+            let return_val = return_type.const_int(0, false);
+            let _ = builder.build_return(Some(&return_val));
+            self.try_slots = try_slots;
+            return func;
+        }
+
+        let mut arg_names = Vec::<Symbol>::new();
+        let mut arg_types = Vec::<DataType>::new();
+        let _ = args
+            .iter()
+            .map(|arg| match arg {
+                Node::FunctionArg { name, arg_type } => {
+                    arg_names.push(Symbol::new(name));
+                    arg_types.push(module.resolve_type(arg_type));
+                }
+                _ => panic!("Shouldn't happen"),
+            })
+            .collect::<Vec<_>>();
+        let method_name = name.to_owned()
+            + "_"
+            + &arg_types
+                .iter()
+                .map(|x| x.full_name())
+                .collect::<Vec<_>>()
+                .join("_");
+
+        // TODO need to figure out how to infer return type
+        let return_datatype = module.resolve_type(return_type);
+        module.insert_method(
+            Symbol::new(&method_name),
+            arg_types.clone(),
+            return_datatype.clone(),
+        );
 
         // TODO infer type based on last IR value
-        let return_type = return_type.get_ir_value_type(module);
-        let func = match return_type {
-            BasicMetadataTypeEnum::FloatType(x) => x.fn_type(&arg_types, false),
-            BasicMetadataTypeEnum::IntType(x) => x.fn_type(&arg_types, false),
-            _ => todo!("Need to support blah..."),
+        let func = module.method_type(&arg_types, &return_datatype);
+        // let func = module.add_function(&name, func, None);
+        let func = module.add_function(&module.method_symbol(&method_name), func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        // backtraces show the method with its argument types
+        let exception = FarneseException::new();
+        let signature = arg_names
+            .iter()
+            .zip(arg_types.iter())
+            .map(|(x, y)| format!("{}::{}", x, y.full_name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        exception.emit_ir_enter(&builder, module, &format!("{}({})", name, signature));
+
+        // arguments might be temporaries of the caller, so they are rooted
+        let gc = FarneseGC::new();
+        let depth = gc.emit_ir_frame(&builder, module);
+        for (n, datatype) in arg_types.iter().enumerate() {
+            if datatype.is_gc_tracked() {
+                let arg = func.get_nth_method_input(n.try_into().unwrap());
+                gc.emit_ir_push_root(&builder, module, arg.into_pointer_value());
+            }
+        }
+
+        // need to first load up arguments and store in scope
+        let _ = arg_names
+            .clone()
+            .into_iter()
+            .enumerate()
+            .map(|(n, name)| {
+                self.scope.insert(
+                    name,
+                    (
+                        func.get_nth_method_input(n.try_into().unwrap()),
+                        arg_types[n].clone(),
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // using the value of the last expression as return value
+        let result = self.compile_block(&builder, module, body);
+        let result = (
+            self.convert(&builder, module, result, &return_datatype),
+            return_datatype,
+        );
+        gc.emit_ir_pop_frame(&builder, module, depth);
+        exception.emit_ir_leave(&builder, module);
+        let _ = match result.0 {
+            Value::FloatValue(x) => builder.build_return(Some(&x)),
+            Value::IntValue(x) => builder.build_return(Some(&x)),
+            Value::PointerValue(x) => builder.build_return(Some(&x)),
+            Value::StructValue(x) => builder.build_return(Some(&x)),
+            _ => todo!("Not supported return type yet"),
+        };
+
+        // pop input argument values
+        let _ = arg_names
+            .iter()
+            .map(|name| self.scope.remove(name))
+            .collect::<Vec<_>>();
+        self.try_slots = try_slots;
+        func
+    }
+
+    fn compile_method_call<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        name: &str,
+        args: Box<Vec<Node>>,
+    ) {
+        if name.starts_with("Vector{") || name.starts_with("Array{") {
+            return self.compile_array_constructor(builder, module, name, *args);
+        }
+        // the target type has to be known statically
+        if matches!(name, "reinterpret" | "bitcast" | "Core.bitcast")
+            && args.len() == 2
+            && let Node::Symbol(x) = &args[0]
+            && let Some(datatype) = module.get_types().get(&Symbol::new(x))
+        {
+            let datatype = datatype.clone();
+            let mut args = args.into_iter();
+            args.next();
+            self.compile_expr(builder, module, args.next().unwrap());
+            let val = self.stack.pop().unwrap();
+            let val = self.compile_bitcast(builder, module, val, &datatype);
+            return self.stack.push((val, datatype));
+        }
+        // qualified calls, e.g. Foo.bar(x)
+        let (owner, name) = match name.rsplit_once('.') {
+            Some((owner, name)) => (Some(Symbol::new(owner)), name),
+            None => (None, name),
+        };
+
+        // evaluate arguments left to right
+        let arg_vals = args
+            .into_iter()
+            .map(|x| {
+                self.compile_expr(builder, module, x);
+                self.stack.pop().unwrap()
+            })
+            .collect::<Vec<_>>();
+        let is_array = |n: usize| arg_vals.len() > n && arg_vals[n].1.name().name() == "Array";
+        let is_type = |n: usize| arg_vals.len() > n && arg_vals[n].1.name().name() == "DataType";
+        let is_owner = |x: &str| owner.as_ref().is_some_and(|y| y.name() == x);
+        let is_symbol = |x: &DataType| !x.is_abstract && x.name().name() == "Symbol";
+        let is_integer = |n: usize| {
+            !arg_vals[n].1.is_abstract
+                && module.is_subtype(&arg_vals[n].1, module.get_type("Integer"))
+        };
+
+        // handle builtins specially for now.. eventually use a trait
+        let result = match name {
+            "printf" => {
+                assert!(arg_vals.len() == 1, "printf needs to have one input");
+                let _ = arg_vals[0].emit_ir_printf(builder, module);
+                let datatype = module.get_type("Nothing").clone();
+                (Primitive::Nothing.emit_ir_value(module).into(), datatype)
+            }
+            "print" | "println" if owner.is_none() => {
+                for val in arg_vals.iter() {
+                    self.compile_print(builder, module, val.clone());
+                }
+                if name == "println" {
+                    FarneseShow::new().emit_ir_print_str(builder, module, "\n");
+                }
+                let datatype = module.get_type("Nothing").clone();
+                (Primitive::Nothing.emit_ir_value(module).into(), datatype)
+            }
+            "show" if owner.is_none() && arg_vals.len() == 1 => {
+                self.compile_show(builder, module, arg_vals[0].clone());
+                let datatype = module.get_type("Nothing").clone();
+                (Primitive::Nothing.emit_ir_value(module).into(), datatype)
+            }
+            "length" if arg_vals.len() == 1 && is_array(0) => {
+                let array = Array::new(arg_vals[0].1.clone());
+                let length = array.emit_ir_length(builder, arg_vals[0].0.into_pointer_value());
+                (length.into(), module.get_type("Int64").clone())
+            }
+            "length" if arg_vals.len() == 1 && arg_vals[0].1.name().name() == "String" => {
+                let length = FarneseString::new().emit_ir_length(
+                    builder,
+                    module,
+                    arg_vals[0].0.into_pointer_value(),
+                );
+                (length.into(), module.get_type("Int64").clone())
+            }
+            "string" => self.compile_string(builder, module, &arg_vals),
+            "typeof" if arg_vals.len() == 1 && arg_vals[0].1.is_abstract => {
+                let ptr = arg_vals[0].0.into_pointer_value();
+                let val = FarneseBox::new().emit_ir_typeof(builder, ptr);
+                (val.into(), module.get_type("DataType").clone())
+            }
+            "typeof" if arg_vals.len() == 1 => {
+                let val = arg_vals[0].1.emit_ir_object(module);
+                (val.into(), module.get_type("DataType").clone())
+            }
+            "nameof" | "supertype" | "fieldnames" if arg_vals.len() == 1 && is_type(0) => {
+                let return_type = match name {
+                    "nameof" => module.get_type("Symbol").clone(),
+                    "supertype" => module.get_type("DataType").clone(),
+                    _ => DataType::new_array_type(module.get_type("Symbol"), 1),
+                };
+                // nameof is called name in Core
+                let func = match name {
+                    "nameof" => "name",
+                    _ => name,
+                };
+                let val = builder
+                    .build_call(module.get_function(func), &[arg_vals[0].0], "")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .unwrap_left();
+                (val.into(), return_type)
+            }
+            "gc" if is_owner("GC") && arg_vals.is_empty() => {
+                FarneseGC::new().emit_ir_collect(builder, module);
+                let datatype = module.get_type("Nothing").clone();
+                (Primitive::Nothing.emit_ir_value(module).into(), datatype)
+            }
+            "stats" if is_owner("GC") && arg_vals.is_empty() => {
+                FarneseGC::new().emit_ir_print_stats(builder, module);
+                let datatype = module.get_type("Nothing").clone();
+                (Primitive::Nothing.emit_ir_value(module).into(), datatype)
+            }
+            "gc_bytes" if is_owner("Base") && arg_vals.is_empty() => {
+                let bytes = FarneseGC::new().emit_ir_bytes(builder, module);
+                (bytes.into(), module.get_type("Int64").clone())
+            }
+            "gc_live_bytes" if is_owner("Base") && arg_vals.is_empty() => {
+                let bytes = FarneseGC::new().emit_ir_live_bytes(builder, module);
+                (bytes.into(), module.get_type("Int64").clone())
+            }
+            "throw" if arg_vals.len() == 1 => {
+                let any = module.get_type("Any").clone();
+                let val = self.convert(builder, module, arg_vals[0].clone(), &any);
+                FarneseException::new().emit_ir_throw(builder, module, val.into_pointer_value());
+                self.compile_after_throw(builder, module)
+            }
+            "error" if arg_vals.len() == 1 && arg_vals[0].1.name().name() == "String" => {
+                let exception = FarneseException::new();
+                let msg = arg_vals[0].0.try_into().unwrap();
+                let e = exception.emit_ir_new(builder, module, "ErrorException", &[msg]);
+                exception.emit_ir_throw(builder, module, e);
+                self.compile_after_throw(builder, module)
+            }
+            "rethrow" if arg_vals.is_empty() => {
+                FarneseException::new().emit_ir_rethrow(builder, module);
+                self.compile_after_throw(builder, module)
+            }
+            "showerror" if arg_vals.len() == 1 => {
+                let any = module.get_type("Any").clone();
+                let val = self.convert(builder, module, arg_vals[0].clone(), &any);
+                FarneseException::new().emit_ir_showerror(
+                    builder,
+                    module,
+                    val.into_pointer_value(),
+                );
+                let datatype = module.get_type("Nothing").clone();
+                (Primitive::Nothing.emit_ir_value(module).into(), datatype)
+            }
+            "div" | "rem" if arg_vals.len() == 2 && is_integer(0) && is_integer(1) => {
+                self.compile_int_division(builder, module, name, &arg_vals)
+            }
+            "Expr" if owner.is_none() && arg_vals.first().is_some_and(|x| is_symbol(&x.1)) => {
+                let any = module.get_type("Any").clone();
+                let args = arg_vals[1..]
+                    .iter()
+                    .map(|x| {
+                        let val = self.convert(builder, module, x.clone(), &any);
+                        BasicValueEnum::try_from(val).unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                let head = arg_vals[0].0.into_pointer_value();
+                let val = FarneseExpr::new().emit_ir_new(builder, module, head, &args);
+                (val.into(), module.get_type("Expr").clone())
+            }
+            _ if owner.is_none()
+                && module
+                    .get_types()
+                    .get(&Symbol::new(name))
+                    .is_some_and(|x| x.is_struct()) =>
+            {
+                self.compile_new(builder, module, name, &arg_vals)
+            }
+            // unless there are methods of that name, e.g. outer constructors
+            _ if owner.is_none()
+                && module
+                    .get_types()
+                    .get(&Symbol::new(name))
+                    .is_some_and(|x| x.is_abstract)
+                && module.get_methods_named(name).is_empty() =>
+            {
+                panic!(
+                    "MethodError: no constructors have been defined for {}",
+                    name
+                )
+            }
+            "push!" if arg_vals.len() == 2 && is_array(0) => {
+                let array = Array::new(arg_vals[0].1.clone());
+                let val = self.convert(builder, module, arg_vals[1].clone(), &array.eltype(module));
+                let ptr = arg_vals[0].0.into_pointer_value();
+                array.emit_ir_push(builder, module, ptr, val.try_into().unwrap());
+                arg_vals[0].clone()
+            }
+            _ => {
+                // methods are mangled by their argument types
+                let method_name = name.to_owned()
+                    + "_"
+                    + &arg_vals
+                        .iter()
+                        .map(|x| x.1.full_name())
+                        .collect::<Vec<_>>()
+                        .join("_");
+                let (ir_name, func_result_type) = match owner {
+                    Some(owner) if owner != module.name() => {
+                        self.declare_qualified_method(module, &owner, &method_name)
+                    }
+                    // no exact match or a boxed argument, pick the most
+                    // specific method, at runtime if needed
+                    _ if (module.get_method(&method_name).is_none()
+                        || arg_vals.iter().any(|x| x.1.is_abstract))
+                        && !module.get_methods_named(name).is_empty() =>
+                    {
+                        let result = self.compile_dispatch(builder, module, name, &arg_vals);
+                        self.stack.push(result);
+                        return;
+                    }
+                    _ => match module.get_method(&method_name) {
+                        Some((_, return_type)) => {
+                            (module.method_symbol(&method_name), return_type.clone())
+                        }
+                        // TODO big hack for now
+                        None => (
+                            method_name.clone(),
+                            DataType::new_primitive_type("Int64", "Signed", 64),
+                        ),
+                    },
+                };
+                let arg_vals = arg_vals.iter().map(|x| x.0).collect::<Vec<_>>();
+                let func_result = builder
+                    .build_call(
+                        module.get_function(&ir_name),
+                        &arg_vals,
+                        format!("__call__{}", name).as_str(),
+                    )
+                    .unwrap()
+                    .try_as_basic_value()
+                    .unwrap_left();
+                (func_result.into(), func_result_type)
+            }
         };
-        // let func = module.add_function(&name, func, None);
-        let func = module.add_function(&method_name, func, None);
-        let entry = context.append_basic_block(func, "entry");
-        builder.position_at_end(entry);
+        self.stack.push(result);
+    }
 
-        // need to first load up arguments and store in scope
-        // TODO need to get actual DataType
-        let arg_datatype = DataType::new_primitive_type("Int64", "Integer", 64);
+    /// the value of quoted code, symbols and literals stand for
+    /// themselves and other expressions become an Expr, with the
+    /// values of interpolations spliced in
+    fn compile_quote<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        node: Node,
+    ) -> (Value<'a>, DataType) {
+        let symbol = |x: &str| {
+            let val = Symbol::new(x).emit_ir_constant(module);
+            (val.into(), module.get_type("Symbol").clone())
+        };
+        // the head and the arguments that are symbols in every such Expr
+        let (head, mut vals, args) = match node {
+            Node::Interpolation { expr } => {
+                self.compile_expr(builder, module, *expr);
+                return self.stack.pop().unwrap();
+            }
+            Node::ParenthesesExpr { expr } => return self.compile_quote(builder, module, *expr),
+            Node::Primitive(_) => {
+                self.compile_expr(builder, module, node);
+                return self.stack.pop().unwrap();
+            }
+            // a.b is a field access
+            Node::Symbol(x) => match x.rsplit_once('.') {
+                Some((value, field)) => (
+                    ".",
+                    vec![],
+                    vec![Node::Symbol(value.into()), Node::Symbol(field.into())],
+                ),
+                None => return symbol(&x),
+            },
+            Node::ArrayExpr { elements } => ("vect", vec![], *elements),
+            Node::AssignmentExpr {
+                identifier, value, ..
+            } => ("=", vec![], vec![Node::Symbol(identifier), *value]),
+            Node::InterpolatedAssignment { target, value, .. } => {
+                ("=", vec![], vec![*target, *value])
+            }
+            Node::BinaryExpr { op, lhs, rhs } => {
+                ("call", vec![symbol(&op.to_string())], vec![*lhs, *rhs])
+            }
+            Node::Block { exprs } => ("block", vec![], *exprs),
+            // f.(x) is Expr(:., :f, Expr(:tuple, :x))
+            Node::Broadcast { expr } => match *expr {
+                Node::AssignmentExpr {
+                    identifier, value, ..
+                } => (".=", vec![], vec![Node::Symbol(identifier), *value]),
+                Node::BinaryExpr { op, lhs, rhs } => {
+                    ("call", vec![symbol(&format!(".{}", op))], vec![*lhs, *rhs])
+                }
+                Node::MethodCall { name, args } => {
+                    let args = args
+                        .into_iter()
+                        .map(|x| self.compile_quote(builder, module, x))
+                        .collect();
+                    let tuple = self.compile_quote_expr(builder, module, "tuple", args);
+                    let name = Symbol::new(&name).emit_ir_constant(module);
+                    let name = (name.into(), module.get_type("Symbol").clone());
+                    (".", vec![name, tuple], vec![])
+                }
+                x => return self.compile_quote(builder, module, x),
+            },
+            Node::IndexExpr { array, indices } => (
+                "ref",
+                vec![],
+                [*array].into_iter().chain(*indices).collect(),
+            ),
+            Node::MacroCall { name, args } => {
+                ("macrocall", vec![symbol(&format!("@{}", name))], *args)
+            }
+            Node::MethodCall { name, args } => (
+                "call",
+                vec![],
+                [Node::Symbol(name)].into_iter().chain(*args).collect(),
+            ),
+            Node::Quote { exprs } => ("quote", vec![], *exprs),
+            Node::UnaryExpr { op, child } => ("call", vec![symbol(&op.to_string())], vec![*child]),
+            x => panic!(
+                "syntax: unsupported expression in quote: {}",
+                macros::unparse(&x)
+            ),
+        };
+        for arg in args.into_iter().filter(|x| *x != Node::Empty) {
+            let val = self.compile_quote(builder, module, arg);
+            vals.push(val);
+        }
+        self.compile_quote_expr(builder, module, head, vals)
+    }
 
-        let _ = arg_names
-            .clone()
+    fn compile_quote_expr<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        head: &str,
+        args: Vec<(Value<'a>, DataType)>,
+    ) -> (Value<'a>, DataType) {
+        let any = module.get_type("Any").clone();
+        let args = args
             .into_iter()
-            .enumerate()
-            .map(|(n, name)| {
-                self.scope.insert(
-                    name,
-                    (
-                        func.get_nth_method_input(n.try_into().unwrap()),
-                        arg_datatype.clone(),
+            .map(|x| BasicValueEnum::try_from(self.convert(builder, module, x, &any)).unwrap())
+            .collect::<Vec<_>>();
+        let head = Symbol::new(head).emit_ir_constant(module);
+        let val = FarneseExpr::new().emit_ir_new(builder, module, head, &args);
+        (val.into(), module.get_type("Expr").clone())
+    }
+
+    /// macros the compiler lowers itself, the rest are expanded into
+    /// code that is compiled in their place
+    fn compile_macro_call<'b>(
+        &mut self,
+        builder: &'b Builder<'a>,
+        module: &mut Module<'a>,
+        name: &str,
+        args: Vec<Node>,
+    ) {
+        match name {
+            "printf" | "sprintf" if self.expander.is_builtin(&module.name(), name) => {
+                let (format, args) = match args.split_first() {
+                    Some((Node::Primitive(ast::Primitive::String(x)), rest)) => (x.clone(), rest),
+                    _ => panic!(
+                        "ArgumentError: @{}: first argument must be a format string",
+                        name
                     ),
-                )
+                };
+                let args = args
+                    .iter()
+                    .map(|x| {
+                        self.compile_expr(builder, module, x.clone());
+                        let (val, datatype) = self.stack.pop().unwrap();
+                        (val.try_into().unwrap(), datatype)
+                    })
+                    .collect::<Vec<_>>();
+                let format_helper = FarneseFormat::new(name);
+                if name == "printf" {
+                    format_helper.emit_ir_printf(builder, module, &format, &args);
+                    self.push_nothing(module);
+                } else {
+                    let val = format_helper.emit_ir_sprintf(builder, module, &format, &args);
+                    self.stack
+                        .push((val.into(), module.get_type("String").clone()));
+                }
+            }
+            "assert" => {
+                let (cond, msg) = match args.as_slice() {
+                    [cond] => (cond, None),
+                    [cond, msg] => (cond, Some(msg)),
+                    _ => panic!(
+                        "MethodError: no method matching {}",
+                        macros::signature(name, &args)
+                    ),
+                };
+                let val = self.compile_condition(builder, module, cond.clone());
+                let context = module.get_context();
+                let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+                let error = context.append_basic_block(func, "");
+                let ok = context.append_basic_block(func, "");
+                let _ = builder.build_conditional_branch(val, ok, error);
+
+                // without a message the condition is shown as written
+                builder.position_at_end(error);
+                let msg = match msg {
+                    Some(msg) => {
+                        self.compile_expr(builder, module, msg.clone());
+                        let msg = self.stack.pop().unwrap();
+                        self.compile_string(builder, module, &[msg]).0
+                    }
+                    None => {
+                        let msg = macros::unparse(cond);
+                        FarneseString::new().emit_ir_literal(module, &msg).into()
+                    }
+                };
+                let exception = FarneseException::new();
+                let e = exception.emit_ir_new(
+                    builder,
+                    module,
+                    "AssertionError",
+                    &[msg.try_into().unwrap()],
+                );
+                exception.emit_ir_throw(builder, module, e);
+
+                builder.position_at_end(ok);
+                self.push_nothing(module);
+            }
+            // only hints LLVM, the function is compiled as usual
+            "inline" => match args.as_slice() {
+                [
+                    Node::Function {
+                        name,
+                        args,
+                        return_type,
+                        body,
+                    },
+                ] => {
+                    let func = self.compile_function(module, name, args, return_type, body);
+                    let context = module.get_context();
+                    let kind = Attribute::get_named_enum_kind_id("alwaysinline");
+                    func.add_attribute(
+                        AttributeLoc::Function,
+                        context.create_enum_attribute(kind, 0),
+                    );
+                    self.push_nothing(module);
+                }
+                _ => panic!("ArgumentError: @inline expects a function definition"),
+            },
+            _ => {
+                let expr = self.expander.expand(&module.name(), name, args);
+                self.compile_expr(builder, module, expr);
+            }
+        }
+    }
+
+    /// print is show except for the types with their own print method,
+    /// e.g. strings are printed without quotes
+    fn compile_print(
+        &self,
+        builder: &Builder<'a>,
+        module: &Module<'a>,
+        val: (Value<'a>, DataType),
+    ) {
+        let method_name = format!("print_{}", val.1.full_name());
+        if val.1.is_abstract || module.get_method(&method_name).is_some() {
+            let _ = self.compile_dispatch(builder, module, "print", &[val]);
+        } else {
+            self.compile_show(builder, module, val);
+        }
+    }
+
+    /// shows a value with the most specific show method, structs without
+    /// one use their default show, which shows each field the same way
+    fn compile_show(&self, builder: &Builder<'a>, module: &Module<'a>, val: (Value<'a>, DataType)) {
+        let show = FarneseShow::new();
+        let show_fn = |builder: &Builder<'a>, val: BasicValueEnum<'a>, datatype: &DataType| {
+            self.compile_show(builder, module, (val.into(), datatype.clone()))
+        };
+        let method_name = format!("show_{}", val.1.full_name());
+        let (val, datatype) = val;
+        match datatype {
+            _ if datatype.is_abstract || module.get_method(&method_name).is_some() => {
+                let _ = self.compile_dispatch(builder, module, "show", &[(val, datatype)]);
+            }
+            _ if datatype.name().name() == "Array" => {
+                show.emit_ir_show_array(
+                    builder,
+                    module,
+                    val.into_pointer_value(),
+                    &datatype,
+                    &show_fn,
+                );
+            }
+            _ if datatype.is_struct() => {
+                self.compile_default_show(module, &datatype);
+                show.emit_ir_call_show_struct(builder, module, val.into_pointer_value(), &datatype);
+            }
+            _ if datatype.is_primitive => {
+                show.emit_ir_show_primitive(builder, module, val.try_into().unwrap(), &datatype);
+            }
+            _ => panic!(
+                "MethodError: no method matching show(::{})",
+                datatype.full_name()
+            ),
+        }
+    }
+
+    /// defines the default show of a struct if this module defines the struct,
+    /// otherwise it is defined by the module it comes from
+    fn compile_default_show(&self, module: &Module<'a>, datatype: &DataType) {
+        let is_defined = module
+            .module()
+            .get_global(&datatype.ir_name())
+            .is_some_and(|x| x.get_initializer().is_some());
+        if !is_defined {
+            return;
+        }
+        let show_fn = |builder: &Builder<'a>, val: BasicValueEnum<'a>, datatype: &DataType| {
+            self.compile_show(builder, module, (val.into(), datatype.clone()))
+        };
+        FarneseShow::new().emit_ir_show_struct(module, datatype, &show_fn);
+    }
+
+    /// calls the most specific method of name applicable to the arguments,
+    /// arguments that are boxed are matched against the methods at runtime
+    fn compile_dispatch<'b>(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        arg_vals: &[(Value<'a>, DataType)],
+    ) -> (Value<'a>, DataType) {
+        let context = module.get_context();
+        let box_helper = FarneseBox::new();
+        let is_static = |arg_types: &[DataType]| {
+            arg_types
+                .iter()
+                .zip(arg_vals)
+                .all(|(x, (_, y))| module.is_subtype(y, x))
+        };
+
+        // a boxed argument might hold a subtype of the method's argument type
+        let mut methods = module
+            .get_methods_named(name)
+            .into_iter()
+            .filter(|(_, arg_types, _)| {
+                arg_types.len() == arg_vals.len()
+                    && arg_types.iter().zip(arg_vals).all(|(x, (_, y))| {
+                        module.is_subtype(y, x) || (y.is_abstract && module.is_subtype(x, y))
+                    })
             })
             .collect::<Vec<_>>();
+        let signature = arg_vals
+            .iter()
+            .map(|x| format!("::{}", x.1.full_name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        assert!(
+            !methods.is_empty(),
+            "MethodError: no method matching {}({})",
+            name,
+            signature
+        );
 
-        for expr in (*body).iter() {
-            self.compile_expr(&builder, module, expr.clone())
+        // most specific first, ties keep the order methods are sorted in
+        let depth = |arg_types: &[DataType]| {
+            arg_types
+                .iter()
+                .map(|x| module.supertypes(x).len())
+                .sum::<usize>()
+        };
+        methods.sort_by_key(|(_, arg_types, _)| std::cmp::Reverse(depth(arg_types)));
+        if is_static(&methods[0].1) {
+            return self.compile_call(builder, module, &methods[0], arg_vals);
         }
 
-        // using last value on stack as return value
-        let result = self.stack.pop().unwrap();
-        let _ = match result.0 {
-            Value::FloatValue(x) => builder.build_return(Some(&x)),
-            Value::IntValue(x) => builder.build_return(Some(&x)),
-            _ => todo!("Not supported return type yet"),
+        // results are boxed unless every method returns the same type
+        let return_type = match methods.iter().all(|x| x.2 == methods[0].2) {
+            true => methods[0].2.clone(),
+            false => module.get_type("Any").clone(),
         };
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let done = context.append_basic_block(func, "");
+        let mut incoming = Vec::new();
+        let mut matched = false;
+        for method in methods.iter() {
+            let is_static = is_static(&method.1);
+            let next = context.append_basic_block(func, "");
+            if !is_static {
+                let mut is_match = context.bool_type().const_int(1, false);
+                for ((val, datatype), arg_type) in arg_vals.iter().zip(method.1.iter()) {
+                    if module.is_subtype(datatype, arg_type) {
+                        continue;
+                    }
+                    let isa =
+                        box_helper.emit_ir_isa(builder, module, val.into_pointer_value(), arg_type);
+                    is_match = builder.build_and(is_match, isa, "").unwrap();
+                }
+                let call = context.append_basic_block(func, "");
+                let _ = builder.build_conditional_branch(is_match, call, next);
+                builder.position_at_end(call);
+            }
+            let result = self.compile_call(builder, module, method, arg_vals);
+            let result = self.convert(builder, module, result, &return_type);
+            incoming.push((result, builder.get_insert_block().unwrap()));
+            let _ = builder.build_unconditional_branch(done);
+            builder.position_at_end(next);
+            if is_static {
+                // this method always applies so the rest are never tried
+                matched = true;
+                let _ = builder.build_unreachable();
+                break;
+            }
+        }
 
-        // pop input argument values
-        let _ = arg_names
-            .iter()
-            .map(|name| self.scope.remove(name))
-            .collect::<Vec<_>>();
+        if !matched {
+            // none of the methods apply, the error shows the runtime types
+            let exception = FarneseException::new();
+            let args = arg_vals
+                .iter()
+                .map(|(val, datatype)| ((*val).try_into().unwrap(), datatype.clone()))
+                .collect::<Vec<_>>();
+            let e = exception.emit_ir_method_error(builder, module, name, &args);
+            exception.emit_ir_throw(builder, module, e);
+        }
+
+        builder.position_at_end(done);
+        let ir_type: BasicTypeEnum<'a> = return_type.get_ir_value_type(module).try_into().unwrap();
+        let phi = builder.build_phi(ir_type, "").unwrap();
+        for (val, block) in incoming.iter() {
+            let val: BasicValueEnum<'a> = (*val).try_into().unwrap();
+            phi.add_incoming(&[(&val, *block)]);
+        }
+        (phi.as_basic_value().into(), return_type)
     }
 
-    fn compile_method_call<'b>(
-        &mut self,
+    /// calls a method, converting the arguments to its argument types
+    fn compile_call<'b>(
+        &self,
         builder: &'b Builder<'a>,
-        module: &mut Module<'a>,
-        name: &str,
-        args: Box<Vec<Node>>,
-    ) {
-        // get arguments values from scope
-        let arg_vals = args
+        module: &Module<'a>,
+        method: &(Symbol, Vec<DataType>, DataType),
+        arg_vals: &[(Value<'a>, DataType)],
+    ) -> (Value<'a>, DataType) {
+        let (method_name, arg_types, return_type) = method;
+        let arg_vals = arg_vals
             .iter()
-            .map(|x| match &*x {
-                // TODO currently not using the arg type here
-                Node::FunctionArg { name, arg_type: _ } => Symbol::new(&name),
-                _ => todo!("Not supported"),
-            })
-            .map(|x| self.scope.get(&x).unwrap().clone())
-            // .map(|x| x.0) // only need first val
+            .zip(arg_types.iter())
+            .map(|(val, datatype)| self.convert(builder, module, val.clone(), datatype))
             .collect::<Vec<_>>();
+        let result = builder
+            .build_call(
+                module.get_function(&module.method_symbol(method_name.name())),
+                &arg_vals,
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .unwrap_left();
+        (result.into(), return_type.clone())
+    }
 
-        // handle printf specially for now.. eventually use a trait
-        let func_result = if name == "printf" {
-            assert!(arg_vals.len() == 1, "printf needs to have one input");
-            arg_vals[0]
-                .emit_ir_printf(builder, module)
-                .try_as_basic_value()
-                .unwrap_left()
-        // }
-        } else {
-            let arg_vals = arg_vals.iter().map(|x| x.0).collect::<Vec<_>>();
-            builder
-                .build_call(
-                    module.get_function(&name),
-                    &arg_vals,
-                    format!("__call__{}", name).as_str(),
-                )
-                .unwrap()
-                .try_as_basic_value()
-                .unwrap_left()
+    /// declares a method of another module without importing it,
+    /// returns its LLVM name and return type
+    fn declare_qualified_method(
+        &self,
+        module: &mut Module<'a>,
+        owner: &Symbol,
+        method_name: &str,
+    ) -> (String, DataType) {
+        let visible = owner.name() == "Core" || module.get_dependencies().contains(owner);
+        let other = match self.modules.get(owner) {
+            Some(x) if visible => x,
+            _ => panic!(
+                "UndefVarError: `{}` not defined in `{}`",
+                owner,
+                module.name()
+            ),
         };
-        // TODO big hack for now
-        // let func_result_type = context.i64_type();
-        let func_result_type = DataType::new_primitive_type("Int64", "Signed", 64);
+        let (arg_types, return_type) = match other.get_method(method_name) {
+            Some(x) => x.clone(),
+            None => panic!("MethodError: no method matching {}.{}", owner, method_name),
+        };
+        let ir_name = other.method_symbol(method_name);
+        if module.module().get_function(&ir_name).is_none() {
+            let func = module.method_type(&arg_types, &return_type);
+            let _ = module.add_function(&ir_name, func, Some(Linkage::External));
+        }
+        (ir_name, return_type)
+    }
 
-        // TODO need the return DataType so we can allocate on the stack
-        // self.stack.push(func_result.into());
-        self.stack.push((func_result.into(), func_result_type));
+    fn compile_module(
+        &mut self,
+        name: Symbol,
+        parent: Symbol,
+        exprs: Vec<Node>,
+        context: &'a Context,
+    ) {
+        self.parents.insert(name.clone(), parent);
+        let mut hasher = SourceHasher::new();
+        hasher.write_u64(self.keys[&Symbol::new("Core")]);
+        hasher.write(name.name().as_bytes());
+        self.hash_source(&mut hasher, &exprs, self.files.last().cloned(), &mut vec![]);
+        let key = hasher.finish();
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|x| x.load(context, &name, key, &self.keys));
+        self.keys.insert(name.clone(), key);
+        let module = match cached {
+            Some(module) => module,
+            None => {
+                let mut module = self.create_module(name.name());
+                let builder = self.init_builder(&module);
+                let _ = self.compile_block(&builder, &mut module, &exprs);
+                self.finish_init(&module);
+                // macros only exist in the compiler, a cached module
+                // would come back without them
+                let cache = self
+                    .cache
+                    .as_ref()
+                    .filter(|_| !self.expander.defines_macros(&name));
+                if let Some(Err(e)) = cache.map(|x| x.store(&module, key, &self.keys)) {
+                    eprintln!("Warning: failed to cache module {}: {}", name, e);
+                }
+                module
+            }
+        };
+        self.modules.insert(name, module);
     }
 
-    fn compile_module(&mut self, name: Symbol, exprs: Box<Vec<Node>>, context: &'a Context) {
-        let mut module = Module::new(context, name.name());
-        module.link(self.get_module("Core"));
-        let builder = context.create_builder();
-        for ast in exprs.iter() {
-            self.compile_expr(&builder, &mut module, ast.clone());
+    /// hashes a module body along with the files it includes, which
+    /// are resolved the same way include does
+    fn hash_source(
+        &self,
+        hasher: &mut SourceHasher,
+        exprs: &[Node],
+        file: Option<PathBuf>,
+        seen: &mut Vec<PathBuf>,
+    ) {
+        for expr in exprs {
+            hasher.write(format!("{:?}", expr).as_bytes());
+            let file_name = match expr {
+                Node::MethodCall { name, args } if name == "include" => match args.as_slice() {
+                    [Node::Primitive(ast::Primitive::String(x))] => x,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let path = match resolve_path(file.as_ref(), file_name).canonicalize() {
+                Ok(x) if !seen.contains(&x) => x,
+                _ => continue,
+            };
+            seen.push(path.clone());
+            if let Ok(ast) = lexer::parse_file(path.to_str().unwrap()) {
+                self.hash_source(hasher, &ast, Some(path), seen);
+            }
         }
-        self.modules.insert(name.clone(), module.clone());
-        let _ = module.print_to_file(format!("{}.ll", name.name()).as_str());
     }
 
     pub fn get_module(&self, name: &str) -> &Module<'a> {
@@ -357,21 +3395,625 @@ impl<'a> Compiler<'a> {
         self.modules.insert(Symbol::new(name), module);
     }
 
+    /// looks for Pkg/src/Pkg.jl in the load path, then among the
+    /// bundled standard libraries
+    fn load_package(&mut self, module: &mut Module<'a>, name: &str) {
+        let file_name = self
+            .load_path
+            .iter()
+            .map(|x| x.join(name).join("src").join(format!("{}.jl", name)))
+            .find(|x| x.is_file())
+            // load path entries are relative to the working directory
+            .and_then(|x| x.canonicalize().ok());
+        let file_name = match file_name {
+            Some(x) => x,
+            None => return self.load_stdlib(module, name),
+        };
+        self.include(module, file_name.to_str().unwrap());
+        let name = Symbol::new(name);
+        assert!(
+            self.modules.contains_key(&name),
+            "package `{}` did not define the expected module `{}`, check {}",
+            name,
+            name,
+            file_name.display()
+        );
+        // packages are top level modules
+        self.parents.remove(&name);
+    }
+
+    /// compiles a bundled standard library as a top level module
+    fn load_stdlib(&mut self, module: &Module<'a>, name: &str) {
+        let Some((_, source, builtins)) = STDLIBS.iter().find(|x| x.0 == name) else {
+            panic!("ArgumentError: Package {} not found in current path", name);
+        };
+        let name = Symbol::new(name);
+        for x in builtins.iter() {
+            self.expander.define_builtin(&name, x);
+        }
+        for node in lexer::parse_source(source).unwrap() {
+            if let Node::Module { exprs, .. } = node {
+                let context = module.get_context();
+                self.compile_module(name.clone(), name.clone(), *exprs, context);
+            }
+        }
+        self.parents.remove(&name);
+    }
+
+    pub fn push_load_path(&mut self, path: &Path) {
+        self.load_path.push(path.to_path_buf())
+    }
+
+    /// includes a file into a module, relative paths are resolved
+    /// against the directory of the file doing the including
     pub fn include(&mut self, module: &mut Module<'a>, file_name: &str) {
-        let ast: Vec<_> = lexer::parse_file(file_name).unwrap();
+        let path = resolve_path(self.files.last(), file_name);
+        let path = path.canonicalize().unwrap_or_else(|_| {
+            panic!(
+                "SystemError: opening file {}: No such file or directory",
+                path.display()
+            )
+        });
+        if self.files.contains(&path) {
+            let cycle = self
+                .files
+                .iter()
+                .skip_while(|x| **x != path)
+                .chain([&path])
+                .map(|x| x.display().to_string())
+                .collect::<Vec<_>>();
+            panic!("LoadError: include cycle detected {}", cycle.join(" -> "));
+        }
+
+        self.files.push(path.clone());
+        let ast: Vec<_> = lexer::parse_file(path.to_str().unwrap()).unwrap();
         let context = module.get_context();
-        let builder = context.create_builder();
         for node in ast {
             match node {
                 Node::Module { name, exprs } => {
-                    self.compile_module(Symbol::new(&name), exprs, &context)
+                    self.compile_module(Symbol::new(&name), module.name(), *exprs, context)
+                }
+                _ => {
+                    // nested includes may have moved on to a new block
+                    let builder = self.init_builder(module);
+                    let _ = self.compile_block(&builder, module, &[node]);
                 }
-                _ => self.compile_expr(&builder, module, node),
             }
         }
+        self.files.pop();
     }
 
     pub fn modules(&self) -> &HashMap<Symbol, Module<'a>> {
         &self.modules
     }
 }
+
+/// definitions only run at compile time
+fn is_declaration(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::AbstractType { .. }
+            | Node::Empty
+            | Node::Exports { .. }
+            | Node::Function { .. }
+            | Node::ImportExpr { .. }
+            | Node::Macro { .. }
+            | Node::Module { .. }
+            | Node::PrimitiveType { .. }
+            | Node::StructType { .. }
+            | Node::UsingExpr { .. }
+    )
+}
+
+/// relative paths are relative to the directory of the file
+/// doing the including
+/// the block top level code continues in, the one without a terminator.
+/// It isn't always the last one, e.g. a dispatch adds the blocks of its
+/// methods after the block it joins them in
+fn init_block(func: FunctionValue<'_>) -> Option<BasicBlock<'_>> {
+    func.get_basic_blocks()
+        .into_iter()
+        .rev()
+        .find(|x| x.get_terminator().is_none())
+}
+
+fn resolve_path(file: Option<&PathBuf>, file_name: &str) -> PathBuf {
+    match file {
+        Some(x) if Path::new(file_name).is_relative() => x.parent().unwrap().join(file_name),
+        _ => PathBuf::from(file_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::{run, run_in};
+    use std::fs;
+
+    /// throws from a function so the catch is reached through longjmp
+    const THROWER: &str = "function thrower(x::Int64)::Int64\n\
+                           throw(ErrorException(\"boom\"))\n\
+                           x\n\
+                           end\n";
+
+    #[test]
+    fn test_top_level_dispatch() {
+        // the dispatch on boxed values adds blocks after the one it returns to
+        let source = "function g(x::Any)::Any\n\
+                      x\n\
+                      end\n\
+                      x::Any = 1\n\
+                      println(g(2) + 1)\n\
+                      println(x)\n";
+        assert_eq!("3\n1", run(source));
+    }
+
+    #[test]
+    fn test_try_assignments() {
+        let source = "function f()::Int64\n\
+                      v = 1\n\
+                      try\n\
+                      v = 2\n\
+                      thrower(3)\n\
+                      v = 5\n\
+                      catch\n\
+                      printf(v)\n\
+                      printf(\" \")\n\
+                      v = v + 10\n\
+                      end\n\
+                      v\n\
+                      end\n\
+                      function main()\n\
+                      printf(f())\n\
+                      end\n";
+        assert_eq!("2 12", run(&format!("{}{}", THROWER, source)));
+    }
+
+    #[test]
+    fn test_try_assignments_finally() {
+        // v changes type in the try and again in the catch
+        let source = "function f()::Any\n\
+                      v = 1\n\
+                      try\n\
+                      try\n\
+                      v = \"two\"\n\
+                      thrower(1)\n\
+                      catch\n\
+                      printf(v)\n\
+                      printf(\" \")\n\
+                      v = 3.5\n\
+                      thrower(2)\n\
+                      finally\n\
+                      printf(v)\n\
+                      printf(\" \")\n\
+                      end\n\
+                      catch\n\
+                      end\n\
+                      v\n\
+                      end\n\
+                      function main()\n\
+                      printf(f())\n\
+                      end\n";
+        assert_eq!(
+            "two 3.50000000 3.50000000",
+            run(&format!("{}{}", THROWER, source))
+        );
+    }
+
+    #[test]
+    fn test_parametric_methods() {
+        // the element type is part of the mangled name
+        let source = "function first2(v::Vector{Int64})\n\
+                      v[1] + v[2]\n\
+                      end\n\
+                      function first2(v::Vector{Float64})\n\
+                      v[1] * v[2]\n\
+                      end\n\
+                      function main()\n\
+                      println(first2([1, 2]), \" \", first2([1.5, 2.0]))\n\
+                      end\n";
+        assert_eq!("3 3.0", run(source));
+    }
+
+    #[test]
+    fn test_struct_roots() {
+        // every call allocates enough to collect while the caller's b is live
+        let source = "struct Node\n\
+                      name::String\n\
+                      n::Int64\n\
+                      end\n\
+                      function build(n)\n\
+                      b = Node(\"a\", n)\n\
+                      junk = Vector{Float64}(undef, 1000)\n\
+                      n == 0 ? 0 : build(n - 1) + b.n\n\
+                      end\n\
+                      function main()\n\
+                      b = Node(string(\"x\", 1), 2)\n\
+                      println(build(150))\n\
+                      GC.gc()\n\
+                      junk = [Node(\"y\", 3)]\n\
+                      println(b.name, \" \", b.n)\n\
+                      end\n";
+        assert_eq!("11325\nx1 2", run(source));
+    }
+
+    #[test]
+    fn test_abstract_types() {
+        // Int64 shadows Core's, and redefining a type the same way is fine
+        let source = "abstract type Shape end\n\
+                      abstract type Polygon <: Shape end\n\
+                      abstract type Shape end\n\
+                      abstract type MyInt end\n\
+                      primitive type Int64 <: MyInt 64 end\n\
+                      function main()\n\
+                      println(supertype(Polygon), \" \", supertype(Int64))\n\
+                      end\n";
+        assert_eq!("Shape MyInt", run(source));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid redefinition of constant Main.Polygon")]
+    fn test_abstract_type_redefinition() {
+        run("abstract type Shape end\n\
+             abstract type Polygon <: Shape end\n\
+             abstract type Polygon end\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "can only subtype abstract types")]
+    fn test_abstract_type_supertype() {
+        run("abstract type Shape <: Float64 end\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "no constructors have been defined for Shape")]
+    fn test_abstract_type_constructor() {
+        run("abstract type Shape end\n\
+             function main()\n\
+             Shape()\n\
+             end\n");
+    }
+
+    #[test]
+    fn test_mixed_array_literal() {
+        let source = "function main()\n\
+                      v = [1, 2.5, \"a\"]\n\
+                      push!(v, 'c')\n\
+                      println(v, \" \", typeof(v), \" \", v[3])\n\
+                      end\n";
+        assert_eq!("Any[1, 2.5, \"a\", 'c'] Vector{Any} a", run(source));
+    }
+
+    #[test]
+    fn test_boxed_bitcast() {
+        // the size of the boxed value is only known when it runs
+        let source = "function f(x::Any)::Int64\n\
+                      reinterpret(Int64, x)\n\
+                      end\n\
+                      function main()\n\
+                      println(reinterpret(Float64, f(2.5)))\n\
+                      try\n\
+                      f(true)\n\
+                      catch e\n\
+                      println(e.msg)\n\
+                      end\n\
+                      end\n";
+        assert_eq!(
+            "2.5\nbitcast: argument size does not match size of target type",
+            run(source)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid number of bits in primitive type F")]
+    fn test_float_primitive_bits() {
+        run("primitive type F <: AbstractFloat 24 end\n");
+    }
+
+    #[test]
+    fn test_macro_expansion() {
+        // y in the quote is renamed, so the caller's y is left alone
+        let source = "macro square(x)\n\
+                      quote\n\
+                      y = $x\n\
+                      y * y\n\
+                      end\n\
+                      end\n\
+                      function main()\n\
+                      y = 3\n\
+                      println(@square(y + 1), \" \", y)\n\
+                      end\n";
+        assert_eq!("16 3", run(source));
+    }
+
+    #[test]
+    fn test_macro_interpolated_assignment() {
+        // the name is spliced in, so x is the caller's variable
+        let source = "macro set(name, val)\n\
+                      quote\n\
+                      $name = $val\n\
+                      $name += 1\n\
+                      end\n\
+                      end\n\
+                      function main()\n\
+                      x = 1\n\
+                      @set x 10\n\
+                      println(x)\n\
+                      end\n";
+        assert_eq!("11", run(source));
+    }
+
+    #[test]
+    fn test_printf() {
+        let source = "using Printf\n\
+                      function main()\n\
+                      @printf(\"%05.2f|%d\\n\", 2.5, 7)\n\
+                      println(@sprintf(\"%x\", 255))\n\
+                      end\n";
+        assert_eq!("02.50|7\nff", run(source));
+    }
+
+    #[test]
+    #[should_panic(expected = "UndefVarError: `@printf` not defined in `Main`")]
+    fn test_printf_without_using() {
+        run("@printf(\"%d\", 1)\n");
+    }
+
+    /// a module exporting @twice, its other macro stays private
+    const TWICE: &str = "module Twice\n\
+                         export @twice\n\
+                         macro twice(ex)\n\
+                         quote\n\
+                         $ex\n\
+                         $ex\n\
+                         end\n\
+                         end\n\
+                         macro once(ex)\n\
+                         ex\n\
+                         end\n\
+                         end\n";
+
+    #[test]
+    fn test_macro_module_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Twice.jl"), TWICE).unwrap();
+        let source = "include(\"Twice.jl\")\nusing .Twice\n@twice println(1)\n";
+
+        // the macros are still there when the module was seen before
+        for _ in 0..2 {
+            assert_eq!("1\n1", run_in(dir.path(), source, Some(cache_dir.path())));
+        }
+        let cached = fs::read_dir(
+            cache_dir
+                .path()
+                .join(format!("v{}", env!("CARGO_PKG_VERSION"))),
+        )
+        .unwrap()
+        .any(|x| {
+            x.unwrap()
+                .file_name()
+                .to_str()
+                .unwrap()
+                .starts_with("Twice-")
+        });
+        assert!(!cached);
+    }
+
+    #[test]
+    #[should_panic(expected = "UndefVarError: `@once` not defined in `Main`")]
+    fn test_macro_not_exported() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Twice.jl"), TWICE).unwrap();
+        let source = "include(\"Twice.jl\")\nusing .Twice\n@once println(1)\n";
+        run_in(dir.path(), source, None);
+    }
+
+    #[test]
+    fn test_macro_import() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Twice.jl"), TWICE).unwrap();
+        let source = "include(\"Twice.jl\")\nusing .Twice: @once\n@once println(1)\n";
+        assert_eq!("1", run_in(dir.path(), source, None));
+    }
+
+    #[test]
+    fn test_module_methods() {
+        // both f_Int64 are linked into Main under their module's name
+        let source = "module A\n\
+                      export f\n\
+                      function f(x::Int64)\n\
+                      x + 1\n\
+                      end\n\
+                      end\n\
+                      module B\n\
+                      export f\n\
+                      function f(x::Int64)\n\
+                      x * 10\n\
+                      end\n\
+                      end\n\
+                      import .A\n\
+                      import .B\n\
+                      function main()\n\
+                      println(A.f(1), \" \", B.f(2))\n\
+                      end\n";
+        assert_eq!("2 20", run(source));
+    }
+
+    #[test]
+    fn test_module_types() {
+        // type objects are named after their module too, Main's
+        // Number shadows Base's
+        let source = "module A\n\
+                      export Point, pa\n\
+                      struct Point\n\
+                      x::Int64\n\
+                      end\n\
+                      function pa(x::Int64)\n\
+                      Point(x)\n\
+                      end\n\
+                      end\n\
+                      module B\n\
+                      export Point, pb\n\
+                      struct Point\n\
+                      x::Float64\n\
+                      end\n\
+                      function pb(x::Float64)\n\
+                      Point(x)\n\
+                      end\n\
+                      end\n\
+                      import .A\n\
+                      import .B\n\
+                      abstract type Number end\n\
+                      struct Num <: Number\n\
+                      n::Int64\n\
+                      end\n\
+                      function main()\n\
+                      println(A.pa(1), \" \", B.pb(2.5), \" \", Num(3))\n\
+                      println(supertype(Num), \" \", supertype(Float64))\n\
+                      end\n";
+        assert_eq!(
+            "Point(1) Point(2.5) Num(3)\nNumber AbstractFloat",
+            run(source)
+        );
+    }
+
+    #[test]
+    fn test_module_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let counter = "module Counter\n\
+                       export twice\n\
+                       function twice(x::Int64)::Int64\n    2 * x\nend\n\
+                       end\n";
+        fs::write(dir.path().join("Counter.jl"), counter).unwrap();
+        let source = "include(\"Counter.jl\")\n\
+                      using .Counter\n\
+                      function main()\n\
+                      printf(twice(21))\n\
+                      end\n";
+
+        assert_eq!("42", run_in(dir.path(), source, Some(cache_dir.path())));
+        let metadata = fs::read_dir(
+            cache_dir
+                .path()
+                .join(format!("v{}", env!("CARGO_PKG_VERSION"))),
+        )
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .find(|x| {
+            let name = x.file_name().unwrap().to_str().unwrap();
+            name.starts_with("Counter-") && name.ends_with(".ji")
+        })
+        .unwrap();
+        let modified = fs::metadata(&metadata).unwrap().modified().unwrap();
+
+        // the second run loads Counter instead of compiling it again
+        assert_eq!("42", run_in(dir.path(), source, Some(cache_dir.path())));
+        assert!(
+            fs::read_to_string(&metadata)
+                .unwrap()
+                .contains("export twice")
+        );
+        assert_eq!(
+            modified,
+            fs::metadata(&metadata).unwrap().modified().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_global_reassignment() {
+        let source = "x::Any = 1\n\
+                      x = 2.5\n\
+                      y = 1\n\
+                      y = 3\n\
+                      println(x, \" \", y)\n";
+        assert_eq!("2.5 3", run(source));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "cannot assign a value of type Float64 to global Main.x of type Int64"
+    )]
+    fn test_untyped_global_reassignment() {
+        run("x = 1\nx = 2.5\nprintln(x)\n");
+    }
+
+    #[test]
+    fn test_let_blocks() {
+        // let bindings shadow the global x, begin shares the scope
+        let source = "x = 10\n\
+                      y = let x = 1, z = 2\n\
+                      x + z\n\
+                      end\n\
+                      w = begin\n\
+                      a = 4\n\
+                      a * 2\n\
+                      end\n\
+                      println(x, \" \", y, \" \", w, \" \", a)\n";
+        assert_eq!("10 3 8 4", run(source));
+    }
+
+    #[test]
+    fn test_ternary() {
+        // ?: binds looser than comparisons and arithmetic and groups to the right
+        let source = "a = 1\n\
+                      b = a > 0 ? a + 1 : 0\n\
+                      c = a < 0 ? 1 : a == 1 ? 2 : 3\n\
+                      d = 1 + (false ? 1 : 2) * 3\n\
+                      println(b, \" \", c, \" \", d, \" \", true ? 1.5 : 2.5)\n";
+        assert_eq!("2 2 7 1.5", run(source));
+    }
+
+    #[test]
+    fn test_broadcast_fusion() {
+        // the fused loop calls f and g once per element, interleaved
+        let source = "function f(x::Int64)::Int64\n\
+                      println(\"f\", x)\n\
+                      x\n\
+                      end\n\
+                      function g(x::Int64)::Int64\n\
+                      println(\"g\", x)\n\
+                      x * 10\n\
+                      end\n\
+                      function main()\n\
+                      u = [1.0, 2.0, 3.0]\n\
+                      println(f.([1, 2]) .+ g.([3, 4]))\n\
+                      println(@. u * u + 1.0)\n\
+                      end\n";
+        assert_eq!("f1\ng3\nf2\ng4\n[31, 42]\n[2.0, 5.0, 10.0]", run(source));
+    }
+
+    #[test]
+    fn test_broadcast_shapes() {
+        let source = "function main()\n\
+                      println([1, 2] .+ 1)\n\
+                      try\n\
+                      println([1, 2] .+ [1, 2, 3])\n\
+                      catch e\n\
+                      println(e.msg)\n\
+                      end\n\
+                      end\n";
+        assert_eq!(
+            "[2, 3]\narrays could not be broadcast to a common size; got a dimension with lengths 2 and 3",
+            run(source)
+        );
+    }
+
+    #[test]
+    fn test_reinterpret() {
+        // Half is a 16 bit float, reinterpreting keeps the bits as they are
+        let source = "primitive type Word <: Unsigned 64 end\n\
+                      primitive type Half <: AbstractFloat 16 end\n\
+                      w = reinterpret(Word, 5)\n\
+                      h = reinterpret(Half, 0x3c00)\n\
+                      println(reinterpret(UInt64, 1.0), \" \", reinterpret(Float64, 0x4000000000000000))\n\
+                      println(reinterpret(Int64, w) + 1, \" \", typeof(w), \" \", reinterpret(UInt16, h))\n";
+        assert_eq!("0x3ff0000000000000 2.0\n6 Word 0x3c00", run(source));
+    }
+
+    #[test]
+    #[should_panic(expected = "bitcast: argument size does not match size of target type")]
+    fn test_reinterpret_size() {
+        run("primitive type Int24 <: Signed 24 end\nreinterpret(Int24, 5)\n");
+    }
+}
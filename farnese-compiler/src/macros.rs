@@ -0,0 +1,506 @@
+use farnese_core::{ModuleError, Symbol};
+use farnese_lexer::ast::{Node, Primitive};
+use std::collections::{HashMap, HashSet};
+
+/// expressions bound to names while a macro body runs
+type Env = HashMap<String, Node>;
+
+/// a macro definition, the body runs over the argument expressions
+#[derive(Clone, Debug)]
+struct Macro {
+    /// the module the macro was defined in
+    module: Symbol,
+    params: Vec<String>,
+    body: Vec<Node>,
+    /// lowered by the compiler instead of expanded, e.g. @printf
+    is_builtin: bool,
+}
+
+/// expands macro calls into the code they stand for before it's compiled
+///
+/// macro bodies run when compiling and work on expressions rather than
+/// values, they can bind names to expressions, splice them into a
+/// `quote` with `$` and the last expression is the expansion, e.g.
+///
+///   macro twice(ex)
+///     quote
+///       $ex
+///       $ex
+///     end
+///   end
+///
+/// variables assigned in a quote are renamed so they can't clash with
+/// the ones where the macro is called. Arguments are spliced in as
+/// written, so like an `esc` in julia they see the caller's variables
+///
+/// like other names macros belong to a module, other modules see them
+/// through `using` and `import`, e.g. `export @twice` and `using .M`
+#[derive(Clone, Debug, Default)]
+pub struct MacroExpander {
+    /// the macros each module can call, by name without the @
+    macros: HashMap<Symbol, HashMap<String, Macro>>,
+    /// numbers the renamed variables
+    counter: usize,
+}
+
+impl MacroExpander {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// defines or redefines a macro in module
+    pub fn define(&mut self, module: &Symbol, name: &str, args: &[Node], body: &[Node]) {
+        let params = args
+            .iter()
+            .map(|x| match x {
+                Node::Symbol(x) => x.clone(),
+                x => panic!("syntax: invalid macro argument {}", unparse(x)),
+            })
+            .collect();
+        let body = body.to_vec();
+        let m = Macro {
+            module: module.clone(),
+            params,
+            body,
+            is_builtin: false,
+        };
+        self.macros
+            .entry(module.clone())
+            .or_default()
+            .insert(name.to_owned(), m);
+    }
+
+    /// defines a macro in module that the compiler lowers itself
+    pub fn define_builtin(&mut self, module: &Symbol, name: &str) {
+        let m = Macro {
+            module: module.clone(),
+            params: Vec::new(),
+            body: Vec::new(),
+            is_builtin: true,
+        };
+        self.macros
+            .entry(module.clone())
+            .or_default()
+            .insert(name.to_owned(), m);
+    }
+
+    /// whether @name in module is a macro the compiler lowers itself
+    pub fn is_builtin(&self, module: &Symbol, name: &str) -> bool {
+        self.macros
+            .get(module)
+            .and_then(|x| x.get(name))
+            .is_some_and(|m| m.is_builtin)
+    }
+
+    /// whether module defines macros of its own
+    pub fn defines_macros(&self, module: &Symbol) -> bool {
+        self.macros
+            .get(module)
+            .is_some_and(|x| x.values().any(|m| m.module == *module))
+    }
+
+    /// makes the macros of other callable from module, the ones
+    /// other exports if names is empty. Names start with @
+    pub fn import(
+        &mut self,
+        module: &Symbol,
+        other: &Symbol,
+        names: &[Symbol],
+        exports: &[Symbol],
+    ) -> Result<(), ModuleError> {
+        let explicit = !names.is_empty();
+        let names = match explicit {
+            true => names,
+            false => exports,
+        };
+        for name in names.iter().filter(|x| x.name().starts_with('@')) {
+            let m = self
+                .macros
+                .get(other)
+                .and_then(|x| x.get(&name.name()[1..]))
+                .cloned();
+            match m {
+                Some(m) => {
+                    self.macros
+                        .entry(module.clone())
+                        .or_default()
+                        .insert(name.name()[1..].to_owned(), m);
+                }
+                None if explicit => return Err(ModuleError::UndefVar(name.clone(), other.clone())),
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// the code @name(args...) in module expands to, macros in the
+    /// result are expanded when it's compiled
+    pub fn expand(&mut self, module: &Symbol, name: &str, args: Vec<Node>) -> Node {
+        let m = self.macros.get(module).and_then(|x| x.get(name)).cloned();
+        if name == "show" && m.is_none() {
+            return self.expand_show(args);
+        }
+        if name == "." {
+            return match <[Node; 1]>::try_from(args) {
+                Ok([expr]) => add_dots(expr),
+                Err(args) => panic!("MethodError: no method matching {}", signature(name, &args)),
+            };
+        }
+        let Some(m) = m else {
+            panic!("UndefVarError: `@{}` not defined in `{}`", name, module);
+        };
+        if m.params.len() != args.len() {
+            panic!("MethodError: no method matching {}", signature(name, &args));
+        }
+        let mut env = m.params.into_iter().zip(args).collect::<Env>();
+        let mut result = Node::Primitive(Primitive::Nothing);
+        for expr in m.body.iter() {
+            result = self.eval(name, &mut env, expr);
+        }
+        result
+    }
+
+    /// runs an expression of a macro body
+    fn eval(&mut self, name: &str, env: &mut Env, expr: &Node) -> Node {
+        match expr {
+            Node::AssignmentExpr {
+                identifier, value, ..
+            } => {
+                let val = self.eval(name, env, value);
+                env.insert(identifier.clone(), val.clone());
+                val
+            }
+            Node::MethodCall { name: f, args } if f == "esc" && args.len() == 1 => {
+                self.eval(name, env, &args[0])
+            }
+            Node::ParenthesesExpr { expr } => self.eval(name, env, expr),
+            Node::Primitive(_) => expr.clone(),
+            Node::Quote { exprs } => {
+                let mut template = match exprs.as_slice() {
+                    [x] => x.clone(),
+                    _ => Node::Block {
+                        exprs: exprs.clone(),
+                    },
+                };
+                self.rename_locals(&mut template);
+                visit_mut(&mut template, &mut |x| match x {
+                    Node::Interpolation { expr } => {
+                        *x = self.eval(name, env, expr);
+                        false
+                    }
+                    Node::InterpolatedAssignment {
+                        target,
+                        datatype,
+                        value,
+                    } => {
+                        let Node::Interpolation { expr } = &**target else {
+                            unreachable!()
+                        };
+                        let value = value.clone();
+                        *x = match self.eval(name, env, expr) {
+                            Node::Symbol(identifier) => Node::AssignmentExpr {
+                                identifier,
+                                datatype: datatype.clone(),
+                                value,
+                            },
+                            Node::IndexExpr { array, indices } => Node::SetIndexExpr {
+                                array,
+                                indices,
+                                value,
+                            },
+                            x => panic!("syntax: invalid assignment location \"{}\"", unparse(&x)),
+                        };
+                        true
+                    }
+                    // nested quotes interpolate when they run
+                    Node::Quote { .. } => false,
+                    _ => true,
+                });
+                template
+            }
+            Node::Symbol(x) => match env.get(x) {
+                Some(x) => x.clone(),
+                None => panic!("UndefVarError: `{}` not defined in macro @{}", x, name),
+            },
+            Node::Interpolation { .. } => panic!("syntax: \"$\" expression outside quote"),
+            _ => panic!(
+                "syntax: `{}` can't run in the body of macro @{}, macros only assign, esc and quote expressions",
+                unparse(expr),
+                name
+            ),
+        }
+    }
+
+    /// renames the variables a quote assigns, leaving what's
+    /// interpolated and the bodies of functions alone
+    fn rename_locals(&mut self, template: &mut Node) {
+        let locals = assigned_names(std::slice::from_ref(template));
+        let mut names = HashMap::new();
+        for local in locals {
+            let gensym = self.gensym(&local);
+            names.insert(local, gensym);
+        }
+        let rename = |x: &mut String| {
+            // field access such as p.x renames the variable p
+            let (root, fields) = match x.split_once('.') {
+                Some((root, fields)) => (root, format!(".{}", fields)),
+                None => (x.as_str(), String::new()),
+            };
+            if let Some(gensym) = names.get(root) {
+                *x = format!("{}{}", gensym, fields);
+            }
+        };
+        visit_mut(template, &mut |x| match x {
+            Node::AssignmentExpr { identifier, .. } => {
+                rename(identifier);
+                true
+            }
+            Node::Symbol(x) => {
+                rename(x);
+                false
+            }
+            Node::Function { .. } | Node::Interpolation { .. } | Node::Quote { .. } => false,
+            _ => true,
+        });
+    }
+
+    /// a name that can't be written in source, like julia's ##x#1
+    fn gensym(&mut self, name: &str) -> String {
+        self.counter += 1;
+        format!("##{}#{}", name, self.counter)
+    }
+
+    /// @show prints each expression as written along with its value,
+    /// the value of the last one is the result
+    fn expand_show(&mut self, args: Vec<Node>) -> Node {
+        let call = |name: &str, args: Vec<Node>| Node::MethodCall {
+            name: name.to_owned(),
+            args: Box::new(args),
+        };
+        let mut exprs = Vec::new();
+        let mut result = Node::Primitive(Primitive::Nothing);
+        for arg in args {
+            let value = self.gensym("value");
+            let label = Node::Primitive(Primitive::String(format!("{} = ", unparse(&arg))));
+            exprs.push(Node::AssignmentExpr {
+                identifier: value.clone(),
+                datatype: None,
+                value: Box::new(arg),
+            });
+            exprs.push(call("print", vec![label]));
+            exprs.push(call("show", vec![Node::Symbol(value.clone())]));
+            exprs.push(call("println", vec![]));
+            result = Node::Symbol(value);
+        }
+        exprs.push(result);
+        Node::Block {
+            exprs: Box::new(exprs),
+        }
+    }
+}
+
+/// @. dots every call, operator and assignment, e.g. y = f(x) + 1
+/// becomes y .= f.(x) .+ 1. Indices are left alone
+fn add_dots(node: Node) -> Node {
+    let dotted = |expr: Node| Node::Broadcast {
+        expr: Box::new(expr),
+    };
+    match node {
+        Node::AssignmentExpr {
+            identifier,
+            datatype,
+            value,
+        } => dotted(Node::AssignmentExpr {
+            identifier,
+            datatype,
+            value: Box::new(add_dots(*value)),
+        }),
+        Node::BinaryExpr { op, lhs, rhs } => dotted(Node::BinaryExpr {
+            op,
+            lhs: Box::new(add_dots(*lhs)),
+            rhs: Box::new(add_dots(*rhs)),
+        }),
+        Node::Broadcast { expr } => add_dots(*expr),
+        Node::MethodCall { name, args } => dotted(Node::MethodCall {
+            name,
+            args: Box::new(args.into_iter().map(add_dots).collect()),
+        }),
+        Node::ParenthesesExpr { expr } => Node::ParenthesesExpr {
+            expr: Box::new(add_dots(*expr)),
+        },
+        x => x,
+    }
+}
+
+/// the variables assigned anywhere in nodes, except in the functions
+/// and quotes they define
+pub fn assigned_names(nodes: &[Node]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for node in nodes {
+        let mut node = node.clone();
+        visit_mut(&mut node, &mut |x| match x {
+            Node::AssignmentExpr { identifier, .. } => {
+                names.insert(identifier.clone());
+                true
+            }
+            Node::MultipleAssignment { targets, .. } => {
+                for target in targets.iter() {
+                    if let Node::Symbol(x) = target {
+                        names.insert(x.clone());
+                    }
+                }
+                true
+            }
+            Node::Function { .. } | Node::Interpolation { .. } | Node::Quote { .. } => false,
+            _ => true,
+        });
+    }
+    names
+}
+
+/// the call as julia shows it in a MethodError, e.g. @m(::Symbol, ::Expr)
+pub fn signature(name: &str, args: &[Node]) -> String {
+    let types = args
+        .iter()
+        .map(|x| match x {
+            Node::Symbol(_) => "::Symbol".to_string(),
+            Node::Primitive(Primitive::Nothing) => "::Nothing".to_string(),
+            Node::Primitive(x) => {
+                let name = format!("{:?}", x);
+                format!("::{}", &name[..name.find('(').unwrap()])
+            }
+            _ => "::Expr".to_string(),
+        })
+        .collect::<Vec<_>>();
+    format!("@{}({})", name, types.join(", "))
+}
+
+/// an expression the way it's written in source, e.g. for @show
+pub fn unparse(node: &Node) -> String {
+    let list = |nodes: &[Node]| nodes.iter().map(unparse).collect::<Vec<_>>().join(", ");
+    match node {
+        Node::ArrayExpr { elements } => format!("[{}]", list(elements)),
+        Node::AssignmentExpr {
+            identifier, value, ..
+        } => format!("{} = {}", identifier, unparse(value)),
+        Node::BinaryExpr { op, lhs, rhs } => {
+            format!("{} {} {}", unparse(lhs), op, unparse(rhs))
+        }
+        Node::Broadcast { expr } => match &**expr {
+            Node::AssignmentExpr {
+                identifier, value, ..
+            } => format!("{} .= {}", identifier, unparse(value)),
+            Node::BinaryExpr { op, lhs, rhs } => {
+                format!("{} .{} {}", unparse(lhs), op, unparse(rhs))
+            }
+            Node::MethodCall { name, args } => format!("{}.({})", name, list(args)),
+            x => unparse(x),
+        },
+        Node::IndexExpr { array, indices } => format!("{}[{}]", unparse(array), list(indices)),
+        Node::InterpolatedAssignment { target, value, .. } => {
+            format!("{} = {}", unparse(target), unparse(value))
+        }
+        Node::Interpolation { expr } => match **expr {
+            Node::Symbol(ref x) => format!("${}", x),
+            ref x => format!("$({})", unparse(x)),
+        },
+        Node::MacroCall { name, args } => format!("@{}({})", name, list(args)),
+        Node::MethodCall { name, args } => format!("{}({})", name, list(args)),
+        Node::ParenthesesExpr { expr } => format!("({})", unparse(expr)),
+        Node::Primitive(x) => match x {
+            Primitive::Bool(x) => x.to_string(),
+            Primitive::Char(x) => format!("{:?}", x),
+            Primitive::Float32(x) => format!("{:?}f0", x),
+            Primitive::Float64(x) => format!("{:?}", x),
+            Primitive::Int16(x) => x.to_string(),
+            Primitive::Int32(x) => x.to_string(),
+            Primitive::Int64(x) => x.to_string(),
+            Primitive::Int128(x) => x.to_string(),
+            Primitive::Nothing => "nothing".to_string(),
+            Primitive::String(x) => format!("{:?}", x),
+            Primitive::UInt8(x) => format!("0x{:02x}", x),
+            Primitive::UInt16(x) => format!("0x{:04x}", x),
+            Primitive::UInt32(x) => format!("0x{:08x}", x),
+            Primitive::UInt64(x) => format!("0x{:016x}", x),
+            Primitive::UInt128(x) => format!("0x{:032x}", x),
+        },
+        Node::Symbol(x) => x.clone(),
+        Node::UnaryExpr { op, child } => format!("{}{}", op, unparse(child)),
+        x => format!("{:?}", x),
+    }
+}
+
+/// calls f on node and then on its children for as long as f returns true
+fn visit_mut(node: &mut Node, f: &mut dyn FnMut(&mut Node) -> bool) {
+    if f(node) {
+        for x in children(node) {
+            visit_mut(x, f);
+        }
+    }
+}
+
+fn children(node: &mut Node) -> Vec<&mut Node> {
+    match node {
+        Node::ArrayExpr { elements: exprs }
+        | Node::Block { exprs }
+        | Node::Exports { symbols: exprs }
+        | Node::MacroCall { args: exprs, .. }
+        | Node::MethodCall { args: exprs, .. }
+        | Node::Module { exprs, .. }
+        | Node::Quote { exprs } => exprs.iter_mut().collect(),
+        Node::AssignmentExpr { value: expr, .. }
+        | Node::Broadcast { expr }
+        | Node::ConstExpr { expr }
+        | Node::GlobalExpr { expr }
+        | Node::Interpolation { expr }
+        | Node::ParenthesesExpr { expr }
+        | Node::UnaryExpr { child: expr, .. } => vec![&mut **expr],
+        Node::BinaryExpr { lhs, rhs, .. }
+        | Node::InterpolatedAssignment {
+            target: lhs,
+            value: rhs,
+            ..
+        } => vec![&mut **lhs, &mut **rhs],
+        Node::Function { args, body, .. } | Node::Macro { args, body, .. } => {
+            args.iter_mut().chain(body.iter_mut()).collect()
+        }
+        Node::Let { bindings, exprs }
+        | Node::MultipleAssignment {
+            targets: bindings,
+            values: exprs,
+        } => bindings.iter_mut().chain(exprs.iter_mut()).collect(),
+        Node::IfExpr {
+            condition,
+            if_block,
+            else_block,
+        } => [&mut **condition]
+            .into_iter()
+            .chain(if_block.iter_mut())
+            .chain(else_block.iter_mut())
+            .collect(),
+        Node::IndexExpr { array, indices } => [&mut **array]
+            .into_iter()
+            .chain(indices.iter_mut())
+            .collect(),
+        Node::SetIndexExpr {
+            array,
+            indices,
+            value,
+        } => [&mut **array]
+            .into_iter()
+            .chain(indices.iter_mut())
+            .chain([&mut **value])
+            .collect(),
+        Node::TryExpr {
+            try_block,
+            catch_block,
+            finally_block,
+            ..
+        } => try_block
+            .iter_mut()
+            .chain(catch_block.iter_mut().flat_map(|x| x.iter_mut()))
+            .chain(finally_block.iter_mut().flat_map(|x| x.iter_mut()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
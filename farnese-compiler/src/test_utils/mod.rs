@@ -0,0 +1,67 @@
+use crate::Compiler;
+use core::ffi::c_char;
+use inkwell::OptimizationLevel;
+use inkwell::context::Context;
+use once_cell::sync::Lazy;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+
+static STDOUT_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// compiles a script as Main and runs it without a module cache
+pub fn run(source: &str) -> String {
+    let dir = tempfile::tempdir().unwrap();
+    run_in(dir.path(), source, None)
+}
+
+/// compiles source as dir/main.jl the same way `farnese compiler`
+/// does, so files next to it can be included, and returns what
+/// running its main writes to stdout
+pub fn run_in(dir: &Path, source: &str, cache_dir: Option<&Path>) -> String {
+    let file_name = dir.join("main.jl");
+    fs::write(&file_name, source).unwrap();
+
+    let context = Context::create();
+    let mut compiler = Compiler::with_cache_dir(&context, cache_dir.map(Path::to_path_buf));
+    let mut main_module = compiler.create_module("Main");
+    compiler.insert_module("Main", main_module.clone());
+    compiler.include(&mut main_module, file_name.to_str().unwrap());
+    compiler.link_dependencies(&mut main_module).unwrap();
+    compiler.emit_initializers(&mut main_module);
+
+    // a failed test poisons the lock, the others can still go ahead
+    let _lock = STDOUT_LOCK.lock().unwrap_or_else(|x| x.into_inner());
+    let engine = main_module
+        .module()
+        .create_jit_execution_engine(OptimizationLevel::None)
+        .unwrap();
+
+    unsafe {
+        // redirect stdout to a temporary file while main runs
+        let stdout_fd = libc::dup(libc::STDOUT_FILENO);
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        let tmpfile_path = tmpfile.path().to_owned();
+        let path = format!("{}\0", tmpfile_path.to_str().unwrap());
+        let tmp_fd = libc::open(path.as_ptr() as *const c_char, libc::O_WRONLY);
+        libc::dup2(tmp_fd, libc::STDOUT_FILENO);
+
+        let addr = engine.get_function_address("main").unwrap();
+        let main_fn = std::mem::transmute::<usize, extern "C" fn() -> i32>(addr);
+        let _ = main_fn();
+
+        libc::fflush(ptr::null_mut());
+        libc::dup2(stdout_fd, libc::STDOUT_FILENO);
+        libc::close(tmp_fd);
+        libc::close(stdout_fd);
+
+        let mut output = String::new();
+        File::open(tmpfile_path)
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        output.trim().to_string()
+    }
+}
@@ -0,0 +1,429 @@
+use super::{
+    DataType, FarneseBox, FarneseException, FarneseGC, FarneseInternal, MethodHelper, Module,
+    StructHelper,
+};
+use inkwell::AddressSpace;
+use inkwell::IntPredicate;
+use inkwell::builder::Builder;
+use inkwell::types::{BasicType, BasicTypeEnum};
+use inkwell::values::{BasicValueEnum, IntValue, PointerValue};
+
+/// runtime layout of an Array{T, N}, every array is a heap allocated
+/// header pointing to a separate heap allocated block of elements
+///
+///   %Array = type { i8* data, i64 length, i64 capacity, i64 elsize }
+///
+/// only the header is shared between element types, element access
+/// casts data to T* using the element type of the DataType
+#[derive(Clone, Debug)]
+pub struct Array {
+    datatype: DataType,
+}
+
+impl<'a, 'b> Array {
+    pub fn new(datatype: DataType) -> Self {
+        assert!(
+            datatype.name().name() == "Array",
+            "Expected an Array type, got {}",
+            datatype.full_name()
+        );
+        Self { datatype }
+    }
+
+    pub fn datatype(&self) -> &DataType {
+        &self.datatype
+    }
+
+    pub fn eltype(&self, module: &Module<'a>) -> DataType {
+        module.resolve_type(self.datatype.parameters[0].name())
+    }
+
+    pub fn ndims(&self) -> u32 {
+        self.datatype.parameters[1].name().parse::<u32>().unwrap()
+    }
+
+    fn eltype_ir_type(&self, module: &Module<'a>) -> BasicTypeEnum<'a> {
+        self.eltype(module)
+            .get_ir_value_type(module)
+            .try_into()
+            .unwrap()
+    }
+
+    /// creates the bounds check, which throws a BoundsError for indices
+    /// outside of 1:length. Has to be created after the exception types
+    pub fn create_checkbounds_method(module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let array_ptr_type = module
+            .get_struct_type("Array")
+            .ptr_type(AddressSpace::default());
+        let datatype_ptr_type = module
+            .get_struct_type("DataType")
+            .ptr_type(AddressSpace::default());
+
+        let func = context.void_type().fn_type(
+            &[
+                array_ptr_type.into(),
+                i64_type.into(),
+                datatype_ptr_type.into(),
+            ],
+            false,
+        );
+        let func = module.add_function("__array_checkbounds", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let error = context.append_basic_block(func, "error");
+        let done = context.append_basic_block(func, "done");
+        builder.position_at_end(entry);
+
+        let array_ptr = func.get_nth_method_input(0);
+        let index = func.get_nth_method_input(1).into_int_value();
+        let tag = func.get_nth_method_input(2).into_pointer_value();
+        let length = array_ptr.load_nth_field(&builder, 1).into_int_value();
+        let too_small = builder
+            .build_int_compare(IntPredicate::SLT, index, i64_type.const_int(1, false), "")
+            .unwrap();
+        let too_big = builder
+            .build_int_compare(IntPredicate::SGT, index, length, "")
+            .unwrap();
+        let out_of_bounds = builder.build_or(too_small, too_big, "").unwrap();
+        let _ = builder.build_conditional_branch(out_of_bounds, error, done);
+
+        // every array has the same payload, only the tag differs
+        builder.position_at_end(error);
+        let any_vector = DataType::new_array_type(module.get_type("Any"), 1);
+        let a = FarneseBox::new().emit_ir_box_with_tag(
+            &builder,
+            module,
+            array_ptr.into_pointer_value().into(),
+            &any_vector,
+            tag,
+        );
+        let exception = FarneseException::new();
+        let e = exception.emit_ir_new(&builder, module, "BoundsError", &[a.into(), index.into()]);
+        exception.emit_ir_throw(&builder, module, e);
+
+        builder.position_at_end(done);
+        let _ = builder.build_return(None);
+    }
+
+    /// allocates a new array with length uninitialized elements
+    pub fn emit_ir_new(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        length: IntValue<'a>,
+    ) -> PointerValue<'a> {
+        let elsize = self.eltype_ir_type(module).size_of().unwrap();
+        let is_tracked = module
+            .get_context()
+            .bool_type()
+            .const_int(self.eltype(module).is_gc_tracked() as u64, false);
+        builder
+            .build_call(
+                module.get_function("__array_new"),
+                &[elsize.into(), length.into(), is_tracked.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    pub fn emit_ir_length(
+        &self,
+        builder: &'b Builder<'a>,
+        array_ptr: PointerValue<'a>,
+    ) -> IntValue<'a> {
+        array_ptr.load_nth_field(builder, 1).into_int_value()
+    }
+
+    /// pointer to the element at the 1-based index, optionally
+    /// checking bounds first
+    pub fn emit_ir_element_ptr(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        array_ptr: PointerValue<'a>,
+        index: IntValue<'a>,
+        check_bounds: bool,
+    ) -> PointerValue<'a> {
+        let context = module.get_context();
+        if check_bounds {
+            let tag = self.datatype.emit_ir_object(module);
+            let _ = builder.build_call(
+                module.get_function("__array_checkbounds"),
+                &[array_ptr.into(), index.into(), tag.into()],
+                "",
+            );
+        }
+        let data = array_ptr.load_nth_field(builder, 0).into_pointer_value();
+        let data = builder
+            .build_pointer_cast(
+                data,
+                self.eltype_ir_type(module)
+                    .ptr_type(AddressSpace::default()),
+                "",
+            )
+            .unwrap();
+        let offset = builder
+            .build_int_sub(index, context.i64_type().const_int(1, false), "")
+            .unwrap();
+        unsafe { builder.build_in_bounds_gep(data, &[offset], "") }.unwrap()
+    }
+
+    pub fn emit_ir_getindex(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        array_ptr: PointerValue<'a>,
+        index: IntValue<'a>,
+    ) -> BasicValueEnum<'a> {
+        let ptr = self.emit_ir_element_ptr(builder, module, array_ptr, index, true);
+        builder.build_load(ptr, "").unwrap()
+    }
+
+    pub fn emit_ir_setindex(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        array_ptr: PointerValue<'a>,
+        index: IntValue<'a>,
+        val: BasicValueEnum<'a>,
+    ) {
+        let ptr = self.emit_ir_element_ptr(builder, module, array_ptr, index, true);
+        let _ = builder.build_store(ptr, val);
+    }
+
+    /// appends val to the end of the array, growing it if needed
+    pub fn emit_ir_push(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        array_ptr: PointerValue<'a>,
+        val: BasicValueEnum<'a>,
+    ) {
+        let _ = builder.build_call(module.get_function("__array_push"), &[array_ptr.into()], "");
+        let length = self.emit_ir_length(builder, array_ptr);
+        let ptr = self.emit_ir_element_ptr(builder, module, array_ptr, length, false);
+        let _ = builder.build_store(ptr, val);
+    }
+
+    /// allocates an array and fills it with the supplied values
+    pub fn emit_ir_literal(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        vals: &[BasicValueEnum<'a>],
+    ) -> PointerValue<'a> {
+        let i64_type = module.get_context().i64_type();
+        let length = i64_type.const_int(vals.len() as u64, false);
+        let array_ptr = self.emit_ir_new(builder, module, length);
+        for (n, val) in vals.iter().enumerate() {
+            let index = i64_type.const_int(n as u64 + 1, false);
+            let ptr = self.emit_ir_element_ptr(builder, module, array_ptr, index, false);
+            let _ = builder.build_store(ptr, *val);
+        }
+        array_ptr
+    }
+
+    fn create_push_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let array_ptr_type = module
+            .get_struct_type("Array")
+            .ptr_type(AddressSpace::default());
+
+        // grows the array by one element, reallocating data when full
+        let func = context.void_type().fn_type(&[array_ptr_type.into()], false);
+        let func = module.add_function("__array_push", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let grow = context.append_basic_block(func, "grow");
+        let done = context.append_basic_block(func, "done");
+        builder.position_at_end(entry);
+
+        let array_ptr = func.get_nth_method_input(0).into_pointer_value();
+        let length = array_ptr.load_nth_field(&builder, 1).into_int_value();
+        let capacity = array_ptr.load_nth_field(&builder, 2).into_int_value();
+        let is_full = builder
+            .build_int_compare(IntPredicate::EQ, length, capacity, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_full, grow, done);
+
+        builder.position_at_end(grow);
+        let doubled = builder
+            .build_int_mul(capacity, i64_type.const_int(2, false), "")
+            .unwrap();
+        let is_small = builder
+            .build_int_compare(IntPredicate::SLT, doubled, i64_type.const_int(4, false), "")
+            .unwrap();
+        let new_capacity = builder
+            .build_select(is_small, i64_type.const_int(4, false), doubled, "")
+            .unwrap()
+            .into_int_value();
+        let elsize = array_ptr.load_nth_field(&builder, 3).into_int_value();
+        let num_bytes = builder.build_int_mul(new_capacity, elsize, "").unwrap();
+        let data = array_ptr.load_nth_field(&builder, 0);
+        let new_data = builder
+            .build_call(
+                module.get_function("__gc_realloc"),
+                &[data.into(), num_bytes.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        array_ptr.set_nth_field(&builder, 0, new_data.into());
+        let _ = builder.build_store(array_ptr.get_nth_field(&builder, 2), new_capacity);
+        let _ = builder.build_unconditional_branch(done);
+
+        builder.position_at_end(done);
+        let new_length = builder
+            .build_int_add(length, i64_type.const_int(1, false), "")
+            .unwrap();
+        let _ = builder.build_store(array_ptr.get_nth_field(&builder, 1), new_length);
+        let _ = builder.build_return(None);
+    }
+}
+
+impl<'a> FarneseInternal<'a> for Array {
+    fn bootstrap(&self, module: &Module<'a>) {
+        self.create_opaque_type(module);
+        self.create_new_method(module);
+        self.create_get_methods(module);
+        self.create_push_method(module);
+    }
+
+    fn create_opaque_type(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let opaque_type = context.opaque_struct_type("Array");
+        opaque_type.set_body(
+            &[
+                context.i8_type().ptr_type(AddressSpace::default()).into(),
+                context.i64_type().into(),
+                context.i64_type().into(),
+                context.i64_type().into(),
+            ],
+            false,
+        );
+    }
+
+    fn create_new_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let array_type = module.get_struct_type("Array");
+        let array_ptr_type = array_type.ptr_type(AddressSpace::default());
+
+        // elements are traced by the GC when is_tracked is set
+        let func = array_ptr_type.fn_type(
+            &[i64_type.into(), i64_type.into(), context.bool_type().into()],
+            false,
+        );
+        let func = module.add_function("__array_new", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let elsize = func.get_nth_method_input(0).into_int_value();
+        let length = func.get_nth_method_input(1).into_int_value();
+        let is_tracked = func.get_nth_method_input(2).into_int_value();
+        let num_bytes = builder.build_int_mul(elsize, length, "").unwrap();
+        let gc = FarneseGC::new();
+        let data = gc.emit_ir_alloc_bytes(&builder, module, num_bytes, is_tracked);
+
+        let array_ptr = gc.emit_ir_alloc(&builder, module, array_type.into(), 0, 1);
+        array_ptr.set_nth_field(&builder, 0, data.into());
+        let _ = builder.build_store(array_ptr.get_nth_field(&builder, 1), length);
+        let _ = builder.build_store(array_ptr.get_nth_field(&builder, 2), length);
+        let _ = builder.build_store(array_ptr.get_nth_field(&builder, 3), elsize);
+        let _ = builder.build_return(Some(&array_ptr));
+    }
+
+    fn create_get_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let array_ptr_type = module
+            .get_struct_type("Array")
+            .ptr_type(AddressSpace::default());
+
+        // create length function
+        let func = i64_type.fn_type(&[array_ptr_type.into()], false);
+        let func = module.add_function("__array_length", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let array_ptr = func.get_nth_method_input(0);
+        let length = array_ptr.load_nth_field(&builder, 1);
+        let _ = builder.build_return(Some(&length));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LLVMPrintf;
+    use crate::test_utils::TestHelper;
+    use inkwell::context::Context;
+
+    #[test]
+    fn test_array_getindex_setindex() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_array_getindex_setindex", &builder, &context);
+        tester.start();
+
+        let i64_type = context.i64_type();
+        let datatype = tester.module.resolve_type("Vector{Float64}");
+        let array = Array::new(datatype);
+        let array_ptr = array.emit_ir_new(&builder, &tester.module, i64_type.const_int(3, false));
+        let val = context.f64_type().const_float(69.0);
+        let index = i64_type.const_int(2, false);
+        array.emit_ir_setindex(&builder, &tester.module, array_ptr, index, val.into());
+        let val = array.emit_ir_getindex(&builder, &tester.module, array_ptr, index);
+        let _ = (val.into(), array.eltype(&tester.module)).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+
+        let test_output = tester.run();
+        assert_eq!(69.0, test_output.parse::<f64>().unwrap());
+    }
+
+    #[test]
+    fn test_array_push() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_array_push", &builder, &context);
+        tester.start();
+
+        let i64_type = context.i64_type();
+        let datatype = tester.module.resolve_type("Vector{Int64}");
+        let array = Array::new(datatype);
+        let vals = [1, 2, 3]
+            .iter()
+            .map(|x| i64_type.const_int(*x, false).into())
+            .collect::<Vec<_>>();
+        let array_ptr = array.emit_ir_literal(&builder, &tester.module, &vals);
+        for x in 4..10 {
+            let val = i64_type.const_int(x, false);
+            array.emit_ir_push(&builder, &tester.module, array_ptr, val.into());
+        }
+        let val = array.emit_ir_getindex(
+            &builder,
+            &tester.module,
+            array_ptr,
+            i64_type.const_int(9, false),
+        );
+        let length = array.emit_ir_length(&builder, array_ptr);
+        let int64 = tester.module.get_type("Int64").clone();
+        let _ = (val.into(), int64.clone()).emit_ir_printf(&builder, &tester.module);
+        let _ = (length.into(), int64).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+
+        let test_output = tester.run();
+        assert_eq!("99", test_output);
+    }
+}
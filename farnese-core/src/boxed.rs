@@ -0,0 +1,346 @@
+use super::{DataType, FarneseGC, FarneseInternal, LLVMPrintf, MethodHelper, Module, StructHelper};
+use inkwell::builder::Builder;
+use inkwell::types::{BasicType, BasicTypeEnum};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, IntValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// values of abstract types like Any or Number are boxed, a heap
+/// allocated type tag followed by the value itself
+///
+///   %Box = type { %DataType* tag }
+///
+/// only the tag is shared between boxes, the payload is read by
+/// casting the box to { %DataType*, T }* once the tag is known
+#[derive(Clone, Debug)]
+pub struct FarneseBox;
+
+impl<'a, 'b> FarneseBox {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn payload_type(&self, module: &Module<'a>, datatype: &DataType) -> BasicTypeEnum<'a> {
+        let tag_type = module
+            .get_struct_type("DataType")
+            .ptr_type(AddressSpace::default());
+        let ir_type: BasicTypeEnum<'a> = datatype.get_ir_value_type(module).try_into().unwrap();
+        module
+            .get_context()
+            .struct_type(&[tag_type.into(), ir_type], false)
+            .into()
+    }
+
+    /// copies val into a new box tagged with its type
+    pub fn emit_ir_box(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: BasicValueEnum<'a>,
+        datatype: &DataType,
+    ) -> PointerValue<'a> {
+        let tag = datatype.emit_ir_object(module);
+        self.emit_ir_box_with_tag(builder, module, val, datatype, tag)
+    }
+
+    /// boxes val with a tag only known at runtime, datatype has to
+    /// have the same layout as the tagged type, e.g. any Array
+    pub fn emit_ir_box_with_tag(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: BasicValueEnum<'a>,
+        datatype: &DataType,
+        tag: PointerValue<'a>,
+    ) -> PointerValue<'a> {
+        let payload_type = self.payload_type(module, datatype);
+        let count = datatype.is_gc_tracked() as u64;
+        let ptr = FarneseGC::new().emit_ir_alloc(builder, module, payload_type, 1, count);
+        ptr.set_nth_field(builder, 0, tag.into());
+        let _ = builder.build_store(ptr.get_nth_field(builder, 1), val);
+        builder
+            .build_pointer_cast(
+                ptr,
+                module
+                    .get_struct_type("Box")
+                    .ptr_type(AddressSpace::default()),
+                "",
+            )
+            .unwrap()
+    }
+
+    /// reads the value out of a box, the tag has to be checked first
+    pub fn emit_ir_unbox(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+        datatype: &DataType,
+    ) -> BasicValueEnum<'a> {
+        let payload_type = self.payload_type(module, datatype);
+        let ptr = builder
+            .build_pointer_cast(ptr, payload_type.ptr_type(AddressSpace::default()), "")
+            .unwrap();
+        ptr.load_nth_field(builder, 1)
+    }
+
+    /// the runtime type of the boxed value
+    pub fn emit_ir_typeof(
+        &self,
+        builder: &'b Builder<'a>,
+        ptr: PointerValue<'a>,
+    ) -> PointerValue<'a> {
+        ptr.load_nth_field(builder, 0).into_pointer_value()
+    }
+
+    /// whether the boxed value is of the given type or one of its subtypes
+    pub fn emit_ir_isa(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+        datatype: &DataType,
+    ) -> IntValue<'a> {
+        let tag = self.emit_ir_typeof(builder, ptr);
+        builder
+            .build_call(
+                module.get_function("__isa"),
+                &[tag.into(), datatype.emit_ir_object(module).into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value()
+    }
+
+    /// unboxes the value, aborting with a TypeError if it isn't a datatype
+    pub fn emit_ir_typeassert(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+        datatype: &DataType,
+    ) -> BasicValueEnum<'a> {
+        let _ = builder.build_call(
+            module.get_function("__typeassert"),
+            &[ptr.into(), datatype.emit_ir_object(module).into()],
+            "",
+        );
+        self.emit_ir_unbox(builder, module, ptr, datatype)
+    }
+
+    /// prints a boxed value by switching on its tag, has to be
+    /// created after every Core type is printable
+    pub fn create_print_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let box_ptr_type = module
+            .get_struct_type("Box")
+            .ptr_type(AddressSpace::default());
+
+        let func = context.void_type().fn_type(&[box_ptr_type.into()], false);
+        let func = module.add_function("__print_box", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let ptr = func.get_nth_method_input(0).into_pointer_value();
+        let tag = self.emit_ir_typeof(&builder, ptr);
+
+        let mut datatypes = module
+            .get_types()
+            .values()
+            .filter(|x| !x.is_abstract && !x.is_struct() && x.name().name() != "Array")
+            .collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+        for datatype in datatypes {
+            let found = context.append_basic_block(func, datatype.name().name());
+            let next = context.append_basic_block(func, "");
+            let is_type = builder
+                .build_int_compare(IntPredicate::EQ, tag, datatype.emit_ir_object(module), "")
+                .unwrap();
+            let _ = builder.build_conditional_branch(is_type, found, next);
+
+            builder.position_at_end(found);
+            let val = self.emit_ir_unbox(&builder, module, ptr, datatype);
+            let val: BasicMetadataValueEnum<'a> = val.into();
+            let _ = (val, datatype.clone()).emit_ir_printf(&builder, module);
+            let _ = builder.build_return(None);
+            builder.position_at_end(next);
+        }
+
+        // types without a printf, e.g. user defined ones, show their name
+        let tag: BasicMetadataValueEnum<'a> = tag.into();
+        let _ = (tag, module.get_type("DataType").clone()).emit_ir_printf(&builder, module);
+        let _ = builder.build_return(None);
+    }
+}
+
+impl Default for FarneseBox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FarneseInternal<'a> for FarneseBox {
+    fn create_opaque_type(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let tag_type = module
+            .get_struct_type("DataType")
+            .ptr_type(AddressSpace::default());
+        let opaque_type = context.opaque_struct_type("Box");
+        opaque_type.set_body(&[tag_type.into()], false);
+    }
+
+    fn create_new_method(&self, _module: &Module<'a>) {}
+
+    fn create_get_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let bool_type = context.bool_type();
+        let box_ptr_type = module
+            .get_struct_type("Box")
+            .ptr_type(AddressSpace::default());
+        let datatype_ptr_type = module
+            .get_struct_type("DataType")
+            .ptr_type(AddressSpace::default());
+
+        // create isa, walks up the supertypes until Any
+        let func = bool_type.fn_type(&[datatype_ptr_type.into(), datatype_ptr_type.into()], false);
+        let func = module.add_function("__isa", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let body = context.append_basic_block(func, "body");
+        let next = context.append_basic_block(func, "next");
+        let found = context.append_basic_block(func, "found");
+        let missing = context.append_basic_block(func, "missing");
+        builder.position_at_end(entry);
+
+        let datatype = func.get_nth_method_input(1).into_pointer_value();
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(body);
+        let current = builder.build_phi(datatype_ptr_type, "").unwrap();
+        current.add_incoming(&[(&func.get_nth_param(0).unwrap(), entry)]);
+        let current_ptr = current.as_basic_value().into_pointer_value();
+        let is_type = builder
+            .build_int_compare(IntPredicate::EQ, current_ptr, datatype, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_type, found, next);
+
+        builder.position_at_end(next);
+        let supertype = builder
+            .build_call(module.get_function("supertype"), &[current_ptr.into()], "")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        current.add_incoming(&[(&supertype, next)]);
+        // Any is its own supertype
+        let is_any = builder
+            .build_int_compare(IntPredicate::EQ, supertype, current_ptr, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_any, missing, body);
+
+        builder.position_at_end(found);
+        let _ = builder.build_return(Some(&bool_type.const_int(1, false)));
+        builder.position_at_end(missing);
+        let _ = builder.build_return(Some(&bool_type.const_zero()));
+
+        // create typeassert, aborts with a TypeError if the box
+        // doesn't hold a value of the given type
+        let func = context
+            .void_type()
+            .fn_type(&[box_ptr_type.into(), datatype_ptr_type.into()], false);
+        let func = module.add_function("__typeassert", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let error = context.append_basic_block(func, "error");
+        let done = context.append_basic_block(func, "done");
+        builder.position_at_end(entry);
+
+        let tag = self.emit_ir_typeof(&builder, func.get_nth_method_input(0).into_pointer_value());
+        let datatype = func.get_nth_method_input(1).into_pointer_value();
+        let is_type = builder
+            .build_call(
+                module.get_function("__isa"),
+                &[tag.into(), datatype.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let _ = builder.build_conditional_branch(is_type, done, error);
+
+        builder.position_at_end(error);
+        let name = |x: PointerValue<'a>| {
+            let sym_ptr = x.load_nth_field(&builder, 0).into_pointer_value();
+            sym_ptr.load_nth_field(&builder, 1)
+        };
+        let format_string = builder
+            .build_global_string_ptr(
+                "TypeError: in typeassert, expected %s, got a value of type %s\n",
+                "",
+            )
+            .unwrap()
+            .as_pointer_value();
+        let args: Vec<BasicMetadataValueEnum<'a>> = vec![
+            format_string.into(),
+            name(datatype).into(),
+            name(tag).into(),
+        ];
+        let _ = builder.build_call(module.get_function("printf"), &args, "");
+        let _ = builder.build_call(
+            module.get_function("exit"),
+            &[context.i32_type().const_int(1, false).into()],
+            "",
+        );
+        let _ = builder.build_unreachable();
+
+        builder.position_at_end(done);
+        let _ = builder.build_return(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestHelper;
+    use inkwell::context::Context;
+
+    #[test]
+    fn test_box() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_box", &builder, &context);
+        tester.start();
+
+        let box_helper = FarneseBox::new();
+        let int = tester.module.get_type("Int64").clone();
+        let val = context.i64_type().const_int(42, false);
+        let ptr = box_helper.emit_ir_box(&builder, &tester.module, val.into(), &int);
+        let _ = builder.build_call(tester.module.get_function("__print_box"), &[ptr.into()], "");
+
+        // Int64 <: Integer but not <: AbstractFloat
+        for name in ["Int64", "Integer", "Any", "AbstractFloat"] {
+            let datatype = tester.module.get_type(name).clone();
+            let isa = box_helper.emit_ir_isa(&builder, &tester.module, ptr, &datatype);
+            let _ = (isa.into(), tester.module.get_type("Bool").clone())
+                .emit_ir_printf(&builder, &tester.module);
+        }
+
+        let val = box_helper.emit_ir_typeassert(&builder, &tester.module, ptr, &int);
+        let val = builder
+            .build_int_add(
+                val.into_int_value(),
+                context.i64_type().const_int(1, false),
+                "",
+            )
+            .unwrap();
+        let _ = (val.into(), int).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+
+        assert_eq!("42truetruetruefalse43", tester.run());
+    }
+}
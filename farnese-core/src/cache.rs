@@ -0,0 +1,452 @@
+use super::{DataType, Module, Symbol};
+use inkwell::context::Context;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+use std::path::PathBuf;
+
+/// version baked into every cache key, it doesn't change between
+/// builds so the cache is also tied to the build that wrote it
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// FNV-1a, unlike DefaultHasher it is stable between runs and
+/// rust versions which matters for keys stored on disk
+pub struct SourceHasher(u64);
+
+impl SourceHasher {
+    pub fn new() -> Self {
+        let mut hasher = Self(0xcbf29ce484222325);
+        hasher.write(VERSION.as_bytes());
+        hasher
+    }
+}
+
+impl Default for SourceHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for SourceHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// precompiled modules on disk, each module is stored as
+/// <Name>-<key>.bc with the LLVM bitcode and <Name>-<key>.ji
+/// with its types, exports, globals, methods and dependencies
+///
+/// entries are only valid for the build that wrote them, which is
+/// checked against the header of the .ji file
+#[derive(Clone, Debug)]
+pub struct ModuleCache {
+    build: u64,
+    dir: PathBuf,
+}
+
+impl ModuleCache {
+    pub fn new(dir: PathBuf, build: u64) -> Self {
+        Self {
+            build,
+            dir: dir.join(format!("v{}", VERSION)),
+        }
+    }
+
+    /// identifies a build of the compiler by the IR of its Core, so
+    /// changes to the layout of Core's types or its runtime invalidate
+    /// everything compiled against it
+    pub fn build_id(core: &Module) -> u64 {
+        let mut hasher = SourceHasher::new();
+        hasher.write(core.module().print_to_string().to_bytes());
+        hasher.finish()
+    }
+
+    fn header(&self) -> String {
+        format!("farnese {} {:016x}", VERSION, self.build)
+    }
+
+    fn path(&self, name: &Symbol, key: u64, extension: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}-{:016x}.{}", name, key, extension))
+    }
+
+    /// loads a module if it was cached with the same key and all of
+    /// its dependencies still have the keys it was compiled against
+    pub fn load<'a>(
+        &self,
+        context: &'a Context,
+        name: &Symbol,
+        key: u64,
+        keys: &HashMap<Symbol, u64>,
+    ) -> Option<Module<'a>> {
+        let metadata = fs::read_to_string(self.path(name, key, "ji")).ok()?;
+        let mut lines = metadata.lines();
+        if lines.next()? != self.header() {
+            return None;
+        }
+
+        let mut module = Module::from_bitcode(context, name.name(), &self.path(name, key, "bc"))?;
+        for line in lines {
+            let (kind, rest) = line.split_once(' ')?;
+            match kind {
+                "binding" => {
+                    let (mangled, owner) = rest.rsplit_once(' ')?;
+                    module.insert_binding(Symbol::new(mangled), Symbol::new(owner));
+                }
+                "dependency" => {
+                    let (dependency, dependency_key) = rest.split_once(' ')?;
+                    let dependency = Symbol::new(dependency);
+                    let dependency_key = u64::from_str_radix(dependency_key, 16).ok()?;
+                    if keys.get(&dependency) != Some(&dependency_key) {
+                        return None;
+                    }
+                    module.push_dependency(dependency);
+                }
+                "export" => module.push_export(Symbol::new(rest)),
+                // the global itself is already in the bitcode
+                "global" => {
+                    let (name, rest) = rest.split_once(' ')?;
+                    let (ir_name, rest) = rest.split_once(' ')?;
+                    let (is_const, rest) = rest.split_once(' ')?;
+                    let datatype = match Sexpr::parse(rest)?.as_slice() {
+                        [x] => decode_datatype(x)?,
+                        _ => return None,
+                    };
+                    module.insert_global_declaration(
+                        Symbol::new(name),
+                        Symbol::new(ir_name),
+                        datatype,
+                        is_const == "1",
+                    );
+                }
+                // mangled names can have spaces, e.g. f_Array{Int64, 2}
+                "method" => {
+                    let sexprs = Sexpr::parse(rest)?;
+                    let (method, sexprs) = sexprs.split_first()?;
+                    let mut datatypes = sexprs
+                        .iter()
+                        .map(decode_datatype)
+                        .collect::<Option<Vec<_>>>()?;
+                    if datatypes.is_empty() {
+                        return None;
+                    }
+                    let return_type = datatypes.remove(0);
+                    module.insert_method(Symbol::new(method.atom()?), datatypes, return_type);
+                }
+                "type" => match Sexpr::parse(rest)?.as_slice() {
+                    [x] => module.insert_type(decode_datatype(x)?),
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+        Some(module)
+    }
+
+    /// writes a module to the cache, keys has the current key of
+    /// every module it depends on
+    pub fn store(&self, module: &Module, key: u64, keys: &HashMap<Symbol, u64>) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut metadata = vec![self.header()];
+        for dependency in module.get_dependencies() {
+            let dependency_key = keys.get(dependency).ok_or(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no key for dependency {}", dependency),
+            ))?;
+            metadata.push(format!("dependency {} {:016x}", dependency, dependency_key));
+        }
+        for export in module.get_exports() {
+            metadata.push(format!("export {}", export));
+        }
+        // sorted so the same module always gives the same file
+        let mut globals = module.get_globals().iter().collect::<Vec<_>>();
+        globals.sort_by(|a, b| a.0.name().cmp(b.0.name()));
+        for (name, (ir_name, datatype, is_const)) in globals {
+            metadata.push(format!(
+                "global {} {} {} {}",
+                name,
+                ir_name,
+                if *is_const { 1 } else { 0 },
+                encode_datatype(datatype)
+            ));
+        }
+        let mut types = module.get_types().values().collect::<Vec<_>>();
+        types.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+        for datatype in types {
+            metadata.push(format!("type {}", encode_datatype(datatype)));
+        }
+        let mut bindings = module.get_bindings().iter().collect::<Vec<_>>();
+        bindings.sort_by(|a, b| a.0.name().cmp(b.0.name()));
+        for (mangled, owner) in bindings {
+            metadata.push(format!("binding {} {}", mangled, owner));
+        }
+        let mut methods = module.get_methods().iter().collect::<Vec<_>>();
+        methods.sort_by(|a, b| a.0.name().cmp(b.0.name()));
+        for (method, (arg_types, return_type)) in methods {
+            let datatypes = [return_type]
+                .into_iter()
+                .chain(arg_types.iter())
+                .map(encode_datatype)
+                .collect::<Vec<_>>();
+            metadata.push(format!(
+                "method {} {}",
+                quote(method.name()),
+                datatypes.join(" ")
+            ));
+        }
+
+        // write the bitcode first so metadata never points at nothing
+        if !module
+            .module()
+            .write_bitcode_to_path(self.path(&module.name(), key, "bc"))
+        {
+            return Err(io::Error::other(format!(
+                "failed writing bitcode for module {}",
+                module.name()
+            )));
+        }
+        fs::write(
+            self.path(&module.name(), key, "ji"),
+            metadata.join("\n") + "\n",
+        )
+    }
+}
+
+/// datatypes are written as ("name" "module" "supertype" "abstract"
+/// "mutable" "primitive" "bits" (parameters...) ((field type)...))
+fn encode_datatype(datatype: &DataType) -> String {
+    let flag = |x: bool| if x { "1" } else { "0" };
+    let parameters = datatype
+        .parameters
+        .iter()
+        .map(|x| quote(x.name()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let fields = datatype
+        .field_names
+        .iter()
+        .zip(datatype.field_types.iter())
+        .map(|(name, field_type)| {
+            format!("({} {})", quote(name.name()), encode_datatype(field_type))
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!(
+        "({} {} {} {} {} {} {} ({}) ({}))",
+        quote(datatype.name.name()),
+        quote(datatype.module.name()),
+        quote(datatype.supertype.name()),
+        quote(flag(datatype.is_abstract)),
+        quote(flag(datatype.is_mutable)),
+        quote(flag(datatype.is_primitive)),
+        quote(&datatype.bits.to_string()),
+        parameters,
+        fields
+    )
+}
+
+fn decode_datatype(sexpr: &Sexpr) -> Option<DataType> {
+    let flag = |x: &Sexpr| match x.atom()? {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    };
+    match sexpr {
+        Sexpr::List(x) => match x.as_slice() {
+            [
+                name,
+                module,
+                supertype,
+                is_abstract,
+                is_mutable,
+                is_primitive,
+                bits,
+                Sexpr::List(parameters),
+                Sexpr::List(fields),
+            ] => {
+                let mut field_names = Vec::<Symbol>::new();
+                let mut field_types = Vec::<DataType>::new();
+                for field in fields {
+                    match field {
+                        Sexpr::List(x) if x.len() == 2 => {
+                            field_names.push(Symbol::new(x[0].atom()?));
+                            field_types.push(decode_datatype(&x[1])?);
+                        }
+                        _ => return None,
+                    }
+                }
+                let mut datatype = DataType::new(
+                    Symbol::new(name.atom()?),
+                    Symbol::new(supertype.atom()?),
+                    flag(is_abstract)?,
+                    flag(is_mutable)?,
+                    flag(is_primitive)?,
+                    field_names,
+                    Box::new(field_types),
+                );
+                datatype.bits = bits.atom()?.parse().ok()?;
+                datatype.module = Symbol::new(module.atom()?);
+                datatype.parameters = parameters
+                    .iter()
+                    .map(|x| Some(Symbol::new(x.atom()?)))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(datatype)
+            }
+            _ => None,
+        },
+        Sexpr::Atom(_) => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Sexpr {
+    Atom(String),
+    List(Vec<Sexpr>),
+}
+
+impl Sexpr {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexpr::Atom(x) => Some(x),
+            Sexpr::List(_) => None,
+        }
+    }
+
+    /// parses a sequence of space separated expressions
+    fn parse(source: &str) -> Option<Vec<Sexpr>> {
+        let mut stack = vec![Vec::<Sexpr>::new()];
+        let mut chars = source.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' => {}
+                '(' => stack.push(Vec::new()),
+                ')' => {
+                    let list = stack.pop()?;
+                    stack.last_mut()?.push(Sexpr::List(list));
+                }
+                '"' => {
+                    let mut atom = String::new();
+                    loop {
+                        match chars.next()? {
+                            '"' => break,
+                            '\\' => match chars.next()? {
+                                'n' => atom.push('\n'),
+                                x => atom.push(x),
+                            },
+                            x => atom.push(x),
+                        }
+                    }
+                    stack.last_mut()?.push(Sexpr::Atom(atom));
+                }
+                _ => return None,
+            }
+        }
+        match stack.len() {
+            1 => stack.pop(),
+            _ => None,
+        }
+    }
+}
+
+/// names can have spaces or parentheses in them, e.g. Vector{Float64}
+fn quote(x: &str) -> String {
+    let x = x
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LLVMPrintf;
+    use crate::test_utils::TestHelper;
+
+    #[test]
+    fn test_datatype_roundtrip() {
+        let float64 = DataType::new_primitive_type("Float64", "AbstractFloat", 64);
+        let vector = DataType::new_array_type(&float64, 1);
+        let pair = DataType::new(
+            Symbol::new("Pair"),
+            Symbol::new("Any"),
+            false,
+            true,
+            false,
+            vec![Symbol::new("a"), Symbol::new("b")],
+            Box::new(vec![float64, vector.clone()]),
+        );
+        let odd = DataType::new_abstract_type("Odd(\"\\", "<: Real ");
+        let int24 = DataType::new_primitive_type("Int24", "Signed", 24);
+        for datatype in [vector, pair, odd, int24] {
+            let sexpr = Sexpr::parse(&encode_datatype(&datatype)).unwrap();
+            assert_eq!(Some(datatype), decode_datatype(&sexpr[0]));
+        }
+    }
+
+    #[test]
+    fn test_module_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ModuleCache::new(dir.path().to_path_buf(), 1);
+        let context = Context::create();
+        let int64 = DataType::new_primitive_type("Int64", "Signed", 64);
+        let keys = HashMap::from([(Symbol::new("Bar"), 7)]);
+
+        // module Foo with answer()::Int64 = 42
+        let mut foo = Module::new(&context, "Foo");
+        let func = context.i64_type().fn_type(&[], false);
+        let func = foo.add_function("Foo.answer_", func, None);
+        let builder = context.create_builder();
+        builder.position_at_end(context.append_basic_block(func, "entry"));
+        let _ = builder.build_return(Some(&context.i64_type().const_int(42, false)));
+        foo.insert_method(Symbol::new("answer_"), vec![], int64.clone());
+        foo.insert_binding(Symbol::new("twice_Int64"), Symbol::new("Bar"));
+        foo.insert_type(DataType::new_abstract_type("Shape", "Any"));
+        foo.push_export(Symbol::new("answer"));
+        foo.push_dependency(Symbol::new("Bar"));
+        cache.store(&foo, 1, &keys).unwrap();
+
+        // a different key, build or a changed dependency is a miss
+        assert!(cache.load(&context, &foo.name(), 2, &keys).is_none());
+        let rebuilt = ModuleCache::new(dir.path().to_path_buf(), 2);
+        assert!(rebuilt.load(&context, &foo.name(), 1, &keys).is_none());
+        let changed = HashMap::from([(Symbol::new("Bar"), 8)]);
+        assert!(cache.load(&context, &foo.name(), 1, &changed).is_none());
+
+        let cached = cache.load(&context, &foo.name(), 1, &keys).unwrap();
+        assert_eq!(cached.get_exports(), foo.get_exports());
+        assert_eq!(cached.get_types(), foo.get_types());
+        assert_eq!(cached.get_methods(), foo.get_methods());
+        assert_eq!(cached.get_bindings(), foo.get_bindings());
+        assert_eq!(cached.get_dependencies(), foo.get_dependencies());
+
+        let mut tester = TestHelper::new("test_module_cache", &builder, &context);
+        tester.module.import(&cached, &[]).unwrap();
+        tester.start();
+        let val = builder
+            .build_call(
+                tester
+                    .module
+                    .get_function(&tester.module.method_symbol("answer_")),
+                &[],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .unwrap_left();
+        let _ = (val.into(), int64).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+        tester.module.link_in_module(&cached).unwrap();
+
+        assert_eq!("42", tester.run());
+    }
+}
@@ -0,0 +1,203 @@
+use super::{FarneseInternal, MethodHelper, Module};
+use inkwell::builder::Builder;
+use inkwell::values::{IntValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// a Char is a single unicode scalar value stored as an i32,
+/// it's only encoded to UTF-8 when it needs to be printed or
+/// turned into a String
+#[derive(Clone, Debug)]
+pub struct FarneseChar;
+
+impl<'a, 'b> FarneseChar {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn emit_ir_value(&self, module: &Module<'a>, value: char) -> IntValue<'a> {
+        module
+            .get_context()
+            .i32_type()
+            .const_int(value as u64, false)
+    }
+
+    /// encodes the char into a NUL terminated stack buffer
+    pub fn emit_ir_utf8(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: IntValue<'a>,
+    ) -> (PointerValue<'a>, IntValue<'a>) {
+        let context = module.get_context();
+        let buffer = builder
+            .build_array_alloca(
+                context.i8_type(),
+                context.i64_type().const_int(5, false),
+                "",
+            )
+            .unwrap();
+        let length = builder
+            .build_call(
+                module.get_function("__char_utf8"),
+                &[val.into(), buffer.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        (buffer, length)
+    }
+
+    pub fn emit_ir_to_string(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: IntValue<'a>,
+    ) -> PointerValue<'a> {
+        let (buffer, length) = self.emit_ir_utf8(builder, module, val);
+        builder
+            .build_call(
+                module.get_function("__string_new"),
+                &[buffer.into(), length.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+}
+
+impl Default for FarneseChar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FarneseInternal<'a> for FarneseChar {
+    // a Char is just an i32 so there's no struct to set up
+    fn create_opaque_type(&self, _module: &Module<'a>) {}
+
+    fn create_new_method(&self, _module: &Module<'a>) {}
+
+    /// __char_utf8(c, buffer) writes the 1 to 4 byte encoding of c
+    /// followed by a NUL and returns the number of bytes
+    fn create_get_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i8_type = context.i8_type();
+        let i32_type = context.i32_type();
+        let i64_type = context.i64_type();
+
+        let func = i64_type.fn_type(
+            &[
+                i32_type.into(),
+                i8_type.ptr_type(AddressSpace::default()).into(),
+            ],
+            false,
+        );
+        let func = module.add_function("__char_utf8", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let blocks = (1..=4)
+            .map(|n| context.append_basic_block(func, &format!("bytes{}", n)))
+            .collect::<Vec<_>>();
+        let c = func.get_nth_method_input(0).into_int_value();
+        let buffer = func.get_nth_method_input(1).into_pointer_value();
+
+        // pick the number of bytes based off of the largest code point
+        // that fits, each check falls through to the next one
+        builder.position_at_end(entry);
+        for (n, limit) in [0x80, 0x800, 0x10000].into_iter().enumerate() {
+            let next = if n == 2 {
+                blocks[3]
+            } else {
+                context.append_basic_block(func, "")
+            };
+            let fits = builder
+                .build_int_compare(IntPredicate::ULT, c, i32_type.const_int(limit, false), "")
+                .unwrap();
+            let _ = builder.build_conditional_branch(fits, blocks[n], next);
+            builder.position_at_end(next);
+        }
+
+        let leads = [0x00, 0xc0, 0xe0, 0xf0];
+        for (n, block) in blocks.iter().enumerate() {
+            builder.position_at_end(*block);
+            let num_bytes = n + 1;
+            for i in 0..num_bytes {
+                let shift = 6 * (num_bytes - 1 - i) as u64;
+                let bits = builder
+                    .build_right_shift(c, i32_type.const_int(shift, false), false, "")
+                    .unwrap();
+                let byte = if i == 0 {
+                    builder
+                        .build_or(bits, i32_type.const_int(leads[n], false), "")
+                        .unwrap()
+                } else {
+                    let bits = builder
+                        .build_and(bits, i32_type.const_int(0x3f, false), "")
+                        .unwrap();
+                    builder
+                        .build_or(bits, i32_type.const_int(0x80, false), "")
+                        .unwrap()
+                };
+                let byte = builder.build_int_truncate(byte, i8_type, "").unwrap();
+                let ptr = unsafe {
+                    builder.build_in_bounds_gep(buffer, &[i64_type.const_int(i as u64, false)], "")
+                }
+                .unwrap();
+                let _ = builder.build_store(ptr, byte);
+            }
+            let length = i64_type.const_int(num_bytes as u64, false);
+            let nul_ptr = unsafe { builder.build_in_bounds_gep(buffer, &[length], "") }.unwrap();
+            let _ = builder.build_store(nul_ptr, i8_type.const_zero());
+            let _ = builder.build_return(Some(&length));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LLVMPrintf;
+    use crate::test_utils::TestHelper;
+    use inkwell::context::Context;
+
+    #[test]
+    fn test_char_printf() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_char_printf", &builder, &context);
+        tester.start();
+
+        let datatype = tester.module.get_type("Char").clone();
+        for c in ['a', 'λ', '€', '😀'] {
+            let val = FarneseChar::new().emit_ir_value(&tester.module, c);
+            let _ = (val.into(), datatype.clone()).emit_ir_printf(&builder, &tester.module);
+        }
+        tester.end();
+
+        assert_eq!("aλ€😀", tester.run());
+    }
+
+    #[test]
+    fn test_char_to_string() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_char_to_string", &builder, &context);
+        tester.start();
+
+        let char = FarneseChar::new();
+        let val = char.emit_ir_value(&tester.module, 'λ');
+        let string = char.emit_ir_to_string(&builder, &tester.module, val);
+        let length = crate::FarneseString::new().emit_ir_ncodeunits(&builder, string);
+        let datatype = tester.module.get_type("Int64").clone();
+        let _ = (length.into(), datatype).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+
+        assert_eq!("2", tester.run());
+    }
+}
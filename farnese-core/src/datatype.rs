@@ -1,19 +1,25 @@
-use super::{FarneseInternal, LLVMAlloca, LLVMType, MethodHelper, Module, StructHelper, Symbol};
-use inkwell::AddressSpace;
+use super::{FarneseGC, FarneseInternal, LLVMType, MethodHelper, Module, StructHelper, Symbol};
 use inkwell::builder::Builder;
-use inkwell::types::BasicMetadataTypeEnum;
-use inkwell::values::PointerValue;
+use inkwell::module::Linkage;
+use inkwell::types::{BasicMetadataTypeEnum, BasicTypeEnum, StructType};
+use inkwell::values::{BasicValueEnum, FunctionValue, GlobalValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
 use std::fmt::{self, Formatter};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DataType {
     pub field_names: Vec<Symbol>,
     pub field_types: Box<Vec<DataType>>,
     pub name: Symbol,
+    pub parameters: Vec<Symbol>,
     pub supertype: Symbol,
     pub is_abstract: bool,
     pub is_mutable: bool,
     pub is_primitive: bool,
+    /// size of a primitive type, 0 for other types
+    pub bits: u32,
+    /// the module that defines the type
+    pub module: Symbol,
 }
 
 impl<'a, 'b> DataType {
@@ -30,10 +36,13 @@ impl<'a, 'b> DataType {
             field_names: field_names,
             field_types: field_types,
             name: name,
+            parameters: Vec::<Symbol>::new(),
             supertype: supertype,
             is_abstract: is_abstract,
             is_mutable: is_mutable,
             is_primitive: is_primitive,
+            bits: 0,
+            module: Symbol::new("Core"),
         }
     }
 
@@ -41,15 +50,151 @@ impl<'a, 'b> DataType {
     pub fn get_ir_value_type(&self, module: &Module<'a>) -> BasicMetadataTypeEnum<'a> {
         let context = module.get_context();
         let ir_val_type = match self.name.name() {
-            "Float32" => context.i32_type().try_into().unwrap(),
-            "Float64" => context.f64_type().try_into().unwrap(),
-            "Int32" => context.i32_type().try_into().unwrap(),
-            "Int64" => context.i64_type().try_into().unwrap(),
+            "Float32" => context.f32_type().into(),
+            "Float64" => context.f64_type().into(),
+            "Int8" | "UInt8" => context.i8_type().into(),
+            "Int16" | "UInt16" => context.i16_type().into(),
+            "Int32" | "UInt32" => context.i32_type().into(),
+            "Int64" | "UInt64" => context.i64_type().into(),
+            "Int128" | "UInt128" => context.i128_type().into(),
+            "Bool" => context.bool_type().into(),
+            "Char" => context.i32_type().into(),
+            "Nothing" => module.get_struct_type("Nothing").into(),
+            "Symbol" | "DataType" => module
+                .get_struct_type(self.name.name())
+                .ptr_type(AddressSpace::default())
+                .into(),
+            "String" => module
+                .get_struct_type("String")
+                .ptr_type(AddressSpace::default())
+                .into(),
+            "Array" => module
+                .get_struct_type("Array")
+                .ptr_type(AddressSpace::default())
+                .into(),
+            _ if self.is_abstract => module
+                .get_struct_type("Box")
+                .ptr_type(AddressSpace::default())
+                .into(),
+            _ if self.is_struct() => self
+                .struct_ir_type(module)
+                .ptr_type(AddressSpace::default())
+                .into(),
+            _ if self.is_primitive => self.primitive_ir_type(module),
             _ => panic!("Unsupported type {}", self.name.name()),
         };
         ir_val_type
     }
 
+    /// user defined primitive types are floats when they subtype
+    /// AbstractFloat and integers of their size otherwise
+    fn primitive_ir_type(&self, module: &Module<'a>) -> BasicMetadataTypeEnum<'a> {
+        let context = module.get_context();
+        let is_float = module
+            .get_types()
+            .get(&Symbol::new("AbstractFloat"))
+            .is_some_and(|x| module.is_subtype(self, x));
+        match (is_float, self.bits) {
+            (true, 16) => context.f16_type().into(),
+            (true, 32) => context.f32_type().into(),
+            (true, 64) => context.f64_type().into(),
+            (true, bits) => panic!(
+                "Unsupported floating point type {} with {} bits",
+                self.name, bits
+            ),
+            (false, bits) => context.custom_width_int_type(bits).into(),
+        }
+    }
+
+    /// user defined structs, builtin types have their own layouts
+    pub fn is_struct(&self) -> bool {
+        !self.is_abstract
+            && !self.is_primitive
+            && !matches!(
+                self.name.name(),
+                "Array" | "DataType" | "Nothing" | "String" | "Symbol"
+            )
+    }
+
+    /// fields in the order they are laid out, the ones the GC
+    /// has to trace come first
+    fn field_order(&self) -> Vec<usize> {
+        let (mut fields, rest): (Vec<_>, Vec<_>) =
+            (0..self.field_types.len()).partition(|n| self.field_types[*n].is_gc_tracked());
+        fields.extend(rest);
+        fields
+    }
+
+    /// struct instances are heap allocated and tagged like boxed values
+    ///
+    ///   { %DataType* tag, fields... }
+    pub fn struct_ir_type(&self, module: &Module<'a>) -> StructType<'a> {
+        let context = module.get_context();
+        let tag_type = module
+            .get_struct_type("DataType")
+            .ptr_type(AddressSpace::default());
+        let mut fields: Vec<BasicTypeEnum<'a>> = vec![tag_type.into()];
+        for n in self.field_order() {
+            let field_type = self.field_types[n].get_ir_value_type(module);
+            fields.push(field_type.try_into().unwrap());
+        }
+        context.struct_type(&fields, false)
+    }
+
+    /// allocates an instance, vals are in the order fields are declared
+    pub fn emit_ir_new(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        vals: &[BasicValueEnum<'a>],
+    ) -> PointerValue<'a> {
+        let ir_type = self.struct_ir_type(module);
+        let count = self
+            .field_types
+            .iter()
+            .filter(|x| x.is_gc_tracked())
+            .count();
+        let ptr = FarneseGC::new().emit_ir_alloc(builder, module, ir_type.into(), 1, count as u64);
+        ptr.set_nth_field(builder, 0, self.emit_ir_object(module).into());
+        for (n, field) in self.field_order().into_iter().enumerate() {
+            let _ = builder.build_store(ptr.get_nth_field(builder, n as u32 + 1), vals[field]);
+        }
+        ptr
+    }
+
+    /// loads the field called name with its type, None if there is no such field
+    pub fn emit_ir_getfield(
+        &self,
+        builder: &'b Builder<'a>,
+        ptr: PointerValue<'a>,
+        name: &str,
+    ) -> Option<(BasicValueEnum<'a>, DataType)> {
+        let field = self.field_names.iter().position(|x| x.name() == name)?;
+        let n = self.field_order().iter().position(|x| *x == field).unwrap();
+        let val = ptr.load_nth_field(builder, n as u32 + 1);
+        Some((val, self.field_types[field].clone()))
+    }
+
+    /// the declared type of the field called name
+    pub fn field_type(&self, name: &str) -> Option<&DataType> {
+        let field = self.field_names.iter().position(|x| x.name() == name)?;
+        Some(&self.field_types[field])
+    }
+
+    /// stores val in the field called name, val has to be of the field's type
+    pub fn emit_ir_setfield(
+        &self,
+        builder: &'b Builder<'a>,
+        ptr: PointerValue<'a>,
+        name: &str,
+        val: BasicValueEnum<'a>,
+    ) {
+        let field = self.field_names.iter().position(|x| x.name() == name);
+        let field = field.expect("setfield! of a missing field");
+        let n = self.field_order().iter().position(|x| *x == field).unwrap();
+        let _ = builder.build_store(ptr.get_nth_field(builder, n as u32 + 1), val);
+    }
+
     pub fn from_str(
         name: &str,
         supertype: &str,
@@ -78,6 +223,54 @@ impl<'a, 'b> DataType {
         &self.name
     }
 
+    /// values are pointers to objects owned by the GC, abstract
+    /// types hold boxed values and structs are heap allocated
+    pub fn is_gc_tracked(&self) -> bool {
+        self.is_abstract || self.is_struct() || matches!(self.name.name(), "Array" | "String")
+    }
+
+    /// name of the global type object, e.g. Base.Number, so modules
+    /// can define types with the same name
+    pub fn ir_name(&self) -> String {
+        format!("{}.{}", self.module, self.full_name())
+    }
+
+    /// name including type parameters, e.g. Vector{Float64}
+    pub fn full_name(&self) -> String {
+        match (self.name.name(), self.parameters.len()) {
+            (_, 0) => self.name.name().to_owned(),
+            ("Array", 2) if self.parameters[1].name() == "1" => {
+                format!("Vector{{{}}}", self.parameters[0])
+            }
+            _ => format!(
+                "{}{{{}}}",
+                self.name,
+                self.parameters
+                    .iter()
+                    .map(|x| x.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    pub fn new_array_type(eltype: &DataType, ndims: u32) -> Self {
+        let mut datatype = Self::new(
+            Symbol::new("Array"),
+            Symbol::new("DenseArray"),
+            false,
+            true,
+            false,
+            Vec::<Symbol>::new(),
+            Box::default(),
+        );
+        datatype.parameters = vec![
+            Symbol::new(&eltype.full_name()),
+            Symbol::from_string(ndims.to_string()),
+        ];
+        datatype
+    }
+
     pub fn new_abstract_type(name: &str, supertype: &str) -> Self {
         let field_names = Vec::<Symbol>::new();
         let field_types = Box::new(Vec::<DataType>::new());
@@ -92,10 +285,10 @@ impl<'a, 'b> DataType {
         )
     }
 
-    pub fn new_primitive_type(name: &str, supertype: &str, _bits: u32) -> Self {
+    pub fn new_primitive_type(name: &str, supertype: &str, bits: u32) -> Self {
         let field_names = Vec::<Symbol>::new();
         let field_types = Box::new(Vec::<DataType>::new());
-        Self::new(
+        let mut datatype = Self::new(
             Symbol::new(name),
             Symbol::new(supertype),
             false,
@@ -103,7 +296,9 @@ impl<'a, 'b> DataType {
             true,
             field_names,
             field_types,
-        )
+        );
+        datatype.bits = bits;
+        datatype
     }
 }
 
@@ -120,42 +315,41 @@ impl fmt::Display for DataType {
     }
 }
 
+/// runtime layout of a type object, one global per declared type
+///
+///   %DataType = type { %Symbol* name, i8* super, i64 nfields,
+///                      %Symbol* fieldnames, i8** fieldtypes, i8* show }
+///
+/// show points to the default show of a struct, a void(i8*) called with
+/// the struct, so boxed structs can be shown. It is null for other types
+///
+/// other type objects are referenced through i8*, the linker renames
+/// recursive named structs so every module would get its own %DataType.
+/// type objects are named after their type and may be declared before
+/// the module defining them is linked in. The get methods need the Array
+/// functions to be bootstrapped first
 impl<'a, 'b> FarneseInternal<'a> for DataType {
     /// only called once when core is bootstrapped
     fn create_opaque_type(&self, module: &Module<'a>) -> () {
         let context = module.get_context();
         let sym_type = module.get_struct_type("Symbol");
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
         let opaque_type = context.opaque_struct_type("DataType");
         opaque_type.set_body(
             &[
                 sym_type.ptr_type(AddressSpace::default()).into(),
-                opaque_type.ptr_type(AddressSpace::default()).into(),
+                i8_ptr_type.into(),
+                context.i64_type().into(),
+                sym_type.ptr_type(AddressSpace::default()).into(),
+                i8_ptr_type.ptr_type(AddressSpace::default()).into(),
+                i8_ptr_type.into(),
             ],
             false,
         );
     }
 
-    fn create_new_method(&self, module: &Module<'a>) {
-        let symbol = module.get_struct_type("Symbol");
-        let datatype = module.get_struct_type("DataType");
-        let symbol_ptr_type = symbol.ptr_type(AddressSpace::default());
-        let datatype_ptr_type = datatype.ptr_type(AddressSpace::default());
-        let context = module.get_context();
-        let builder = context.create_builder();
-
-        let func =
-            datatype_ptr_type.fn_type(&[symbol_ptr_type.into(), datatype_ptr_type.into()], false);
-        let func = module.add_function("Datatype", func, None);
-        let entry = context.append_basic_block(func, "entry");
-        builder.position_at_end(entry);
-
-        let sym_ptr = func.get_nth_method_input(0);
-        let supertype_ptr = func.get_nth_method_input(1);
-        let struct_ptr = builder.build_alloca(datatype, "").unwrap();
-        let _ = struct_ptr.set_nth_field(&builder, 0, sym_ptr);
-        let _ = struct_ptr.set_nth_field(&builder, 1, supertype_ptr);
-        let _ = builder.build_return(Some(&struct_ptr));
-    }
+    /// type objects are constant globals, see emit_ir_type
+    fn create_new_method(&self, _module: &Module<'a>) {}
 
     fn create_get_methods(&self, module: &Module<'a>) {
         let context = module.get_context();
@@ -182,45 +376,201 @@ impl<'a, 'b> FarneseInternal<'a> for DataType {
         builder.position_at_end(entry);
 
         let struct_ptr = func.get_nth_method_input(0);
-        let field_ptr = struct_ptr.load_nth_field(&builder, 1);
+        let field_ptr = struct_ptr.load_nth_field(&builder, 1).into_pointer_value();
+        let field_ptr = builder
+            .build_pointer_cast(field_ptr, datatype_ptr_type, "")
+            .unwrap();
         let _ = builder.build_return(Some(&field_ptr));
+
+        self.create_fieldnames_method(module);
     }
 }
 
-impl<'a, 'b> LLVMAlloca<'a, 'b> for DataType {
-    fn emit_ir_alloca(&self, builder: &'b Builder<'a>, module: &Module<'a>) -> PointerValue<'a> {
-        // setup datatype struct
-        let datatype_opaque = module.get_struct_type("DataType");
-        let _datatype = module.add_global(datatype_opaque, None, self.name.name());
-        let _supertype_glob = module.get_global(self.supertype.name());
-        // println!("supertype = {:?}", self.supertype.name());
-        let ptr = builder.build_alloca(datatype_opaque, "").unwrap();
-
-        // setup a fresh symbol
-        let sym_ptr = self.name.emit_ir_alloca(&builder, &module);
-
-        // set the fields where the supertype of DataType is DataType
-        let _ = ptr.set_nth_field(&builder, 0, sym_ptr.try_into().unwrap());
-        let _ = ptr.set_nth_field(&builder, 1, ptr.try_into().unwrap());
-        // let _ = ptr.set_nth_field(&builder, 1, supertype_glob.as_pointer_value());
-        // ptr
-
-        // let _ = datatype.set_initializer(&datatype_opaque.const_named_struct(&[
-        //     sym_ptr.into(),
-        //     ptr.into()
-        // ]));
-        ptr
+impl<'a> DataType {
+    /// fieldnames(T) as a Vector{Symbol} pointing into the type object
+    fn create_fieldnames_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let symbol_ptr_type = module
+            .get_struct_type("Symbol")
+            .ptr_type(AddressSpace::default());
+        let datatype_ptr_type = module
+            .get_struct_type("DataType")
+            .ptr_type(AddressSpace::default());
+        let array_ptr_type = module
+            .get_struct_type("Array")
+            .ptr_type(AddressSpace::default());
+
+        let func = array_ptr_type.fn_type(&[datatype_ptr_type.into()], false);
+        let func = module.add_function("fieldnames", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let body = context.append_basic_block(func, "body");
+        let done = context.append_basic_block(func, "done");
+        builder.position_at_end(entry);
+
+        let struct_ptr = func.get_nth_method_input(0);
+        let nfields = struct_ptr.load_nth_field(&builder, 2).into_int_value();
+        let names = struct_ptr.load_nth_field(&builder, 3).into_pointer_value();
+        let array_ptr = builder
+            .build_call(
+                module.get_function("__array_new"),
+                &[
+                    symbol_ptr_type.size_of().into(),
+                    nfields.into(),
+                    context.bool_type().const_zero().into(),
+                ],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        let data = array_ptr.load_nth_field(&builder, 0).into_pointer_value();
+        let data = builder
+            .build_pointer_cast(data, symbol_ptr_type.ptr_type(AddressSpace::default()), "")
+            .unwrap();
+        let is_empty = builder
+            .build_int_compare(IntPredicate::EQ, nfields, i64_type.const_zero(), "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_empty, done, body);
+
+        // data[i] = &names[i]
+        builder.position_at_end(body);
+        let i = builder.build_phi(i64_type, "i").unwrap();
+        i.add_incoming(&[(&i64_type.const_zero(), entry)]);
+        let index = i.as_basic_value().into_int_value();
+        let name = unsafe { builder.build_in_bounds_gep(names, &[index], "") }.unwrap();
+        let element = unsafe { builder.build_in_bounds_gep(data, &[index], "") }.unwrap();
+        let _ = builder.build_store(element, name);
+        let next = builder
+            .build_int_add(index, i64_type.const_int(1, false), "")
+            .unwrap();
+        i.add_incoming(&[(&next, body)]);
+        let is_done = builder
+            .build_int_compare(IntPredicate::EQ, next, nfields, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_done, done, body);
+
+        builder.position_at_end(done);
+        let _ = builder.build_return(Some(&array_ptr));
+    }
+
+    /// the default show of a struct, declared here and defined by
+    /// whoever defines the struct since it shows the fields
+    pub fn emit_ir_show_method(&self, module: &Module<'a>) -> FunctionValue<'a> {
+        let name = format!("__show_{}", self.ir_name());
+        if let Some(func) = module.module().get_function(&name) {
+            return func;
+        }
+        let context = module.get_context();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let func = context.void_type().fn_type(&[i8_ptr_type.into()], false);
+        module.add_function(&name, func, Some(Linkage::External))
+    }
+
+    /// pointer to the global type object, parametric types like
+    /// Vector{Float64} are instantiated the first time they are used
+    pub fn emit_ir_object(&self, module: &Module<'a>) -> PointerValue<'a> {
+        let global = type_object(module, &self.ir_name());
+        if !self.parameters.is_empty() && global.get_initializer().is_none() {
+            self.emit_ir_type(module);
+            global.set_linkage(Linkage::LinkOnceODR);
+        }
+        global.as_pointer_value()
     }
 }
 
+/// gets the global type object called name, declaring it if it is
+/// defined in a module that isn't linked in yet
+fn type_object<'a>(module: &Module<'a>, name: &str) -> GlobalValue<'a> {
+    if let Some(global) = module.module().get_global(name) {
+        return global;
+    }
+    let global = module.add_global(module.get_struct_type("DataType"), None, name);
+    global.set_linkage(Linkage::External);
+    global
+}
+
 impl<'a> LLVMType<'a> for DataType {
+    /// defines the global type object
     fn emit_ir_type(&self, module: &Module<'a>) {
-        let datatype_opaque = module.get_struct_type("DataType");
-        let _ = module.add_global(datatype_opaque, None, self.name.name());
-        // let supertype_glob = module.get_global(self.supertype.name());
-        // let _ = datatype.set_initializer(&datatype_opaque.const_named_struct(&[
+        let context = module.get_context();
+        let datatype = module.get_struct_type("DataType");
+        let symbol = module.get_struct_type("Symbol");
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let i32_type = context.i32_type();
+
+        let name = Symbol::new(&self.full_name()).emit_ir_constant(module);
+        // Core's numbers subtype the abstract types Base declares
+        let supertype = match module.get_types().get(&self.supertype) {
+            Some(x) => x.ir_name(),
+            None => format!("Base.{}", self.supertype),
+        };
+        let supertype = type_object(module, &supertype)
+            .as_pointer_value()
+            .const_cast(i8_ptr_type);
+
+        // fields are stored in private constant arrays
+        let (field_names, field_types) = match self.field_names.len() {
+            0 => (
+                symbol.ptr_type(AddressSpace::default()).const_null(),
+                i8_ptr_type.ptr_type(AddressSpace::default()).const_null(),
+            ),
+            _ => {
+                let names = self
+                    .field_names
+                    .iter()
+                    .map(|x| x.emit_ir_const_value(module))
+                    .collect::<Vec<_>>();
+                let names = symbol.const_array(&names);
+                let types = self
+                    .field_types
+                    .iter()
+                    .map(|x| x.emit_ir_object(module).const_cast(i8_ptr_type))
+                    .collect::<Vec<_>>();
+                let types = i8_ptr_type.const_array(&types);
+                let [names, types] = [names, types].map(|x| {
+                    let global = module.add_global(x.get_type(), None, "fields");
+                    global.set_initializer(&x);
+                    global.set_constant(true);
+                    global.set_linkage(Linkage::Private);
+                    unsafe {
+                        global
+                            .as_pointer_value()
+                            .const_in_bounds_gep(&[i32_type.const_zero(), i32_type.const_zero()])
+                    }
+                });
+                (names, types)
+            }
+        };
+
+        let show = match self.is_struct() {
+            true => self
+                .emit_ir_show_method(module)
+                .as_global_value()
+                .as_pointer_value()
+                .const_cast(i8_ptr_type),
+            false => i8_ptr_type.const_null(),
+        };
 
-        // ]))
+        let global = type_object(module, &self.ir_name());
+        global.set_linkage(Linkage::External);
+        global.set_initializer(
+            &datatype.const_named_struct(&[
+                name.into(),
+                supertype.into(),
+                context
+                    .i64_type()
+                    .const_int(self.field_names.len() as u64, false)
+                    .into(),
+                field_names.into(),
+                field_types.into(),
+                show.into(),
+            ]),
+        );
+        global.set_constant(true);
     }
 }
 
@@ -228,150 +578,127 @@ impl<'a> LLVMType<'a> for DataType {
 mod tests {
     use super::*;
     use crate::test_utils::TestHelper;
-    use crate::{DataType, LLVMPrintf};
+    use crate::{Array, FarneseShow, FarneseString, LLVMPrintf};
     use inkwell::context::Context;
+    use inkwell::values::BasicMetadataValueEnum;
 
-    #[test]
-    fn test_datatype() {
-        let context = Context::create();
-        let builder = context.create_builder();
-        let tester = TestHelper::new("test_datatype", &builder, &context);
-        tester.start();
-
-        let sym = Symbol::new("DataType");
-        let sym_super = Symbol::new("DataType");
-        let field_names = Vec::<Symbol>::new();
-        let field_types = Box::new(Vec::<DataType>::new());
-        let datatype = DataType::new(
-            sym.clone(),
-            sym_super,
-            false,
-            false,
-            false,
-            field_names,
-            field_types,
-        );
-        let ptr = datatype.emit_ir_alloca(&builder, &tester.module);
-
-        let func = tester.module.get_function("name");
-        let _ = builder
-            .build_call(func, &[ptr.into()], "call_name")
+    fn call<'a>(
+        tester: &TestHelper<'a, '_>,
+        name: &str,
+        ptr: PointerValue<'a>,
+    ) -> PointerValue<'a> {
+        tester
+            .builder
+            .build_call(tester.module.get_function(name), &[ptr.into()], "")
             .unwrap()
             .try_as_basic_value()
             .left()
             .unwrap()
-            .into_pointer_value();
-        let _ = sym.emit_ir_printf(&builder, &tester.module);
+            .into_pointer_value()
+    }
 
-        let func = tester.module.get_function("supertype");
-        let func_result = builder
-            .build_call(func, &[ptr.into()], "call_supertype")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
+    fn printf<'a>(tester: &TestHelper<'a, '_>, val: BasicMetadataValueEnum<'a>, name: &str) {
+        let datatype = tester.module.get_type(name).clone();
+        let _ = (val, datatype).emit_ir_printf(tester.builder, &tester.module);
+    }
 
-        let func = tester.module.get_function("name");
-        let _ = builder
-            .build_call(func, &[func_result.into()], "call_name")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
-        let _ = sym.emit_ir_printf(&builder, &tester.module);
+    #[test]
+    fn test_datatype() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_datatype", &builder, &context);
+        tester.start();
 
-        // now make Any
-        let sym = Symbol::new("Any");
-        let sym_super = Symbol::new("Any");
-        let field_names = Vec::<Symbol>::new();
-        let field_types = Box::new(Vec::<DataType>::new());
-        let datatype = DataType::new(
-            sym.clone(),
-            sym_super,
+        let float = tester.module.get_type("Float64").clone();
+        let datatype = DataType::from_str(
+            "Pair",
+            "Any",
             false,
             false,
             false,
-            field_names,
-            field_types,
+            vec!["first".to_string(), "second".to_string()],
+            Box::new(vec![float.clone(), float]),
         );
-        let ptr = datatype.emit_ir_alloca(&builder, &tester.module);
-
-        let func = tester.module.get_function("name");
-        let _ = builder
-            .build_call(func, &[ptr.into()], "call_name")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
-        let _ = sym.emit_ir_printf(&builder, &tester.module);
+        datatype.emit_ir_type(&tester.module);
+        let show = FarneseShow::new();
+        show.emit_ir_show_struct(&tester.module, &datatype, &|builder, val, datatype| {
+            show.emit_ir_show(builder, &tester.module, val, datatype)
+        });
+        let ptr = datatype.emit_ir_object(&tester.module);
+
+        printf(&tester, ptr.into(), "DataType");
+        let name = call(&tester, "name", ptr);
+        printf(&tester, name.into(), "Symbol");
+        let supertype = call(&tester, "supertype", ptr);
+        printf(&tester, supertype.into(), "DataType");
+        let supertype = call(&tester, "supertype", supertype);
+        printf(&tester, supertype.into(), "DataType");
+
+        // fieldnames(Pair)[2]
+        let names = call(&tester, "fieldnames", ptr);
+        let array = Array::new(DataType::new_array_type(
+            tester.module.get_type("Symbol"),
+            1,
+        ));
+        let length = array.emit_ir_length(&builder, names);
+        let _ = (length.into(), tester.module.get_type("Int64").clone())
+            .emit_ir_printf(&builder, &tester.module);
+        let index = context.i64_type().const_int(2, false);
+        let name = array.emit_ir_getindex(&builder, &tester.module, names, index);
+        printf(&tester, name.into(), "Symbol");
+
+        // parametric types are instantiated on use
+        let vector = DataType::new_array_type(tester.module.get_type("Int64"), 1);
+        let ptr = vector.emit_ir_object(&tester.module);
+        printf(&tester, ptr.into(), "DataType");
+        let supertype = call(&tester, "supertype", ptr);
+        printf(&tester, supertype.into(), "DataType");
+        tester.end();
 
-        let func = tester.module.get_function("supertype");
-        let func_result = builder
-            .build_call(func, &[ptr.into()], "call_supertype")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
+        assert_eq!("PairPairAnyAny2secondVector{Int64}DenseArray", tester.run());
+    }
 
-        let func = tester.module.get_function("name");
-        let _ = builder
-            .build_call(func, &[func_result.into()], "call_name")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
-        let _ = sym.emit_ir_printf(&builder, &tester.module);
+    #[test]
+    fn test_setfield() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_setfield", &builder, &context);
+        tester.start();
 
-        // now make some other type
-        let sym = Symbol::new("Number");
-        let sym_super = Symbol::new("Any");
-        let field_names = Vec::<Symbol>::new();
-        let field_types = Box::new(Vec::<DataType>::new());
-        let datatype = DataType::new(
-            sym.clone(),
-            sym_super,
-            false,
+        let module = &tester.module;
+        let float = module.get_type("Float64").clone();
+        let string = module.get_type("String").clone();
+        let datatype = DataType::from_str(
+            "Labeled",
+            "Any",
             false,
+            true,
             false,
-            field_names,
-            field_types,
+            vec!["value".to_string(), "label".to_string()],
+            Box::new(vec![float.clone(), string.clone()]),
         );
-        let ptr = datatype.emit_ir_alloca(&builder, &tester.module);
-
-        let func = tester.module.get_function("name");
-        let _ = builder
-            .build_call(func, &[ptr.into()], "call_name")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
-        let _ = sym.emit_ir_printf(&builder, &tester.module);
-
-        let func = tester.module.get_function("supertype");
-        let func_result = builder
-            .build_call(func, &[ptr.into()], "call_supertype")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
-
-        let func = tester.module.get_function("name");
-        let _ = builder
-            .build_call(func, &[func_result.into()], "call_name")
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
-        let _ = sym.emit_ir_printf(&builder, &tester.module);
-
+        datatype.emit_ir_type(module);
+        let show = FarneseShow::new();
+        show.emit_ir_show_struct(module, &datatype, &|builder, val, datatype| {
+            show.emit_ir_show(builder, module, val, datatype)
+        });
+        let one = context.f64_type().const_float(1.0);
+        let string_helper = FarneseString::new();
+        let label = string_helper.emit_ir_literal(module, "a");
+        let ptr = datatype.emit_ir_new(&builder, module, &[one.into(), label.into()]);
+
+        // the label is laid out first for the GC, so the order matters
+        let two = context.f64_type().const_float(2.5);
+        datatype.emit_ir_setfield(&builder, ptr, "value", two.into());
+        let label = string_helper.emit_ir_literal(module, "b");
+        datatype.emit_ir_setfield(&builder, ptr, "label", label.into());
+        for name in ["value", "label"] {
+            let (val, datatype) = datatype.emit_ir_getfield(&builder, ptr, name).unwrap();
+            let _ = (val.into(), datatype).emit_ir_printf(&builder, module);
+        }
+        assert_eq!(Some(&string), datatype.field_type("label"));
         tester.end();
+
+        assert_eq!("2.50000000b", tester.run());
     }
 }
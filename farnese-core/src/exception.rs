@@ -0,0 +1,844 @@
+use super::{
+    Array, DataType, FarneseBox, FarneseGC, FarneseInternal, LLVMPrintf, MethodHelper, Module,
+    StructHelper, Symbol,
+};
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::builder::Builder;
+use inkwell::module::Linkage;
+use inkwell::types::StructType;
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValueEnum, FunctionValue, IntValue, PointerValue,
+};
+use inkwell::{AddressSpace, IntPredicate};
+
+// fields of %ExceptionState
+const HANDLER: u32 = 0;
+const FRAMES: u32 = 1;
+const EXCEPTION: u32 = 2;
+
+// fields of %Handler
+const PREV: u32 = 0;
+const ROOTS: u32 = 1;
+const HANDLER_FRAMES: u32 = 2;
+const JMP_BUF: u32 = 3;
+
+/// words reserved for a jmp_buf, glibc needs 25 on x86_64
+const JMP_BUF_WORDS: u32 = 32;
+
+/// exceptions unwind with setjmp/longjmp, entering a try block pushes
+/// a handler onto a linked list and throwing jumps back to the most
+/// recent one
+///
+///   %Handler = type { i8* prev, i64 roots, i8* frames, [32 x i64] jmp_buf }
+///
+/// the handler remembers the depth of the GC shadow stack and the
+/// innermost frame, both are restored when the exception is caught.
+/// every compiled function pushes a %Frame = type { i8* prev, i8* name }
+/// onto another linked list, which is printed as the backtrace when an
+/// exception reaches the top without being caught
+///
+/// exceptions are boxed so they can be of any type
+#[derive(Clone, Debug)]
+pub struct FarneseException;
+
+impl<'a, 'b> FarneseException {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn state(&self, module: &Module<'a>) -> PointerValue<'a> {
+        module.get_global("__exceptions").as_pointer_value()
+    }
+
+    fn call(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        args: &[BasicMetadataValueEnum<'a>],
+    ) -> Option<BasicValueEnum<'a>> {
+        builder
+            .build_call(module.get_function(name), args, "")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+    }
+
+    fn i8_ptr(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+    ) -> PointerValue<'a> {
+        let i8_ptr_type = module
+            .get_context()
+            .i8_type()
+            .ptr_type(AddressSpace::default());
+        builder.build_pointer_cast(ptr, i8_ptr_type, "").unwrap()
+    }
+
+    fn box_ptr(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+    ) -> PointerValue<'a> {
+        let box_ptr_type = module
+            .get_struct_type("Box")
+            .ptr_type(AddressSpace::default());
+        builder.build_pointer_cast(ptr, box_ptr_type, "").unwrap()
+    }
+
+    /// the name of the DataType as a C string
+    fn type_name(&self, builder: &'b Builder<'a>, tag: PointerValue<'a>) -> PointerValue<'a> {
+        let sym_ptr = tag.load_nth_field(builder, 0).into_pointer_value();
+        sym_ptr.load_nth_field(builder, 1).into_pointer_value()
+    }
+
+    fn printf(&self, builder: &'b Builder<'a>, module: &Module<'a>, format: &str) {
+        self.printf_args(builder, module, format, &[]);
+    }
+
+    fn printf_args(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        format: &str,
+        args: &[BasicMetadataValueEnum<'a>],
+    ) {
+        let format_string = builder
+            .build_global_string_ptr(format, "")
+            .unwrap()
+            .as_pointer_value();
+        let mut func_args: Vec<BasicMetadataValueEnum<'a>> = vec![format_string.into()];
+        func_args.extend_from_slice(args);
+        let _ = builder.build_call(module.get_function("printf"), &func_args, "");
+    }
+
+    /// pushes a frame for the function being compiled, name is shown in backtraces
+    pub fn emit_ir_enter(&self, builder: &'b Builder<'a>, module: &Module<'a>, name: &str) {
+        let frame = builder
+            .build_alloca(module.get_struct_type("Frame"), "")
+            .unwrap();
+        let name = builder
+            .build_global_string_ptr(name, "")
+            .unwrap()
+            .as_pointer_value();
+        let _ = self.call(builder, module, "__enter", &[frame.into(), name.into()]);
+    }
+
+    /// pops the frame pushed by emit_ir_enter
+    pub fn emit_ir_leave(&self, builder: &'b Builder<'a>, module: &Module<'a>) {
+        let _ = self.call(builder, module, "__leave", &[]);
+    }
+
+    /// setjmp is only called from compiled code, so it is declared there
+    fn setjmp(&self, module: &Module<'a>) -> FunctionValue<'a> {
+        if let Some(func) = module.module().get_function("_setjmp") {
+            return func;
+        }
+        let context = module.get_context();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let func = context.i32_type().fn_type(&[i8_ptr_type.into()], false);
+        let func = module.add_function("_setjmp", func, Some(Linkage::External));
+        func.add_attribute(AttributeLoc::Function, self.returns_twice(module));
+        func
+    }
+
+    fn returns_twice(&self, module: &Module<'a>) -> Attribute {
+        let kind_id = Attribute::get_named_enum_kind_id("returns_twice");
+        module.get_context().create_enum_attribute(kind_id, 0)
+    }
+
+    /// enters a try block, returns the handler and whether an exception
+    /// was thrown, which is true when control comes back from a throw
+    pub fn emit_ir_try(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+    ) -> (PointerValue<'a>, IntValue<'a>) {
+        let handler = builder
+            .build_alloca(module.get_struct_type("Handler"), "")
+            .unwrap();
+        let _ = self.call(builder, module, "__try_enter", &[handler.into()]);
+        let jmp_buf = self.i8_ptr(builder, module, handler.get_nth_field(builder, JMP_BUF));
+        let call = builder
+            .build_call(self.setjmp(module), &[jmp_buf.into()], "")
+            .unwrap();
+        call.add_attribute(AttributeLoc::Function, self.returns_twice(module));
+        let val = call.try_as_basic_value().unwrap_left().into_int_value();
+        let is_thrown = builder
+            .build_int_compare(IntPredicate::NE, val, val.get_type().const_zero(), "")
+            .unwrap();
+        (handler, is_thrown)
+    }
+
+    /// leaves a try block that finished without throwing
+    pub fn emit_ir_try_leave(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        handler: PointerValue<'a>,
+    ) {
+        let _ = self.call(builder, module, "__try_leave", &[handler.into()]);
+    }
+
+    /// the exception that was thrown to handler, it is rooted until
+    /// the current function returns
+    pub fn emit_ir_catch(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        handler: PointerValue<'a>,
+    ) -> PointerValue<'a> {
+        self.call(builder, module, "__catch", &[handler.into()])
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    /// throws a boxed value, nothing after this is reachable
+    pub fn emit_ir_throw(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+    ) {
+        let ptr = self.box_ptr(builder, module, ptr);
+        let _ = self.call(builder, module, "__throw", &[ptr.into()]);
+        let _ = builder.build_unreachable();
+    }
+
+    /// throws the exception currently being handled again
+    pub fn emit_ir_rethrow(&self, builder: &'b Builder<'a>, module: &Module<'a>) {
+        let _ = self.call(builder, module, "__rethrow", &[]);
+        let _ = builder.build_unreachable();
+    }
+
+    /// prints the message of a boxed exception
+    pub fn emit_ir_showerror(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+    ) {
+        let _ = self.call(builder, module, "__showerror", &[ptr.into()]);
+    }
+
+    /// a new boxed exception of type name, vals are its fields
+    pub fn emit_ir_new(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        vals: &[BasicValueEnum<'a>],
+    ) -> PointerValue<'a> {
+        let datatype = module.get_type(name);
+        let ptr = datatype.emit_ir_new(builder, module, vals);
+        FarneseBox::new().emit_ir_box(builder, module, ptr.into(), datatype)
+    }
+
+    /// a boxed MethodError for calling name with args, values that
+    /// aren't boxed yet get boxed for the args vector
+    pub fn emit_ir_method_error(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        args: &[(BasicValueEnum<'a>, DataType)],
+    ) -> PointerValue<'a> {
+        let box_helper = FarneseBox::new();
+        let args = args
+            .iter()
+            .map(|(val, datatype)| match datatype.is_abstract {
+                true => *val,
+                false => box_helper
+                    .emit_ir_box(builder, module, *val, datatype)
+                    .into(),
+            })
+            .collect::<Vec<_>>();
+        let array = Array::new(DataType::new_array_type(module.get_type("Any"), 1));
+        let args = array.emit_ir_literal(builder, module, &args);
+        let name = Symbol::new(name).emit_ir_constant(module);
+        self.emit_ir_new(builder, module, "MethodError", &[name.into(), args.into()])
+    }
+
+    /// a boxed ErrorException for reading a field a value of type tag
+    /// doesn't have, like Julia's "type Int64 has no field x"
+    pub fn emit_ir_field_error(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        tag: PointerValue<'a>,
+        field: &str,
+    ) -> PointerValue<'a> {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let size = i64_type.const_int(256, false);
+        let buffer = builder
+            .build_array_alloca(context.i8_type(), size, "")
+            .unwrap();
+        let format_string = format!("type %s has no field {}", field);
+        let format_string = builder
+            .build_global_string_ptr(&format_string, "")
+            .unwrap()
+            .as_pointer_value();
+        let args = [
+            buffer.into(),
+            size.into(),
+            format_string.into(),
+            self.type_name(builder, tag).into(),
+        ];
+        let length = self
+            .call(builder, module, "snprintf", &args)
+            .unwrap()
+            .into_int_value();
+        let length = builder.build_int_s_extend(length, i64_type, "").unwrap();
+        let max_length = i64_type.const_int(255, false);
+        let is_truncated = builder
+            .build_int_compare(IntPredicate::UGT, length, max_length, "")
+            .unwrap();
+        let length = builder
+            .build_select(is_truncated, max_length, length, "")
+            .unwrap();
+        let msg = self
+            .call(
+                builder,
+                module,
+                "__string_new",
+                &[buffer.into(), length.into()],
+            )
+            .unwrap();
+        self.emit_ir_new(builder, module, "ErrorException", &[msg])
+    }
+
+    fn create_handler_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let void_type = context.void_type();
+        let box_ptr_type = module
+            .get_struct_type("Box")
+            .ptr_type(AddressSpace::default());
+        let frame_ptr_type = module
+            .get_struct_type("Frame")
+            .ptr_type(AddressSpace::default());
+        let handler_ptr_type = module
+            .get_struct_type("Handler")
+            .ptr_type(AddressSpace::default());
+        let state = self.state(module);
+        let gc = FarneseGC::new();
+
+        // create enter, links a frame into the list of frames
+        let func = void_type.fn_type(&[frame_ptr_type.into(), i8_ptr_type.into()], false);
+        let func = module.add_function("__enter", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let frame = func.get_nth_method_input(0).into_pointer_value();
+        let frames = state.load_nth_field(&builder, FRAMES);
+        frame.set_nth_field(&builder, 0, frames.into());
+        frame.set_nth_field(&builder, 1, func.get_nth_method_input(1));
+        let frame = self.i8_ptr(&builder, module, frame);
+        state.set_nth_field(&builder, FRAMES, frame.into());
+        let _ = builder.build_return(None);
+
+        // create leave, unlinks the innermost frame
+        let func = void_type.fn_type(&[], false);
+        let func = module.add_function("__leave", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let frame = state.load_nth_field(&builder, FRAMES).into_pointer_value();
+        let frame = builder
+            .build_pointer_cast(frame, frame_ptr_type, "")
+            .unwrap();
+        let prev = frame.load_nth_field(&builder, 0);
+        state.set_nth_field(&builder, FRAMES, prev.into());
+        let _ = builder.build_return(None);
+
+        // create try enter, pushes a handler
+        let func = void_type.fn_type(&[handler_ptr_type.into()], false);
+        let func = module.add_function("__try_enter", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let handler = func.get_nth_method_input(0).into_pointer_value();
+        let prev = state.load_nth_field(&builder, HANDLER);
+        handler.set_nth_field(&builder, PREV, prev.into());
+        let depth = gc.emit_ir_frame(&builder, module);
+        let _ = builder.build_store(handler.get_nth_field(&builder, ROOTS), depth);
+        let frames = state.load_nth_field(&builder, FRAMES);
+        handler.set_nth_field(&builder, HANDLER_FRAMES, frames.into());
+        let handler = self.i8_ptr(&builder, module, handler);
+        state.set_nth_field(&builder, HANDLER, handler.into());
+        let _ = builder.build_return(None);
+
+        // create try leave, pops the handler
+        let func = void_type.fn_type(&[handler_ptr_type.into()], false);
+        let func = module.add_function("__try_leave", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let handler = func.get_nth_method_input(0).into_pointer_value();
+        let prev = handler.load_nth_field(&builder, PREV);
+        state.set_nth_field(&builder, HANDLER, prev.into());
+        let _ = builder.build_return(None);
+
+        // create catch, pops the handler and drops the roots and
+        // frames of everything that was unwound
+        let func = box_ptr_type.fn_type(&[handler_ptr_type.into()], false);
+        let func = module.add_function("__catch", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let handler = func.get_nth_method_input(0).into_pointer_value();
+        let prev = handler.load_nth_field(&builder, PREV);
+        state.set_nth_field(&builder, HANDLER, prev.into());
+        let depth = handler.load_nth_field(&builder, ROOTS).into_int_value();
+        gc.emit_ir_pop_frame(&builder, module, depth);
+        let frames = handler.load_nth_field(&builder, HANDLER_FRAMES);
+        state.set_nth_field(&builder, FRAMES, frames.into());
+        let exception = state
+            .load_nth_field(&builder, EXCEPTION)
+            .into_pointer_value();
+        gc.emit_ir_push_root(&builder, module, exception);
+        let _ = builder.build_return(Some(&exception));
+    }
+
+    fn create_throw_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i32_type = context.i32_type();
+        let i64_type = context.i64_type();
+        let void_type = context.void_type();
+        let box_ptr_type = module
+            .get_struct_type("Box")
+            .ptr_type(AddressSpace::default());
+        let frame_ptr_type = module
+            .get_struct_type("Frame")
+            .ptr_type(AddressSpace::default());
+        let handler_ptr_type = module
+            .get_struct_type("Handler")
+            .ptr_type(AddressSpace::default());
+        let state = self.state(module);
+        let noreturn =
+            context.create_enum_attribute(Attribute::get_named_enum_kind_id("noreturn"), 0);
+
+        // create uncaught, prints the exception and the frames it
+        // was thrown from, then exits
+        let func = void_type.fn_type(&[box_ptr_type.into()], false);
+        let func = module.add_function("__uncaught", func, None);
+        func.add_attribute(AttributeLoc::Function, noreturn);
+        let entry = context.append_basic_block(func, "entry");
+        let body = context.append_basic_block(func, "body");
+        let next = context.append_basic_block(func, "next");
+        let done = context.append_basic_block(func, "done");
+        builder.position_at_end(entry);
+
+        self.printf(&builder, module, "ERROR: ");
+        let _ = self.call(
+            &builder,
+            module,
+            "__showerror",
+            &[func.get_nth_method_input(0)],
+        );
+        self.printf(&builder, module, "\nStacktrace:\n");
+        let first = state.load_nth_field(&builder, FRAMES).into_pointer_value();
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(body);
+        let frame = builder.build_phi(first.get_type(), "").unwrap();
+        let n = builder.build_phi(i64_type, "").unwrap();
+        frame.add_incoming(&[(&first, entry)]);
+        n.add_incoming(&[(&i64_type.const_int(1, false), entry)]);
+        let frame_ptr = frame.as_basic_value().into_pointer_value();
+        let is_null = builder.build_is_null(frame_ptr, "").unwrap();
+        let _ = builder.build_conditional_branch(is_null, done, next);
+
+        builder.position_at_end(next);
+        let frame_ptr = builder
+            .build_pointer_cast(frame_ptr, frame_ptr_type, "")
+            .unwrap();
+        let name = frame_ptr.load_nth_field(&builder, 1);
+        self.printf_args(
+            &builder,
+            module,
+            " [%lld] %s\n",
+            &[n.as_basic_value().into(), name.into()],
+        );
+        let prev = frame_ptr.load_nth_field(&builder, 0);
+        let n_next = builder
+            .build_int_add(
+                n.as_basic_value().into_int_value(),
+                i64_type.const_int(1, false),
+                "",
+            )
+            .unwrap();
+        frame.add_incoming(&[(&prev, next)]);
+        n.add_incoming(&[(&n_next, next)]);
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(done);
+        let _ = builder.build_call(
+            module.get_function("exit"),
+            &[i32_type.const_int(1, false).into()],
+            "",
+        );
+        let _ = builder.build_unreachable();
+
+        // create throw, jumps to the innermost handler
+        let func = void_type.fn_type(&[box_ptr_type.into()], false);
+        let func = module.add_function("__throw", func, None);
+        func.add_attribute(AttributeLoc::Function, noreturn);
+        let entry = context.append_basic_block(func, "entry");
+        let uncaught = context.append_basic_block(func, "uncaught");
+        let caught = context.append_basic_block(func, "caught");
+        builder.position_at_end(entry);
+
+        let exception = func.get_nth_method_input(0);
+        state.set_nth_field(&builder, EXCEPTION, exception);
+        let handler = state.load_nth_field(&builder, HANDLER).into_pointer_value();
+        let is_null = builder.build_is_null(handler, "").unwrap();
+        let _ = builder.build_conditional_branch(is_null, uncaught, caught);
+
+        builder.position_at_end(uncaught);
+        let _ = self.call(&builder, module, "__uncaught", &[exception]);
+        let _ = builder.build_unreachable();
+
+        builder.position_at_end(caught);
+        let handler = builder
+            .build_pointer_cast(handler, handler_ptr_type, "")
+            .unwrap();
+        let jmp_buf = self.i8_ptr(&builder, module, handler.get_nth_field(&builder, JMP_BUF));
+        let _ = builder.build_call(
+            module.get_function("longjmp"),
+            &[jmp_buf.into(), i32_type.const_int(1, false).into()],
+            "",
+        );
+        let _ = builder.build_unreachable();
+
+        // create rethrow, throws the last exception again
+        let func = void_type.fn_type(&[], false);
+        let func = module.add_function("__rethrow", func, None);
+        func.add_attribute(AttributeLoc::Function, noreturn);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let exception = state.load_nth_field(&builder, EXCEPTION);
+        let _ = self.call(&builder, module, "__throw", &[exception.into()]);
+        let _ = builder.build_unreachable();
+    }
+
+    /// prints exceptions like Julia's showerror, values that aren't
+    /// one of the Core exceptions are printed as they are
+    fn create_showerror_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let box_helper = FarneseBox::new();
+        let box_ptr_type = module
+            .get_struct_type("Box")
+            .ptr_type(AddressSpace::default());
+
+        let func = context.void_type().fn_type(&[box_ptr_type.into()], false);
+        let func = module.add_function("__showerror", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let ptr = func.get_nth_method_input(0).into_pointer_value();
+        let tag = box_helper.emit_ir_typeof(&builder, ptr);
+        let field = |name: &str, field: &str| {
+            let datatype = module.get_type(name);
+            let val = box_helper
+                .emit_ir_unbox(&builder, module, ptr, datatype)
+                .into_pointer_value();
+            datatype.emit_ir_getfield(&builder, val, field).unwrap()
+        };
+        for name in [
+            "ErrorException",
+            "AssertionError",
+            "BoundsError",
+            "DivideError",
+            "DimensionMismatch",
+            "MethodError",
+        ] {
+            let found = context.append_basic_block(func, name);
+            let next = context.append_basic_block(func, "");
+            let datatype = module.get_type(name);
+            let is_type = builder
+                .build_int_compare(IntPredicate::EQ, tag, datatype.emit_ir_object(module), "")
+                .unwrap();
+            let _ = builder.build_conditional_branch(is_type, found, next);
+
+            builder.position_at_end(found);
+            match name {
+                "ErrorException" => {
+                    let (msg, string_type) = field(name, "msg");
+                    let _ = (msg.into(), string_type).emit_ir_printf(&builder, module);
+                }
+                "AssertionError" => {
+                    let (msg, string_type) = field(name, "msg");
+                    self.printf(&builder, module, "AssertionError: ");
+                    let _ = (msg.into(), string_type).emit_ir_printf(&builder, module);
+                }
+                "BoundsError" => {
+                    let (a, _) = field(name, "a");
+                    let (i, _) = field(name, "i");
+                    self.emit_ir_bounds_error(&builder, module, a.into_pointer_value(), i);
+                }
+                "DivideError" => {
+                    self.printf(&builder, module, "DivideError: integer division error");
+                }
+                "DimensionMismatch" => {
+                    let (msg, string_type) = field(name, "msg");
+                    self.printf(&builder, module, "DimensionMismatch: ");
+                    let _ = (msg.into(), string_type).emit_ir_printf(&builder, module);
+                }
+                _ => {
+                    let (f, symbol_type) = field(name, "f");
+                    let (args, array_type) = field(name, "args");
+                    self.printf(&builder, module, "MethodError: no method matching ");
+                    let _ = (f.into(), symbol_type).emit_ir_printf(&builder, module);
+                    self.printf(&builder, module, "(");
+                    self.emit_ir_arg_types(&builder, module, args.into_pointer_value(), array_type);
+                    self.printf(&builder, module, ")");
+                }
+            }
+            let _ = builder.build_return(None);
+            builder.position_at_end(next);
+        }
+
+        let _ = builder.build_call(module.get_function("__print_box"), &[ptr.into()], "");
+        let _ = builder.build_return(None);
+    }
+
+    /// arrays show their length and type, anything else just its type
+    fn emit_ir_bounds_error(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        a: PointerValue<'a>,
+        i: BasicValueEnum<'a>,
+    ) {
+        let context = module.get_context();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let box_helper = FarneseBox::new();
+        let is_array = context.append_basic_block(func, "");
+        let other = context.append_basic_block(func, "");
+        let done = context.append_basic_block(func, "");
+
+        let dense_array = module.get_type("DenseArray");
+        let tag = box_helper.emit_ir_typeof(builder, a);
+        let supertype = self
+            .call(builder, module, "supertype", &[tag.into()])
+            .unwrap()
+            .into_pointer_value();
+        let cond = builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                supertype,
+                dense_array.emit_ir_object(module),
+                "",
+            )
+            .unwrap();
+        let _ = builder.build_conditional_branch(cond, is_array, other);
+
+        // every array payload has the same layout
+        builder.position_at_end(is_array);
+        let array_type = DataType::new_array_type(module.get_type("Any"), 1);
+        let array_ptr = box_helper
+            .emit_ir_unbox(builder, module, a, &array_type)
+            .into_pointer_value();
+        let length = Array::new(array_type).emit_ir_length(builder, array_ptr);
+        self.printf_args(
+            builder,
+            module,
+            "BoundsError: attempt to access %lld-element %s at index [%lld]",
+            &[length.into(), self.type_name(builder, tag).into(), i.into()],
+        );
+        let _ = builder.build_unconditional_branch(done);
+
+        builder.position_at_end(other);
+        self.printf_args(
+            builder,
+            module,
+            "BoundsError: attempt to access %s at index [%lld]",
+            &[self.type_name(builder, tag).into(), i.into()],
+        );
+        let _ = builder.build_unconditional_branch(done);
+        builder.position_at_end(done);
+    }
+
+    /// prints ::T for the runtime type of every boxed argument
+    fn emit_ir_arg_types(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        args: PointerValue<'a>,
+        array_type: DataType,
+    ) {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let start = builder.get_insert_block().unwrap();
+        let body = context.append_basic_block(func, "");
+        let next = context.append_basic_block(func, "");
+        let done = context.append_basic_block(func, "");
+        let array = Array::new(array_type);
+        let length = array.emit_ir_length(builder, args);
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(body);
+        let n = builder.build_phi(i64_type, "").unwrap();
+        n.add_incoming(&[(&i64_type.const_int(1, false), start)]);
+        let index = n.as_basic_value().into_int_value();
+        let is_done = builder
+            .build_int_compare(IntPredicate::SGT, index, length, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_done, done, next);
+
+        builder.position_at_end(next);
+        let separator = builder
+            .build_select(
+                builder
+                    .build_int_compare(IntPredicate::EQ, index, i64_type.const_int(1, false), "")
+                    .unwrap(),
+                builder
+                    .build_global_string_ptr("", "")
+                    .unwrap()
+                    .as_pointer_value(),
+                builder
+                    .build_global_string_ptr(", ", "")
+                    .unwrap()
+                    .as_pointer_value(),
+                "",
+            )
+            .unwrap();
+        let ptr = array.emit_ir_element_ptr(builder, module, args, index, false);
+        let val = builder.build_load(ptr, "").unwrap().into_pointer_value();
+        let tag = FarneseBox::new().emit_ir_typeof(builder, val);
+        self.printf_args(
+            builder,
+            module,
+            "%s::%s",
+            &[separator.into(), self.type_name(builder, tag).into()],
+        );
+        let index_next = builder
+            .build_int_add(index, i64_type.const_int(1, false), "")
+            .unwrap();
+        n.add_incoming(&[(&index_next, next)]);
+        let _ = builder.build_unconditional_branch(body);
+        builder.position_at_end(done);
+    }
+}
+
+impl Default for FarneseException {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FarneseInternal<'a> for FarneseException {
+    fn create_opaque_type(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+
+        let frame_type = context.opaque_struct_type("Frame");
+        frame_type.set_body(&[i8_ptr_type.into(), i8_ptr_type.into()], false);
+
+        let handler_type: StructType<'a> = context.opaque_struct_type("Handler");
+        handler_type.set_body(
+            &[
+                i8_ptr_type.into(),
+                i64_type.into(),
+                i8_ptr_type.into(),
+                i64_type.array_type(JMP_BUF_WORDS).into(),
+            ],
+            false,
+        );
+
+        //   %ExceptionState = type { i8* handler, i8* frames, %Box* exception }
+        let box_ptr_type = module
+            .get_struct_type("Box")
+            .ptr_type(AddressSpace::default());
+        let state_type = context.opaque_struct_type("ExceptionState");
+        state_type.set_body(
+            &[i8_ptr_type.into(), i8_ptr_type.into(), box_ptr_type.into()],
+            false,
+        );
+        let state = module.add_global(state_type, None, "__exceptions");
+        state.set_initializer(&state_type.const_zero());
+        state.set_linkage(Linkage::WeakODR);
+    }
+
+    fn create_new_method(&self, _module: &Module<'a>) {}
+
+    fn create_get_methods(&self, module: &Module<'a>) {
+        self.create_handler_methods(module);
+        self.create_showerror_method(module);
+        self.create_throw_methods(module);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestHelper;
+    use crate::{FarneseString, LLVMPrintf};
+    use inkwell::context::Context;
+
+    #[test]
+    fn test_throw_catch() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_throw_catch", &builder, &context);
+        tester.start();
+
+        let exception = FarneseException::new();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let body = context.append_basic_block(func, "body");
+        let caught = context.append_basic_block(func, "caught");
+        let done = context.append_basic_block(func, "done");
+        let (handler, is_thrown) = exception.emit_ir_try(&builder, &tester.module);
+        let _ = builder.build_conditional_branch(is_thrown, caught, body);
+
+        builder.position_at_end(body);
+        let msg = FarneseString::new().emit_ir_literal(&tester.module, "boom");
+        let e = exception.emit_ir_new(&builder, &tester.module, "ErrorException", &[msg.into()]);
+        exception.emit_ir_throw(&builder, &tester.module, e);
+
+        builder.position_at_end(caught);
+        let e = exception.emit_ir_catch(&builder, &tester.module, handler);
+        exception.emit_ir_showerror(&builder, &tester.module, e);
+        let int = tester.module.get_type("Int64").clone();
+        let vector = DataType::new_array_type(&int, 1);
+        let one = context.i64_type().const_int(1, false);
+        let a = Array::new(vector.clone()).emit_ir_literal(&builder, &tester.module, &[one.into()]);
+        let boxed = FarneseBox::new().emit_ir_box(&builder, &tester.module, a.into(), &vector);
+        let i = context.i64_type().const_int(2, false);
+        let e = exception.emit_ir_new(
+            &builder,
+            &tester.module,
+            "BoundsError",
+            &[boxed.into(), i.into()],
+        );
+        exception.emit_ir_showerror(&builder, &tester.module, e);
+        let msg = FarneseString::new().emit_ir_literal(&tester.module, "x > 0");
+        let e = exception.emit_ir_new(&builder, &tester.module, "AssertionError", &[msg.into()]);
+        exception.emit_ir_showerror(&builder, &tester.module, e);
+        let msg = FarneseString::new().emit_ir_literal(&tester.module, "lengths 3 and 2");
+        let e = exception.emit_ir_new(&builder, &tester.module, "DimensionMismatch", &[msg.into()]);
+        exception.emit_ir_showerror(&builder, &tester.module, e);
+        let _ = builder.build_unconditional_branch(done);
+
+        builder.position_at_end(done);
+        let _ = (i.into(), int).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+
+        assert_eq!(
+            "boomBoundsError: attempt to access 1-element Vector{Int64} at index [2]\
+             AssertionError: x > 0DimensionMismatch: lengths 3 and 22",
+            tester.run()
+        );
+    }
+}
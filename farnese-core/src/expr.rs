@@ -0,0 +1,665 @@
+use super::{
+    Array, DataType, FarneseBox, FarneseInternal, LLVMPrintf, LLVMType, MethodHelper, Module,
+    StructHelper, Symbol,
+};
+use inkwell::builder::Builder;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, FunctionValue, IntValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// operators written between their arguments, with the same
+/// precedence the lexer gives them
+const OPERATORS: [(&str, u64); 22] = [
+    ("==", 1),
+    ("===", 1),
+    ("!=", 1),
+    ("<", 1),
+    ("<=", 1),
+    (">", 1),
+    (">=", 1),
+    ("<:", 1),
+    (".==", 1),
+    (".!=", 1),
+    (".<", 1),
+    (".<=", 1),
+    (".>", 1),
+    (".>=", 1),
+    ("+", 2),
+    ("-", 2),
+    (".+", 2),
+    (".-", 2),
+    ("*", 3),
+    ("/", 3),
+    (".*", 3),
+    ("./", 3),
+];
+
+/// binds tighter than every operator, e.g. the argument of -x
+const PREFIX: u64 = 4;
+
+/// julia's Expr, code as a value
+///
+///   struct Expr
+///     head::Symbol
+///     args::Vector{Any}
+///   end
+///
+/// a quote compiles to nested Exprs, e.g. :(a + 1) is Expr(:call, :+, :a, 1).
+/// Symbols and literals stand for themselves. Exprs are shown the way
+/// the code is written, putting parentheses where precedence needs them
+#[derive(Clone, Debug)]
+pub struct FarneseExpr;
+
+impl<'a, 'b> FarneseExpr {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Expr is added to Core after the struct shows are defined, so
+    /// it keeps its own
+    pub fn datatype(&self, module: &Module<'a>) -> DataType {
+        DataType::new(
+            Symbol::new("Expr"),
+            Symbol::new("Any"),
+            false,
+            false,
+            false,
+            vec![Symbol::new("head"), Symbol::new("args")],
+            Box::new(vec![
+                module.get_type("Symbol").clone(),
+                module.resolve_type("Vector{Any}"),
+            ]),
+        )
+    }
+
+    fn vector_any(&self, module: &Module<'a>) -> Array {
+        Array::new(DataType::new_array_type(module.get_type("Any"), 1))
+    }
+
+    fn box_ptr_type(&self, module: &Module<'a>) -> BasicTypeEnum<'a> {
+        module
+            .get_struct_type("Box")
+            .ptr_type(AddressSpace::default())
+            .into()
+    }
+
+    fn expr_ptr_type(&self, module: &Module<'a>) -> BasicTypeEnum<'a> {
+        module
+            .get_type("Expr")
+            .get_ir_value_type(module)
+            .try_into()
+            .unwrap()
+    }
+
+    /// a new Expr, head points to a Symbol and args are boxed values
+    pub fn emit_ir_new(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        head: PointerValue<'a>,
+        args: &[BasicValueEnum<'a>],
+    ) -> PointerValue<'a> {
+        let args = self
+            .vector_any(module)
+            .emit_ir_literal(builder, module, args);
+        module
+            .get_type("Expr")
+            .emit_ir_new(builder, module, &[head.into(), args.into()])
+    }
+
+    fn print_str(&self, builder: &'b Builder<'a>, module: &Module<'a>, val: &str) {
+        let val = builder
+            .build_global_string_ptr(val, "")
+            .unwrap()
+            .as_pointer_value();
+        let format = builder
+            .build_global_string_ptr("%s", "")
+            .unwrap()
+            .as_pointer_value();
+        let _ = builder.build_call(
+            module.get_function("printf"),
+            &[format.into(), val.into()],
+            "",
+        );
+    }
+
+    /// the nth argument, 1-based
+    fn emit_ir_arg(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        args: PointerValue<'a>,
+        n: u64,
+    ) -> BasicValueEnum<'a> {
+        let n = module.get_context().i64_type().const_int(n, false);
+        let ptr = self
+            .vector_any(module)
+            .emit_ir_element_ptr(builder, module, args, n, false);
+        builder.build_load(ptr, "").unwrap()
+    }
+
+    fn emit_ir_print_arg(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: BasicValueEnum<'a>,
+        precedence: IntValue<'a>,
+    ) {
+        let _ = builder.build_call(
+            module.get_function("__print_expr_arg"),
+            &[val.into(), precedence.into()],
+            "",
+        );
+    }
+
+    /// prints the arguments from the nth on with sep in between
+    fn emit_ir_print_args(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        args: PointerValue<'a>,
+        from: u64,
+        sep: &str,
+    ) {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let array = self.vector_any(module);
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let body = context.append_basic_block(func, "body");
+        let separator = context.append_basic_block(func, "separator");
+        let element = context.append_basic_block(func, "element");
+        let done = context.append_basic_block(func, "done");
+
+        let from = i64_type.const_int(from, false);
+        let length = array.emit_ir_length(builder, args);
+        let is_empty = builder
+            .build_int_compare(IntPredicate::SGT, from, length, "")
+            .unwrap();
+        let entry = builder.get_insert_block().unwrap();
+        let _ = builder.build_conditional_branch(is_empty, done, body);
+
+        builder.position_at_end(body);
+        let i = builder.build_phi(i64_type, "i").unwrap();
+        let index = i.as_basic_value().into_int_value();
+        let is_first = builder
+            .build_int_compare(IntPredicate::EQ, index, from, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_first, element, separator);
+
+        builder.position_at_end(separator);
+        self.print_str(builder, module, sep);
+        let _ = builder.build_unconditional_branch(element);
+
+        builder.position_at_end(element);
+        let ptr = array.emit_ir_element_ptr(builder, module, args, index, false);
+        let val = builder.build_load(ptr, "").unwrap();
+        self.emit_ir_print_arg(builder, module, val, i64_type.const_zero());
+        let next = builder
+            .build_int_add(index, i64_type.const_int(1, false), "")
+            .unwrap();
+        let is_done = builder
+            .build_int_compare(IntPredicate::SGT, next, length, "")
+            .unwrap();
+        i.add_incoming(&[(&from, entry), (&next, element)]);
+        let _ = builder.build_conditional_branch(is_done, done, body);
+
+        builder.position_at_end(done);
+    }
+
+    /// __expr_precedence(Box*) -> i64, 0 unless the value is an operator symbol
+    fn create_precedence_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let func = i64_type.fn_type(&[self.box_ptr_type(module).into()], false);
+        let func = module.add_function("__expr_precedence", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let symbol = context.append_basic_block(func, "symbol");
+        let other = context.append_basic_block(func, "other");
+        builder.position_at_end(entry);
+
+        let box_helper = FarneseBox::new();
+        let ptr = func.get_nth_method_input(0).into_pointer_value();
+        let symbol_type = module.get_type("Symbol");
+        let tag = box_helper.emit_ir_typeof(&builder, ptr);
+        let is_symbol = builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                tag,
+                symbol_type.emit_ir_object(module),
+                "",
+            )
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_symbol, symbol, other);
+
+        builder.position_at_end(symbol);
+        let sym = box_helper
+            .emit_ir_unbox(&builder, module, ptr, symbol_type)
+            .into_pointer_value();
+        let hash = sym.load_nth_field(&builder, 0).into_int_value();
+        let cases = OPERATORS
+            .iter()
+            .map(|(op, precedence)| {
+                let block = context.append_basic_block(func, op);
+                builder.position_at_end(block);
+                let _ = builder.build_return(Some(&i64_type.const_int(*precedence, false)));
+                let hash = Symbol::new(op).hash() as u64;
+                (i64_type.const_int(hash, true), block)
+            })
+            .collect::<Vec<_>>();
+        builder.position_at_end(symbol);
+        let _ = builder.build_switch(hash, other, &cases);
+
+        builder.position_at_end(other);
+        let _ = builder.build_return(Some(&i64_type.const_zero()));
+    }
+
+    /// __print_expr_arg(Box*, i64 precedence), Exprs are printed as code,
+    /// symbols by their name and anything else is shown
+    fn create_print_arg_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let func = context
+            .void_type()
+            .fn_type(&[self.box_ptr_type(module).into(), i64_type.into()], false);
+        let func = module.add_function("__print_expr_arg", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let expr = context.append_basic_block(func, "expr");
+        let not_expr = context.append_basic_block(func, "not_expr");
+        let symbol = context.append_basic_block(func, "symbol");
+        let other = context.append_basic_block(func, "other");
+        builder.position_at_end(entry);
+
+        let box_helper = FarneseBox::new();
+        let ptr = func.get_nth_method_input(0).into_pointer_value();
+        let precedence = func.get_nth_method_input(1);
+        let tag = box_helper.emit_ir_typeof(&builder, ptr);
+        let expr_type = module.get_type("Expr");
+        let symbol_type = module.get_type("Symbol");
+        let is_type = |datatype: &DataType| {
+            builder
+                .build_int_compare(IntPredicate::EQ, tag, datatype.emit_ir_object(module), "")
+                .unwrap()
+        };
+        let _ = builder.build_conditional_branch(is_type(expr_type), expr, not_expr);
+
+        builder.position_at_end(expr);
+        let val = box_helper.emit_ir_unbox(&builder, module, ptr, expr_type);
+        let _ = builder.build_call(
+            module.get_function("__print_expr"),
+            &[val.into(), precedence],
+            "",
+        );
+        let _ = builder.build_return(None);
+
+        builder.position_at_end(not_expr);
+        let _ = builder.build_conditional_branch(is_type(symbol_type), symbol, other);
+
+        builder.position_at_end(symbol);
+        let val = box_helper.emit_ir_unbox(&builder, module, ptr, symbol_type);
+        let _ = (val.into(), symbol_type.clone()).emit_ir_printf(&builder, module);
+        let _ = builder.build_return(None);
+
+        builder.position_at_end(other);
+        let _ = builder.build_call(module.get_function("show_Any"), &[ptr.into()], "");
+        let _ = builder.build_return(None);
+    }
+
+    /// __print_expr(Expr*, i64 precedence), it calls __print_expr_arg
+    /// which calls it back
+    fn declare_print_method(&self, module: &Module<'a>) -> FunctionValue<'a> {
+        let context = module.get_context();
+        let func = context.void_type().fn_type(
+            &[self.expr_ptr_type(module).into(), context.i64_type().into()],
+            false,
+        );
+        module.add_function("__print_expr", func, None)
+    }
+
+    /// prints the code of an Expr, an operator binding less tightly
+    /// than precedence is put in parentheses
+    fn create_print_method(&self, module: &Module<'a>, func: FunctionValue<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let entry = context.append_basic_block(func, "entry");
+        let other = context.append_basic_block(func, "other");
+        builder.position_at_end(entry);
+
+        let ptr = func.get_nth_method_input(0).into_pointer_value();
+        let min_precedence = func.get_nth_method_input(1).into_int_value();
+        let expr_type = module.get_type("Expr");
+        let (head, symbol_type) = expr_type.emit_ir_getfield(&builder, ptr, "head").unwrap();
+        let args = expr_type
+            .emit_ir_getfield(&builder, ptr, "args")
+            .unwrap()
+            .0
+            .into_pointer_value();
+        let length = self.vector_any(module).emit_ir_length(&builder, args);
+        let hash = head
+            .into_pointer_value()
+            .load_nth_field(&builder, 0)
+            .into_int_value();
+
+        let heads = [
+            "call",
+            "block",
+            "=",
+            ".=",
+            "vect",
+            "tuple",
+            "ref",
+            "macrocall",
+            ".",
+        ];
+        let cases = heads
+            .iter()
+            .map(|x| {
+                let hash = Symbol::new(x).hash() as u64;
+                (
+                    i64_type.const_int(hash, true),
+                    context.append_basic_block(func, x),
+                )
+            })
+            .collect::<Vec<_>>();
+        let _ = builder.build_switch(hash, other, &cases);
+
+        let zero = i64_type.const_zero();
+        for (head, (_, block)) in heads.iter().zip(cases.iter()) {
+            builder.position_at_end(*block);
+            let arg = |n| self.emit_ir_arg(&builder, module, args, n);
+            match *head {
+                "call" => {
+                    let infix = context.append_basic_block(func, "infix");
+                    let not_infix = context.append_basic_block(func, "not_infix");
+                    let prefix = context.append_basic_block(func, "prefix");
+                    let call = context.append_basic_block(func, "call");
+                    let f = arg(1);
+                    let precedence = builder
+                        .build_call(module.get_function("__expr_precedence"), &[f.into()], "")
+                        .unwrap()
+                        .try_as_basic_value()
+                        .left()
+                        .unwrap()
+                        .into_int_value();
+                    let is_operator = builder
+                        .build_int_compare(IntPredicate::SGT, precedence, zero, "")
+                        .unwrap();
+                    let has_args = |n| {
+                        builder
+                            .build_int_compare(
+                                IntPredicate::EQ,
+                                length,
+                                i64_type.const_int(n, false),
+                                "",
+                            )
+                            .unwrap()
+                    };
+                    let is_infix = builder.build_and(is_operator, has_args(3), "").unwrap();
+                    let _ = builder.build_conditional_branch(is_infix, infix, not_infix);
+
+                    // a + b, parenthesized when the surrounding operator binds tighter
+                    builder.position_at_end(infix);
+                    let is_nested = builder
+                        .build_int_compare(IntPredicate::SLT, precedence, min_precedence, "")
+                        .unwrap();
+                    let paren = |open: &str| {
+                        let format = builder
+                            .build_global_string_ptr("%s", "")
+                            .unwrap()
+                            .as_pointer_value();
+                        let paren = builder
+                            .build_global_string_ptr(open, "")
+                            .unwrap()
+                            .as_pointer_value();
+                        let empty = builder
+                            .build_global_string_ptr("", "")
+                            .unwrap()
+                            .as_pointer_value();
+                        let val = builder.build_select(is_nested, paren, empty, "").unwrap();
+                        let _ = builder.build_call(
+                            module.get_function("printf"),
+                            &[format.into(), val.into()],
+                            "",
+                        );
+                    };
+                    paren("(");
+                    self.emit_ir_print_arg(&builder, module, arg(2), precedence);
+                    self.print_str(&builder, module, " ");
+                    self.emit_ir_print_arg(&builder, module, f, zero);
+                    self.print_str(&builder, module, " ");
+                    // operators are left associative
+                    let right = builder
+                        .build_int_add(precedence, i64_type.const_int(1, false), "")
+                        .unwrap();
+                    self.emit_ir_print_arg(&builder, module, arg(3), right);
+                    paren(")");
+                    let _ = builder.build_return(None);
+
+                    builder.position_at_end(not_infix);
+                    let is_prefix = builder.build_and(is_operator, has_args(2), "").unwrap();
+                    let _ = builder.build_conditional_branch(is_prefix, prefix, call);
+
+                    // -x
+                    builder.position_at_end(prefix);
+                    self.emit_ir_print_arg(&builder, module, f, zero);
+                    let precedence = i64_type.const_int(PREFIX, false);
+                    self.emit_ir_print_arg(&builder, module, arg(2), precedence);
+                    let _ = builder.build_return(None);
+
+                    // f(a, b)
+                    builder.position_at_end(call);
+                    self.emit_ir_print_arg(&builder, module, f, zero);
+                    self.print_str(&builder, module, "(");
+                    self.emit_ir_print_args(&builder, module, args, 2, ", ");
+                    self.print_str(&builder, module, ")");
+                }
+                "block" => {
+                    self.print_str(&builder, module, "quote\n    ");
+                    self.emit_ir_print_args(&builder, module, args, 1, "\n    ");
+                    self.print_str(&builder, module, "\nend");
+                }
+                // f.(x) is Expr(:., :f, Expr(:tuple, :x))
+                "=" | ".=" | "." => {
+                    let sep = match *head {
+                        "=" => " = ",
+                        ".=" => " .= ",
+                        _ => ".",
+                    };
+                    self.emit_ir_print_arg(&builder, module, arg(1), zero);
+                    self.print_str(&builder, module, sep);
+                    self.emit_ir_print_arg(&builder, module, arg(2), zero);
+                }
+                "vect" | "tuple" => {
+                    let (open, close) = match *head {
+                        "vect" => ("[", "]"),
+                        _ => ("(", ")"),
+                    };
+                    self.print_str(&builder, module, open);
+                    self.emit_ir_print_args(&builder, module, args, 1, ", ");
+                    self.print_str(&builder, module, close);
+                }
+                // a[i] and @m(x)
+                _ => {
+                    let (open, close) = match *head {
+                        "ref" => ("[", "]"),
+                        _ => ("(", ")"),
+                    };
+                    self.emit_ir_print_arg(&builder, module, arg(1), zero);
+                    self.print_str(&builder, module, open);
+                    self.emit_ir_print_args(&builder, module, args, 2, ", ");
+                    self.print_str(&builder, module, close);
+                }
+            }
+            let _ = builder.build_return(None);
+        }
+
+        // anything else as a call of the constructor, e.g. Expr(:tuple, a, b)
+        builder.position_at_end(other);
+        self.print_str(&builder, module, "Expr(:");
+        let _ = (head.into(), symbol_type).emit_ir_printf(&builder, module);
+        let has_args = builder
+            .build_int_compare(IntPredicate::SGT, length, zero, "")
+            .unwrap();
+        let format = builder
+            .build_global_string_ptr("%s", "")
+            .unwrap()
+            .as_pointer_value();
+        let comma = builder
+            .build_global_string_ptr(", ", "")
+            .unwrap()
+            .as_pointer_value();
+        let empty = builder
+            .build_global_string_ptr("", "")
+            .unwrap()
+            .as_pointer_value();
+        let sep = builder.build_select(has_args, comma, empty, "").unwrap();
+        let _ = builder.build_call(
+            module.get_function("printf"),
+            &[format.into(), sep.into()],
+            "",
+        );
+        self.emit_ir_print_args(&builder, module, args, 1, ", ");
+        self.print_str(&builder, module, ")");
+        let _ = builder.build_return(None);
+    }
+
+    /// the methods Core adds to its method table
+    pub fn methods(&self, module: &Module<'a>) -> Vec<(Symbol, Vec<DataType>, DataType)> {
+        let expr_type = module.get_type("Expr").clone();
+        let nothing = module.get_type("Nothing").clone();
+        vec![(Symbol::new("print_Expr"), vec![expr_type], nothing)]
+    }
+
+    /// print_Expr writes the code without the :( ) around it
+    fn create_print_expr_method(&self, module: &Module<'a>, print: FunctionValue<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let nothing_type = module.get_type("Nothing");
+        let func = module.method_type(&[module.get_type("Expr").clone()], nothing_type);
+        let func = module.add_function("print_Expr", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let ptr = func.get_nth_method_input(0);
+        let zero = context.i64_type().const_zero();
+        let _ = builder.build_call(print, &[ptr, zero.into()], "");
+        let nothing = module.get_struct_type("Nothing").const_named_struct(&[]);
+        let _ = builder.build_return(Some(&nothing));
+    }
+
+    /// the show of the Expr type object, blocks are shown as quote ... end
+    /// and everything else as :(...)
+    fn create_show_method(&self, module: &Module<'a>, print: FunctionValue<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let expr_type = module.get_type("Expr");
+        let func = expr_type.emit_ir_show_method(module);
+        let entry = context.append_basic_block(func, "entry");
+        let block = context.append_basic_block(func, "block");
+        let quoted = context.append_basic_block(func, "quoted");
+        builder.position_at_end(entry);
+
+        let ptr = func.get_nth_method_input(0).into_pointer_value();
+        let ptr = builder
+            .build_pointer_cast(ptr, self.expr_ptr_type(module).into_pointer_type(), "")
+            .unwrap();
+        let (head, _) = expr_type.emit_ir_getfield(&builder, ptr, "head").unwrap();
+        let hash = head
+            .into_pointer_value()
+            .load_nth_field(&builder, 0)
+            .into_int_value();
+        let i64_type = context.i64_type();
+        let block_hash = i64_type.const_int(Symbol::new("block").hash() as u64, true);
+        let is_block = builder
+            .build_int_compare(IntPredicate::EQ, hash, block_hash, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_block, block, quoted);
+
+        let zero = i64_type.const_zero();
+        builder.position_at_end(block);
+        let _ = builder.build_call(print, &[ptr.into(), zero.into()], "");
+        let _ = builder.build_return(None);
+
+        builder.position_at_end(quoted);
+        self.print_str(&builder, module, ":(");
+        let _ = builder.build_call(print, &[ptr.into(), zero.into()], "");
+        self.print_str(&builder, module, ")");
+        let _ = builder.build_return(None);
+    }
+}
+
+impl Default for FarneseExpr {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FarneseInternal<'a> for FarneseExpr {
+    /// the type has to be inserted first, see datatype
+    fn create_opaque_type(&self, module: &Module<'a>) {
+        module.get_type("Expr").emit_ir_type(module);
+    }
+
+    fn create_new_method(&self, _module: &Module<'a>) {}
+
+    /// has to come after show_Any exists
+    fn create_get_methods(&self, module: &Module<'a>) {
+        let print = self.declare_print_method(module);
+        self.create_precedence_method(module);
+        self.create_print_arg_method(module);
+        self.create_print_method(module, print);
+        self.create_show_method(module, print);
+        self.create_print_expr_method(module, print);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestHelper;
+    use inkwell::context::Context;
+
+    #[test]
+    fn test_print_show() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_print_show", &builder, &context);
+        tester.start();
+
+        // :((a + b) * 2)
+        let module = &tester.module;
+        let box_helper = FarneseBox::new();
+        let expr = FarneseExpr::new();
+        let symbol = |x: &str| {
+            let val = Symbol::new(x).emit_ir_constant(module).into();
+            box_helper.emit_ir_box(&builder, module, val, module.get_type("Symbol"))
+        };
+        let call = |args: [_; 3]| {
+            let head = Symbol::new("call").emit_ir_constant(module);
+            expr.emit_ir_new(
+                &builder,
+                module,
+                head,
+                &args.map(BasicValueEnum::PointerValue),
+            )
+        };
+        let sum = call([symbol("+"), symbol("a"), symbol("b")]);
+        let sum = box_helper.emit_ir_box(&builder, module, sum.into(), module.get_type("Expr"));
+        let two = context.i64_type().const_int(2, false).into();
+        let two = box_helper.emit_ir_box(&builder, module, two, module.get_type("Int64"));
+        let product = call([symbol("*"), sum, two]);
+
+        let _ = builder.build_call(module.get_function("print_Expr"), &[product.into()], "");
+        expr.print_str(&builder, module, "|");
+        let show = module.get_type("Expr").emit_ir_show_method(module);
+        let _ = builder.build_call(show, &[product.into()], "");
+        expr.print_str(&builder, module, "|");
+        let dotted = call([symbol(".+"), symbol("a"), symbol("b")]);
+        let _ = builder.build_call(module.get_function("print_Expr"), &[dotted.into()], "");
+        tester.end();
+
+        assert_eq!("(a + b) * 2|:((a + b) * 2)|a .+ b", tester.run());
+    }
+}
@@ -0,0 +1,430 @@
+use super::{DataType, FarneseChar, FarneseString, Module, StructHelper};
+use inkwell::AddressSpace;
+use inkwell::builder::Builder;
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, PointerValue};
+
+/// a conversion in a format string, e.g. %-8.3f
+#[derive(Clone, Debug, PartialEq)]
+struct Spec {
+    /// flags, width and precision as written, e.g. -8.3
+    modifiers: String,
+    /// the width and precision are arguments, e.g. %*.*f
+    width_arg: bool,
+    precision_arg: bool,
+    conversion: char,
+}
+
+/// a format string split into literal text and conversions
+#[derive(Clone, Debug, PartialEq)]
+enum Piece {
+    Text(String),
+    Spec(Spec),
+}
+
+/// formatted output like julia's @printf and @sprintf, the format
+/// string is checked against the argument types when compiling and
+/// lowers to a single call of libc's printf or snprintf
+///
+/// the conversions are the C ones without length modifiers, which are
+/// picked from the argument type instead, e.g. %d of an Int32 becomes
+/// %lld of the sign extended value
+#[derive(Clone, Debug)]
+pub struct FarneseFormat {
+    /// the macro being compiled, used in error messages
+    name: String,
+}
+
+impl<'a, 'b> FarneseFormat {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+        }
+    }
+
+    fn error(&self, msg: &str) -> ! {
+        panic!("ArgumentError: @{}: {}", self.name, msg)
+    }
+
+    fn parse(&self, format: &str) -> Vec<Piece> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                text.push_str("%%");
+                continue;
+            }
+            let mut spec = Spec {
+                modifiers: String::new(),
+                width_arg: false,
+                precision_arg: false,
+                conversion: ' ',
+            };
+            while let Some(x) = chars.next_if(|x| "-+ #0".contains(*x)) {
+                spec.modifiers.push(x);
+            }
+            // widths and precisions are either digits or *
+            if chars.next_if_eq(&'*').is_some() {
+                spec.width_arg = true;
+                spec.modifiers.push('*');
+            } else {
+                while let Some(x) = chars.next_if(|x| x.is_ascii_digit()) {
+                    spec.modifiers.push(x);
+                }
+            }
+            if chars.next_if_eq(&'.').is_some() {
+                spec.modifiers.push('.');
+                if chars.next_if_eq(&'*').is_some() {
+                    spec.precision_arg = true;
+                    spec.modifiers.push('*');
+                } else {
+                    while let Some(x) = chars.next_if(|x| x.is_ascii_digit()) {
+                        spec.modifiers.push(x);
+                    }
+                }
+            }
+            spec.conversion = match chars.next() {
+                Some(x) if "diouxXeEfFgGaAcs".contains(x) => x,
+                Some(x) => self.error(&format!("invalid conversion %{} in \"{}\"", x, format)),
+                None => self.error(&format!("incomplete format string \"{}\"", format)),
+            };
+            if !text.is_empty() {
+                pieces.push(Piece::Text(std::mem::take(&mut text)));
+            }
+            pieces.push(Piece::Spec(spec));
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        pieces
+    }
+
+    /// the C format string and the arguments to pass along with it,
+    /// panics if the arguments don't match the format
+    fn emit_ir_args(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        format: &str,
+        args: &[(BasicValueEnum<'a>, DataType)],
+    ) -> (String, Vec<BasicMetadataValueEnum<'a>>) {
+        let pieces = self.parse(format);
+        let count = pieces
+            .iter()
+            .map(|x| match x {
+                Piece::Spec(x) => 1 + x.width_arg as usize + x.precision_arg as usize,
+                Piece::Text(_) => 0,
+            })
+            .sum::<usize>();
+        if count != args.len() {
+            self.error(&format!(
+                "format string \"{}\" needs {} arguments but {} were given",
+                format,
+                count,
+                args.len()
+            ));
+        }
+
+        let mut c_format = String::new();
+        let mut c_args = Vec::new();
+        let mut args = args.iter();
+        for piece in pieces {
+            let spec = match piece {
+                Piece::Text(x) => {
+                    c_format.push_str(&x);
+                    continue;
+                }
+                Piece::Spec(x) => x,
+            };
+            // widths and precisions passed as arguments are C ints
+            for _ in 0..(spec.width_arg as usize + spec.precision_arg as usize) {
+                let (val, datatype) = args.next().unwrap();
+                self.check(module, &spec, datatype, "Integer");
+                let val = builder
+                    .build_int_cast_sign_flag(
+                        val.into_int_value(),
+                        module.get_context().i32_type(),
+                        self.is_signed(module, datatype),
+                        "",
+                    )
+                    .unwrap();
+                c_args.push(val.into());
+            }
+            let (val, datatype) = args.next().unwrap();
+            let (conversion, val) = self.emit_ir_arg(builder, module, &spec, *val, datatype);
+            c_format.push('%');
+            c_format.push_str(&spec.modifiers);
+            c_format.push_str(&conversion);
+            c_args.push(val);
+        }
+        (c_format, c_args)
+    }
+
+    fn is_signed(&self, module: &Module<'a>, datatype: &DataType) -> bool {
+        module.is_subtype(datatype, module.get_type("Signed"))
+    }
+
+    /// panics unless datatype is a concrete subtype of the type called name
+    fn check(&self, module: &Module<'a>, spec: &Spec, datatype: &DataType, name: &str) {
+        if datatype.is_abstract {
+            self.error(&format!(
+                "the argument of %{} has type {}, which is only known when running",
+                spec.conversion,
+                datatype.full_name()
+            ));
+        }
+        let is_128 = matches!(datatype.name().name(), "Int128" | "UInt128");
+        if is_128 || !module.is_subtype(datatype, module.get_type(name)) {
+            self.error(&format!(
+                "%{} needs an argument of type {}, got {}",
+                spec.conversion,
+                name,
+                datatype.full_name()
+            ));
+        }
+    }
+
+    /// the C conversion with its length modifier and the value converted to match
+    fn emit_ir_arg(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        spec: &Spec,
+        val: BasicValueEnum<'a>,
+        datatype: &DataType,
+    ) -> (String, BasicMetadataValueEnum<'a>) {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let f64_type = context.f64_type();
+        let name = datatype.name().name();
+        let is_integer = !datatype.is_abstract
+            && module.is_subtype(datatype, module.get_type("Integer"))
+            && !matches!(name, "Int128" | "UInt128");
+        let is_signed = self.is_signed(module, datatype);
+        match spec.conversion {
+            'd' | 'i' | 'o' | 'u' | 'x' | 'X' => {
+                self.check(module, spec, datatype, "Integer");
+                let val = builder
+                    .build_int_cast_sign_flag(val.into_int_value(), i64_type, is_signed, "")
+                    .unwrap();
+                let conversion = match spec.conversion {
+                    'd' | 'i' if !is_signed => 'u',
+                    x => x,
+                };
+                (format!("ll{}", conversion), val.into())
+            }
+            'c' => {
+                let val = match name {
+                    "Char" => val.into_int_value(),
+                    _ => {
+                        self.check(module, spec, datatype, "Integer");
+                        builder
+                            .build_int_cast_sign_flag(
+                                val.into_int_value(),
+                                module.get_context().i32_type(),
+                                is_signed,
+                                "",
+                            )
+                            .unwrap()
+                    }
+                };
+                let (buffer, _) = FarneseChar::new().emit_ir_utf8(builder, module, val);
+                ("s".to_string(), buffer.into())
+            }
+            's' => {
+                let chars = match name {
+                    "String" => val.into_pointer_value().load_nth_field(builder, 0),
+                    "Symbol" => val.into_pointer_value().load_nth_field(builder, 1),
+                    "Char" => {
+                        let (buffer, _) =
+                            FarneseChar::new().emit_ir_utf8(builder, module, val.into_int_value());
+                        buffer.into()
+                    }
+                    "Bool" => builder
+                        .build_select(
+                            val.into_int_value(),
+                            self.emit_ir_c_string(builder, "true"),
+                            self.emit_ir_c_string(builder, "false"),
+                            "",
+                        )
+                        .unwrap(),
+                    "Nothing" => self.emit_ir_c_string(builder, "nothing").into(),
+                    "Float32" | "Float64" => {
+                        let buffer = builder
+                            .build_array_alloca(
+                                context.i8_type(),
+                                i64_type.const_int(32, false),
+                                "",
+                            )
+                            .unwrap();
+                        let is_float32 = context
+                            .bool_type()
+                            .const_int((name == "Float32") as u64, false);
+                        let val = builder
+                            .build_float_ext(val.into_float_value(), f64_type, "")
+                            .unwrap();
+                        let _ = builder.build_call(
+                            module.get_function("__float_chars"),
+                            &[val.into(), is_float32.into(), buffer.into()],
+                            "",
+                        );
+                        buffer.into()
+                    }
+                    _ if is_integer => {
+                        let val = builder
+                            .build_int_cast_sign_flag(val.into_int_value(), i64_type, is_signed, "")
+                            .unwrap();
+                        let conversion = if is_signed { "lld" } else { "llu" };
+                        return (conversion.to_string(), val.into());
+                    }
+                    _ => self.error(&format!(
+                        "%s needs a String, Symbol, Char, Bool or number, got {}",
+                        datatype.full_name()
+                    )),
+                };
+                ("s".to_string(), chars.into())
+            }
+            _ => {
+                let val = match val {
+                    BasicValueEnum::FloatValue(x) => {
+                        builder.build_float_ext(x, f64_type, "").unwrap()
+                    }
+                    _ if is_integer && is_signed => builder
+                        .build_signed_int_to_float(val.into_int_value(), f64_type, "")
+                        .unwrap(),
+                    _ => {
+                        self.check(module, spec, datatype, "Integer");
+                        builder
+                            .build_unsigned_int_to_float(val.into_int_value(), f64_type, "")
+                            .unwrap()
+                    }
+                };
+                (spec.conversion.to_string(), val.into())
+            }
+        }
+    }
+
+    fn emit_ir_c_string(&self, builder: &'b Builder<'a>, val: &str) -> PointerValue<'a> {
+        builder
+            .build_global_string_ptr(val, "")
+            .unwrap()
+            .as_pointer_value()
+    }
+
+    /// @printf(format, args...)
+    pub fn emit_ir_printf(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        format: &str,
+        args: &[(BasicValueEnum<'a>, DataType)],
+    ) {
+        let (format, args) = self.emit_ir_args(builder, module, format, args);
+        let mut func_args = vec![self.emit_ir_c_string(builder, &format).into()];
+        func_args.extend(args);
+        let _ = builder.build_call(module.get_function("printf"), &func_args, "");
+    }
+
+    /// @sprintf(format, args...), formats twice, first to find the length
+    pub fn emit_ir_sprintf(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        format: &str,
+        args: &[(BasicValueEnum<'a>, DataType)],
+    ) -> PointerValue<'a> {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let (format, args) = self.emit_ir_args(builder, module, format, args);
+        let format = self.emit_ir_c_string(builder, &format);
+        let snprintf = |buffer: PointerValue<'a>, size| {
+            let mut func_args: Vec<BasicMetadataValueEnum<'a>> =
+                vec![buffer.into(), size, format.into()];
+            func_args.extend(args.iter().cloned());
+            builder
+                .build_call(module.get_function("snprintf"), &func_args, "")
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value()
+        };
+        let null = context
+            .i8_type()
+            .ptr_type(AddressSpace::default())
+            .const_null();
+        let length = snprintf(null, i64_type.const_zero().into());
+        let length = builder.build_int_s_extend(length, i64_type, "").unwrap();
+        let (string_ptr, data) = FarneseString::emit_ir_alloc(builder, module, length);
+        let size = builder
+            .build_int_add(length, i64_type.const_int(1, false), "")
+            .unwrap();
+        let _ = snprintf(data, size.into());
+        string_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestHelper;
+    use inkwell::context::Context;
+
+    #[test]
+    fn test_printf_sprintf() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_printf_sprintf", &builder, &context);
+        tester.start();
+
+        let module = &tester.module;
+        let int = module.get_type("Int64").clone();
+        let int32 = module.get_type("Int32").clone();
+        let float = module.get_type("Float64").clone();
+        let args = [
+            (
+                context.i64_type().const_int(-7i64 as u64, true).into(),
+                int.clone(),
+            ),
+            (context.i32_type().const_int(255, false).into(), int32),
+            (context.f64_type().const_float(2.5).into(), float.clone()),
+            (context.f64_type().const_float(0.1).into(), float),
+        ];
+        let printf = FarneseFormat::new("printf");
+        printf.emit_ir_printf(&builder, module, "%d|%04x|%5.2f|%s|%%|", &args);
+
+        let width = [
+            (context.i64_type().const_int(4, false).into(), int.clone()),
+            (context.i64_type().const_int(3, false).into(), int),
+        ];
+        let val = FarneseFormat::new("sprintf").emit_ir_sprintf(&builder, module, "%-*d|", &width);
+        let _ = builder.build_call(module.get_function("print_String"), &[val.into()], "");
+        tester.end();
+
+        assert_eq!("-7|00ff| 2.50|0.1|%|3   |", tester.run());
+    }
+
+    #[test]
+    fn test_parse() {
+        let format = FarneseFormat::new("printf");
+        let pieces = format.parse("x=%-8.3f%%");
+        assert_eq!(
+            vec![
+                Piece::Text("x=".to_string()),
+                Piece::Spec(Spec {
+                    modifiers: "-8.3".to_string(),
+                    width_arg: false,
+                    precision_arg: false,
+                    conversion: 'f',
+                }),
+                Piece::Text("%%".to_string()),
+            ],
+            pieces
+        );
+    }
+}
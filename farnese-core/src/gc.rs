@@ -0,0 +1,773 @@
+use super::{FarneseInternal, MethodHelper, Module, StructHelper};
+use inkwell::builder::Builder;
+use inkwell::module::Linkage;
+use inkwell::types::{BasicType, BasicTypeEnum, StructType};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, IntValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// bytes allocated between two automatic collections, grows
+/// with the live heap so big heaps aren't traced too often
+const THRESHOLD: u64 = 1 << 20;
+
+// fields of %GCState
+const OBJECTS: u32 = 0;
+const ROOTS: u32 = 1;
+const GLOBALS: u32 = 2;
+const NEXT_COLLECTION: u32 = 3;
+const ALLOCATED: u32 = 4;
+const ALLOCATIONS: u32 = 5;
+const BYTES: u32 = 6;
+const FREED: u32 = 7;
+const COLLECTIONS: u32 = 8;
+const LIVE: u32 = 9;
+
+/// a precise mark-sweep collector for Strings, Arrays, Exprs and boxed values.
+/// every object is preceded by a header linking it into the list of
+/// objects and telling which of its words are pointers
+///
+///   %GCHeader = type { i8* next, i64 size, i64 first, i64 count, i64 mark }
+///
+/// words first until first + count of the object are traced, mark is 1
+/// for reachable objects while collecting and 2 for static objects like
+/// string literals, which live in globals and are never freed
+///
+/// roots are a shadow stack of values pushed by compiled code, every
+/// function pops what it pushed when it returns, and the addresses of
+/// module globals. collections only happen at safepoints in between
+/// statements or on GC.gc() so values in registers stay valid
+#[derive(Clone, Debug)]
+pub struct FarneseGC;
+
+impl<'a, 'b> FarneseGC {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn state(&self, module: &Module<'a>) -> PointerValue<'a> {
+        module.get_global("__gc").as_pointer_value()
+    }
+
+    fn call(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        args: &[BasicMetadataValueEnum<'a>],
+    ) -> Option<BasicValueEnum<'a>> {
+        builder
+            .build_call(module.get_function(name), args, "")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+    }
+
+    /// allocates a zeroed object of ir_type, count words starting at
+    /// first are pointers to other objects
+    pub fn emit_ir_alloc(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ir_type: BasicTypeEnum<'a>,
+        first: u64,
+        count: u64,
+    ) -> PointerValue<'a> {
+        let i64_type = module.get_context().i64_type();
+        let args = [
+            ir_type.size_of().unwrap().into(),
+            i64_type.const_int(first, false).into(),
+            i64_type.const_int(count, false).into(),
+        ];
+        let ptr = self.call(builder, module, "__gc_alloc", &args).unwrap();
+        builder
+            .build_pointer_cast(
+                ptr.into_pointer_value(),
+                ir_type.ptr_type(AddressSpace::default()),
+                "",
+            )
+            .unwrap()
+    }
+
+    /// allocates a zeroed buffer of num_bytes, which holds nothing
+    /// but pointers to other objects when is_tracked is set
+    pub fn emit_ir_alloc_bytes(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        num_bytes: IntValue<'a>,
+        is_tracked: IntValue<'a>,
+    ) -> PointerValue<'a> {
+        let i64_type = module.get_context().i64_type();
+        let words = builder
+            .build_int_unsigned_div(num_bytes, i64_type.const_int(8, false), "")
+            .unwrap();
+        let count = builder
+            .build_select(is_tracked, words, i64_type.const_zero(), "")
+            .unwrap();
+        let args = [num_bytes.into(), i64_type.const_zero().into(), count.into()];
+        self.call(builder, module, "__gc_alloc", &args)
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    /// the header of a static object, these are never traced or freed
+    pub fn const_static_header(&self, module: &Module<'a>, size: u64) -> BasicValueEnum<'a> {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        module
+            .get_struct_type("GCHeader")
+            .const_named_struct(&[
+                context
+                    .i8_type()
+                    .ptr_type(AddressSpace::default())
+                    .const_null()
+                    .into(),
+                i64_type.const_int(size, false).into(),
+                i64_type.const_zero().into(),
+                i64_type.const_zero().into(),
+                i64_type.const_int(2, false).into(),
+            ])
+            .into()
+    }
+
+    /// keeps ptr alive until the current function returns
+    pub fn emit_ir_push_root(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+    ) {
+        let i8_ptr_type = module
+            .get_context()
+            .i8_type()
+            .ptr_type(AddressSpace::default());
+        let ptr = builder.build_pointer_cast(ptr, i8_ptr_type, "").unwrap();
+        let _ = self.call(builder, module, "__gc_push_root", &[ptr.into()]);
+    }
+
+    /// keeps whatever the global points to alive for good
+    pub fn emit_ir_add_global(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        global: PointerValue<'a>,
+    ) {
+        let i8_ptr_type = module
+            .get_context()
+            .i8_type()
+            .ptr_type(AddressSpace::default());
+        let global = builder
+            .build_pointer_cast(global, i8_ptr_type.ptr_type(AddressSpace::default()), "")
+            .unwrap();
+        let _ = self.call(builder, module, "__gc_add_global", &[global.into()]);
+    }
+
+    /// the depth of the shadow stack, to be passed to emit_ir_pop_frame
+    pub fn emit_ir_frame(&self, builder: &'b Builder<'a>, module: &Module<'a>) -> IntValue<'a> {
+        self.call(builder, module, "__gc_frame", &[])
+            .unwrap()
+            .into_int_value()
+    }
+
+    /// drops the roots pushed since the frame was taken
+    pub fn emit_ir_pop_frame(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        depth: IntValue<'a>,
+    ) {
+        let _ = self.call(builder, module, "__gc_pop_frame", &[depth.into()]);
+    }
+
+    /// collects if enough has been allocated since the last collection
+    pub fn emit_ir_safepoint(&self, builder: &'b Builder<'a>, module: &Module<'a>) {
+        let _ = self.call(builder, module, "__gc_safepoint", &[]);
+    }
+
+    pub fn emit_ir_collect(&self, builder: &'b Builder<'a>, module: &Module<'a>) {
+        let _ = self.call(builder, module, "__gc_collect", &[]);
+    }
+
+    /// total number of bytes allocated so far
+    pub fn emit_ir_bytes(&self, builder: &'b Builder<'a>, module: &Module<'a>) -> IntValue<'a> {
+        self.state(module)
+            .load_nth_field(builder, BYTES)
+            .into_int_value()
+    }
+
+    /// number of bytes allocated and not freed yet
+    pub fn emit_ir_live_bytes(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+    ) -> IntValue<'a> {
+        self.state(module)
+            .load_nth_field(builder, LIVE)
+            .into_int_value()
+    }
+
+    pub fn emit_ir_print_stats(&self, builder: &'b Builder<'a>, module: &Module<'a>) {
+        let _ = self.call(builder, module, "__gc_print_stats", &[]);
+    }
+
+    fn roots_type(&self, module: &Module<'a>) -> StructType<'a> {
+        module.get_struct_type("GCRoots")
+    }
+
+    fn header_type(&self, module: &Module<'a>) -> StructType<'a> {
+        module.get_struct_type("GCHeader")
+    }
+
+    fn header(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+    ) -> PointerValue<'a> {
+        let header_ptr_type = self.header_type(module).ptr_type(AddressSpace::default());
+        let ptr = builder
+            .build_pointer_cast(ptr, header_ptr_type, "")
+            .unwrap();
+        let minus_one = module.get_context().i64_type().const_all_ones();
+        unsafe { builder.build_in_bounds_gep(ptr, &[minus_one], "") }.unwrap()
+    }
+
+    fn add_to_field(
+        &self,
+        builder: &'b Builder<'a>,
+        ptr: PointerValue<'a>,
+        n: u32,
+        val: IntValue<'a>,
+    ) {
+        let field_ptr = ptr.get_nth_field(builder, n);
+        let old = builder.build_load(field_ptr, "").unwrap().into_int_value();
+        let new = builder.build_int_add(old, val, "").unwrap();
+        let _ = builder.build_store(field_ptr, new);
+    }
+
+    fn create_alloc_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i8_type = context.i8_type();
+        let i64_type = context.i64_type();
+        let i8_ptr_type = i8_type.ptr_type(AddressSpace::default());
+        let state = self.state(module);
+
+        // create alloc, links a zeroed object into the object list
+        let func = i8_ptr_type.fn_type(&[i64_type.into(), i64_type.into(), i64_type.into()], false);
+        let func = module.add_function("__gc_alloc", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let size = func.get_nth_method_input(0).into_int_value();
+        let header_type = self.header_type(module);
+        let num_bytes = builder
+            .build_int_add(size, header_type.size_of().unwrap(), "")
+            .unwrap();
+        let raw = builder.build_array_malloc(i8_type, num_bytes, "").unwrap();
+        let _ = builder.build_memset(raw, 8, i8_type.const_zero(), num_bytes);
+        let header = builder
+            .build_pointer_cast(raw, header_type.ptr_type(AddressSpace::default()), "")
+            .unwrap();
+        header.set_nth_field(&builder, 0, state.load_nth_field(&builder, OBJECTS).into());
+        state.set_nth_field(&builder, OBJECTS, raw.into());
+        for n in 0..3 {
+            let val = func.get_nth_param(n).unwrap();
+            let _ = builder.build_store(header.get_nth_field(&builder, n + 1), val);
+        }
+        self.add_to_field(&builder, state, ALLOCATED, size);
+        self.add_to_field(&builder, state, ALLOCATIONS, i64_type.const_int(1, false));
+        self.add_to_field(&builder, state, BYTES, size);
+        self.add_to_field(&builder, state, LIVE, size);
+
+        let one = i64_type.const_int(1, false);
+        let ptr = unsafe { builder.build_in_bounds_gep(header, &[one], "") }.unwrap();
+        let ptr = builder.build_pointer_cast(ptr, i8_ptr_type, "").unwrap();
+        let _ = builder.build_return(Some(&ptr));
+
+        // create realloc, the new object traces its words like the old one
+        let func = i8_ptr_type.fn_type(&[i8_ptr_type.into(), i64_type.into()], false);
+        let func = module.add_function("__gc_realloc", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let old = func.get_nth_method_input(0).into_pointer_value();
+        let size = func.get_nth_method_input(1).into_int_value();
+        let header = self.header(&builder, module, old);
+        let old_size = header.load_nth_field(&builder, 1).into_int_value();
+        let count = header.load_nth_field(&builder, 3).into_int_value();
+        let is_tracked = builder
+            .build_int_compare(IntPredicate::NE, count, i64_type.const_zero(), "")
+            .unwrap();
+        let ptr = self.emit_ir_alloc_bytes(&builder, module, size, is_tracked);
+        let is_smaller = builder
+            .build_int_compare(IntPredicate::ULT, size, old_size, "")
+            .unwrap();
+        let num_bytes = builder
+            .build_select(is_smaller, size, old_size, "")
+            .unwrap()
+            .into_int_value();
+        let _ = builder.build_memcpy(ptr, 8, old, 8, num_bytes);
+        let _ = builder.build_return(Some(&ptr));
+    }
+
+    fn create_root_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let roots_ptr_type = self.roots_type(module).ptr_type(AddressSpace::default());
+        let state = self.state(module);
+
+        // create append, grows the list by doubling its capacity
+        let func = context
+            .void_type()
+            .fn_type(&[roots_ptr_type.into(), i8_ptr_type.into()], false);
+        let append = module.add_function("__gc_append", func, None);
+        let entry = context.append_basic_block(append, "entry");
+        let grow = context.append_basic_block(append, "grow");
+        let done = context.append_basic_block(append, "done");
+        builder.position_at_end(entry);
+
+        let roots = append.get_nth_method_input(0).into_pointer_value();
+        let length = roots.load_nth_field(&builder, 1).into_int_value();
+        let capacity = roots.load_nth_field(&builder, 2).into_int_value();
+        let is_full = builder
+            .build_int_compare(IntPredicate::EQ, length, capacity, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_full, grow, done);
+
+        builder.position_at_end(grow);
+        let doubled = builder
+            .build_int_mul(capacity, i64_type.const_int(2, false), "")
+            .unwrap();
+        let is_small = builder
+            .build_int_compare(
+                IntPredicate::SLT,
+                doubled,
+                i64_type.const_int(64, false),
+                "",
+            )
+            .unwrap();
+        let new_capacity = builder
+            .build_select(is_small, i64_type.const_int(64, false), doubled, "")
+            .unwrap()
+            .into_int_value();
+        let num_bytes = builder
+            .build_int_mul(new_capacity, i64_type.const_int(8, false), "")
+            .unwrap();
+        let data = builder
+            .build_pointer_cast(
+                roots.load_nth_field(&builder, 0).into_pointer_value(),
+                i8_ptr_type,
+                "",
+            )
+            .unwrap();
+        let data = self
+            .call(
+                &builder,
+                module,
+                "realloc",
+                &[data.into(), num_bytes.into()],
+            )
+            .unwrap()
+            .into_pointer_value();
+        let data = builder
+            .build_pointer_cast(data, i8_ptr_type.ptr_type(AddressSpace::default()), "")
+            .unwrap();
+        roots.set_nth_field(&builder, 0, data.into());
+        let _ = builder.build_store(roots.get_nth_field(&builder, 2), new_capacity);
+        let _ = builder.build_unconditional_branch(done);
+
+        builder.position_at_end(done);
+        let data = roots.load_nth_field(&builder, 0).into_pointer_value();
+        let slot = unsafe { builder.build_in_bounds_gep(data, &[length], "") }.unwrap();
+        let _ = builder.build_store(slot, append.get_nth_param(1).unwrap());
+        let new_length = builder
+            .build_int_add(length, i64_type.const_int(1, false), "")
+            .unwrap();
+        let _ = builder.build_store(roots.get_nth_field(&builder, 1), new_length);
+        let _ = builder.build_return(None);
+
+        // create push root and add global
+        for (name, n, arg_type) in [
+            ("__gc_push_root", ROOTS, i8_ptr_type),
+            (
+                "__gc_add_global",
+                GLOBALS,
+                i8_ptr_type.ptr_type(AddressSpace::default()),
+            ),
+        ] {
+            let func = context.void_type().fn_type(&[arg_type.into()], false);
+            let func = module.add_function(name, func, None);
+            let entry = context.append_basic_block(func, "entry");
+            builder.position_at_end(entry);
+
+            let ptr = func.get_nth_method_input(0).into_pointer_value();
+            let ptr = builder.build_pointer_cast(ptr, i8_ptr_type, "").unwrap();
+            let _ = builder.build_call(
+                append,
+                &[state.get_nth_field(&builder, n).into(), ptr.into()],
+                "",
+            );
+            let _ = builder.build_return(None);
+        }
+
+        // create frame and pop frame
+        let roots = unsafe {
+            state.const_in_bounds_gep(&[
+                context.i32_type().const_zero(),
+                context.i32_type().const_int(ROOTS as u64, false),
+                context.i32_type().const_int(1, false),
+            ])
+        };
+        let func = i64_type.fn_type(&[], false);
+        let func = module.add_function("__gc_frame", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+        let depth = builder.build_load(roots, "").unwrap();
+        let _ = builder.build_return(Some(&depth));
+
+        let func = context.void_type().fn_type(&[i64_type.into()], false);
+        let func = module.add_function("__gc_pop_frame", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+        let _ = builder.build_store(roots, func.get_nth_param(0).unwrap());
+        let _ = builder.build_return(None);
+    }
+
+    fn create_collect_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let state = self.state(module);
+
+        // create mark, follows the pointer words of unmarked objects
+        let func = context.void_type().fn_type(&[i8_ptr_type.into()], false);
+        let mark = module.add_function("__gc_mark", func, None);
+        let entry = context.append_basic_block(mark, "entry");
+        let check = context.append_basic_block(mark, "check");
+        let trace = context.append_basic_block(mark, "trace");
+        let body = context.append_basic_block(mark, "body");
+        let next = context.append_basic_block(mark, "next");
+        let done = context.append_basic_block(mark, "done");
+        builder.position_at_end(entry);
+
+        let ptr = mark.get_nth_method_input(0).into_pointer_value();
+        let is_null = builder.build_is_null(ptr, "").unwrap();
+        let _ = builder.build_conditional_branch(is_null, done, check);
+
+        builder.position_at_end(check);
+        let header = self.header(&builder, module, ptr);
+        let marked = header.load_nth_field(&builder, 4).into_int_value();
+        let is_marked = builder
+            .build_int_compare(IntPredicate::NE, marked, i64_type.const_zero(), "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_marked, done, trace);
+
+        builder.position_at_end(trace);
+        let _ = builder.build_store(
+            header.get_nth_field(&builder, 4),
+            i64_type.const_int(1, false),
+        );
+        let first = header.load_nth_field(&builder, 2).into_int_value();
+        let count = header.load_nth_field(&builder, 3).into_int_value();
+        let end = builder.build_int_add(first, count, "").unwrap();
+        let words = builder
+            .build_pointer_cast(ptr, i8_ptr_type.ptr_type(AddressSpace::default()), "")
+            .unwrap();
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(body);
+        let index = builder.build_phi(i64_type, "").unwrap();
+        index.add_incoming(&[(&first, trace)]);
+        let index_val = index.as_basic_value().into_int_value();
+        let in_range = builder
+            .build_int_compare(IntPredicate::ULT, index_val, end, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(in_range, next, done);
+
+        builder.position_at_end(next);
+        let word = unsafe { builder.build_in_bounds_gep(words, &[index_val], "") }.unwrap();
+        let word = builder.build_load(word, "").unwrap();
+        let _ = builder.build_call(mark, &[word.into()], "");
+        let next_index = builder
+            .build_int_add(index_val, i64_type.const_int(1, false), "")
+            .unwrap();
+        index.add_incoming(&[(&next_index, next)]);
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(done);
+        let _ = builder.build_return(None);
+
+        // create collect, marks from the roots then frees the unmarked
+        let func = context.void_type().fn_type(&[], false);
+        let collect = module.add_function("__gc_collect", func, None);
+        let entry = context.append_basic_block(collect, "entry");
+        builder.position_at_end(entry);
+        for (n, is_global) in [(ROOTS, false), (GLOBALS, true)] {
+            let roots = state.get_nth_field(&builder, n);
+            let length = roots.load_nth_field(&builder, 1).into_int_value();
+            let data = roots.load_nth_field(&builder, 0).into_pointer_value();
+            let start = builder.get_insert_block().unwrap();
+            let body = context.append_basic_block(collect, "roots");
+            let next = context.append_basic_block(collect, "");
+            let done = context.append_basic_block(collect, "");
+            let _ = builder.build_unconditional_branch(body);
+
+            builder.position_at_end(body);
+            let index = builder.build_phi(i64_type, "").unwrap();
+            index.add_incoming(&[(&i64_type.const_zero(), start)]);
+            let index_val = index.as_basic_value().into_int_value();
+            let in_range = builder
+                .build_int_compare(IntPredicate::ULT, index_val, length, "")
+                .unwrap();
+            let _ = builder.build_conditional_branch(in_range, next, done);
+
+            builder.position_at_end(next);
+            let root = unsafe { builder.build_in_bounds_gep(data, &[index_val], "") }.unwrap();
+            let mut root = builder.build_load(root, "").unwrap().into_pointer_value();
+            if is_global {
+                // globals are stored by address
+                let address = builder
+                    .build_pointer_cast(root, i8_ptr_type.ptr_type(AddressSpace::default()), "")
+                    .unwrap();
+                root = builder
+                    .build_load(address, "")
+                    .unwrap()
+                    .into_pointer_value();
+            }
+            let _ = builder.build_call(mark, &[root.into()], "");
+            let next_index = builder
+                .build_int_add(index_val, i64_type.const_int(1, false), "")
+                .unwrap();
+            index.add_incoming(&[(&next_index, next)]);
+            let _ = builder.build_unconditional_branch(body);
+            builder.position_at_end(done);
+        }
+
+        // sweep, link is the pointer to the current object
+        let start = builder.get_insert_block().unwrap();
+        let body = context.append_basic_block(collect, "sweep");
+        let check = context.append_basic_block(collect, "");
+        let keep = context.append_basic_block(collect, "keep");
+        let free = context.append_basic_block(collect, "free");
+        let done = context.append_basic_block(collect, "done");
+        let objects = state.get_nth_field(&builder, OBJECTS);
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(body);
+        let link = builder
+            .build_phi(i8_ptr_type.ptr_type(AddressSpace::default()), "")
+            .unwrap();
+        link.add_incoming(&[(&objects, start)]);
+        let link_val = link.as_basic_value().into_pointer_value();
+        let current = builder
+            .build_load(link_val, "")
+            .unwrap()
+            .into_pointer_value();
+        let is_null = builder.build_is_null(current, "").unwrap();
+        let _ = builder.build_conditional_branch(is_null, done, check);
+
+        builder.position_at_end(check);
+        let header = builder
+            .build_pointer_cast(
+                current,
+                self.header_type(module).ptr_type(AddressSpace::default()),
+                "",
+            )
+            .unwrap();
+        let marked = header.load_nth_field(&builder, 4).into_int_value();
+        let is_marked = builder
+            .build_int_compare(IntPredicate::NE, marked, i64_type.const_zero(), "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_marked, keep, free);
+
+        builder.position_at_end(keep);
+        let _ = builder.build_store(header.get_nth_field(&builder, 4), i64_type.const_zero());
+        link.add_incoming(&[(&header.get_nth_field(&builder, 0), keep)]);
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(free);
+        let next = header.load_nth_field(&builder, 0);
+        let _ = builder.build_store(link_val, next);
+        let size = header.load_nth_field(&builder, 1).into_int_value();
+        self.add_to_field(&builder, state, FREED, size);
+        let minus_size = builder.build_int_neg(size, "").unwrap();
+        self.add_to_field(&builder, state, LIVE, minus_size);
+        let _ = builder.build_free(current);
+        link.add_incoming(&[(&link_val, free)]);
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(done);
+        self.add_to_field(&builder, state, COLLECTIONS, i64_type.const_int(1, false));
+        let _ = builder.build_store(
+            state.get_nth_field(&builder, ALLOCATED),
+            i64_type.const_zero(),
+        );
+        let live = state.load_nth_field(&builder, LIVE).into_int_value();
+        let doubled = builder
+            .build_int_mul(live, i64_type.const_int(2, false), "")
+            .unwrap();
+        let threshold = i64_type.const_int(THRESHOLD, false);
+        let is_small = builder
+            .build_int_compare(IntPredicate::ULT, doubled, threshold, "")
+            .unwrap();
+        let next_collection = builder
+            .build_select(is_small, threshold, doubled, "")
+            .unwrap();
+        let _ = builder.build_store(
+            state.get_nth_field(&builder, NEXT_COLLECTION),
+            next_collection,
+        );
+        let _ = builder.build_return(None);
+
+        // create safepoint
+        let func = context.void_type().fn_type(&[], false);
+        let func = module.add_function("__gc_safepoint", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let run = context.append_basic_block(func, "collect");
+        let done = context.append_basic_block(func, "done");
+        builder.position_at_end(entry);
+        let allocated = state.load_nth_field(&builder, ALLOCATED).into_int_value();
+        let next_collection = state
+            .load_nth_field(&builder, NEXT_COLLECTION)
+            .into_int_value();
+        let is_due = builder
+            .build_int_compare(IntPredicate::UGE, allocated, next_collection, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_due, run, done);
+        builder.position_at_end(run);
+        let _ = builder.build_call(collect, &[], "");
+        let _ = builder.build_unconditional_branch(done);
+        builder.position_at_end(done);
+        let _ = builder.build_return(None);
+
+        // create print stats
+        let func = context.void_type().fn_type(&[], false);
+        let func = module.add_function("__gc_print_stats", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+        let format_string = builder
+            .build_global_string_ptr(
+                "allocations: %lld (%lld bytes), collections: %lld, freed: %lld bytes, live: %lld bytes\n",
+                "",
+            )
+            .unwrap()
+            .as_pointer_value();
+        let mut args: Vec<BasicMetadataValueEnum<'a>> = vec![format_string.into()];
+        for n in [ALLOCATIONS, BYTES, COLLECTIONS, FREED, LIVE] {
+            args.push(state.load_nth_field(&builder, n).into());
+        }
+        let _ = builder.build_call(module.get_function("printf"), &args, "");
+        let _ = builder.build_return(None);
+    }
+}
+
+impl Default for FarneseGC {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FarneseInternal<'a> for FarneseGC {
+    fn bootstrap(&self, module: &Module<'a>) {
+        self.create_opaque_type(module);
+        self.create_new_method(module);
+        self.create_get_methods(module);
+        self.create_root_methods(module);
+        self.create_collect_methods(module);
+    }
+
+    fn create_opaque_type(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+
+        let header_type = context.opaque_struct_type("GCHeader");
+        header_type.set_body(
+            &[
+                i8_ptr_type.into(),
+                i64_type.into(),
+                i64_type.into(),
+                i64_type.into(),
+                i64_type.into(),
+            ],
+            false,
+        );
+
+        // a growable list of roots
+        let roots_type = context.opaque_struct_type("GCRoots");
+        roots_type.set_body(
+            &[
+                i8_ptr_type.ptr_type(AddressSpace::default()).into(),
+                i64_type.into(),
+                i64_type.into(),
+            ],
+            false,
+        );
+
+        //   %GCState = type { i8* objects, %GCRoots roots, %GCRoots globals,
+        //                     i64 next_collection, i64 allocated, i64 allocations,
+        //                     i64 bytes, i64 freed, i64 collections, i64 live }
+        let state_type = context.opaque_struct_type("GCState");
+        let mut fields: Vec<BasicTypeEnum<'a>> =
+            vec![i8_ptr_type.into(), roots_type.into(), roots_type.into()];
+        fields.extend([BasicTypeEnum::from(i64_type); 7]);
+        state_type.set_body(&fields, false);
+
+        let state = module.add_global(state_type, None, "__gc");
+        let mut initial: Vec<BasicValueEnum<'a>> = vec![
+            i8_ptr_type.const_null().into(),
+            roots_type.const_zero().into(),
+            roots_type.const_zero().into(),
+            i64_type.const_int(THRESHOLD, false).into(),
+        ];
+        initial.extend([BasicValueEnum::from(i64_type.const_zero()); 6]);
+        state.set_initializer(&state_type.const_named_struct(&initial));
+        state.set_linkage(Linkage::WeakODR);
+    }
+
+    fn create_new_method(&self, module: &Module<'a>) {
+        self.create_alloc_methods(module);
+    }
+
+    fn create_get_methods(&self, _module: &Module<'a>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestHelper;
+    use crate::{FarneseString, LLVMPrintf};
+    use inkwell::context::Context;
+
+    #[test]
+    fn test_gc_collect() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_gc_collect", &builder, &context);
+        tester.start();
+
+        let gc = FarneseGC::new();
+        let string = FarneseString::new();
+        let hello = string.emit_ir_literal(&tester.module, "hello ");
+        let world = string.emit_ir_literal(&tester.module, "world");
+        let kept = string.emit_ir_concat(&builder, &tester.module, hello, world);
+        gc.emit_ir_push_root(&builder, &tester.module, kept);
+        let _ = string.emit_ir_concat(&builder, &tester.module, world, hello);
+        gc.emit_ir_collect(&builder, &tester.module);
+
+        // only the rooted string and its 12 bytes survive
+        let string_type = tester.module.get_type("String").clone();
+        let _ = (kept.into(), string_type).emit_ir_printf(&builder, &tester.module);
+        let int = tester.module.get_type("Int64").clone();
+        let live = gc.emit_ir_live_bytes(&builder, &tester.module);
+        let _ = (live.into(), int.clone()).emit_ir_printf(&builder, &tester.module);
+        let bytes = gc.emit_ir_bytes(&builder, &tester.module);
+        let _ = (bytes.into(), int).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+
+        assert_eq!("hello world2856", tester.run());
+    }
+}
@@ -0,0 +1,129 @@
+use super::{FarneseInternal, MethodHelper, Module};
+use inkwell::builder::Builder;
+use inkwell::values::{IntValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// helpers for integers that printf can't handle on its own,
+/// currently just decimal formatting of Int128
+#[derive(Clone, Debug)]
+pub struct FarneseInteger;
+
+impl<'a> FarneseInteger {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// formats an Int128 into a stack buffer, returns a pointer
+    /// to the first digit (or sign) of the NUL terminated result
+    pub fn emit_ir_int128_chars(
+        &self,
+        builder: &Builder<'a>,
+        module: &Module<'a>,
+        val: IntValue<'a>,
+    ) -> PointerValue<'a> {
+        let context = module.get_context();
+        // 39 digits, a sign and a NUL
+        let buffer = builder
+            .build_array_alloca(
+                context.i8_type(),
+                context.i64_type().const_int(41, false),
+                "",
+            )
+            .unwrap();
+        builder
+            .build_call(
+                module.get_function("__int128_chars"),
+                &[val.into(), buffer.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+}
+
+impl Default for FarneseInteger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FarneseInternal<'a> for FarneseInteger {
+    fn create_opaque_type(&self, _module: &Module<'a>) {}
+
+    fn create_new_method(&self, _module: &Module<'a>) {}
+
+    /// __int128_chars(x, buffer) writes the digits of x backwards
+    /// from the end of the buffer
+    fn create_get_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i8_type = context.i8_type();
+        let i64_type = context.i64_type();
+        let i128_type = context.i128_type();
+        let i8_ptr_type = i8_type.ptr_type(AddressSpace::default());
+
+        let func = i8_ptr_type.fn_type(&[i128_type.into(), i8_ptr_type.into()], false);
+        let func = module.add_function("__int128_chars", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let body = context.append_basic_block(func, "body");
+        let sign = context.append_basic_block(func, "sign");
+        let done = context.append_basic_block(func, "done");
+        builder.position_at_end(entry);
+
+        let x = func.get_nth_method_input(0).into_int_value();
+        let buffer = func.get_nth_method_input(1).into_pointer_value();
+        let end =
+            unsafe { builder.build_in_bounds_gep(buffer, &[i64_type.const_int(40, false)], "") }
+                .unwrap();
+        let _ = builder.build_store(end, i8_type.const_zero());
+        let is_negative = builder
+            .build_int_compare(IntPredicate::SLT, x, i128_type.const_zero(), "")
+            .unwrap();
+        // negating as unsigned also works for typemin(Int128)
+        let negated = builder.build_int_neg(x, "").unwrap();
+        let magnitude = builder
+            .build_select(is_negative, negated, x, "")
+            .unwrap()
+            .into_int_value();
+        let _ = builder.build_unconditional_branch(body);
+
+        builder.position_at_end(body);
+        let ptr = builder.build_phi(i8_ptr_type, "").unwrap();
+        let rest = builder.build_phi(i128_type, "").unwrap();
+        let ptr_val = ptr.as_basic_value().into_pointer_value();
+        let rest_val = rest.as_basic_value().into_int_value();
+        let ten = i128_type.const_int(10, false);
+        let digit = builder.build_int_unsigned_rem(rest_val, ten, "").unwrap();
+        let digit = builder.build_int_truncate(digit, i8_type, "").unwrap();
+        let digit = builder
+            .build_int_add(digit, i8_type.const_int(b'0'.into(), false), "")
+            .unwrap();
+        let next_ptr =
+            unsafe { builder.build_in_bounds_gep(ptr_val, &[i64_type.const_all_ones()], "") }
+                .unwrap();
+        let _ = builder.build_store(next_ptr, digit);
+        let next_rest = builder.build_int_unsigned_div(rest_val, ten, "").unwrap();
+        let is_done = builder
+            .build_int_compare(IntPredicate::EQ, next_rest, i128_type.const_zero(), "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_done, sign, body);
+        ptr.add_incoming(&[(&end, entry), (&next_ptr, body)]);
+        rest.add_incoming(&[(&magnitude, entry), (&next_rest, body)]);
+
+        builder.position_at_end(sign);
+        let minus_ptr =
+            unsafe { builder.build_in_bounds_gep(next_ptr, &[i64_type.const_all_ones()], "") }
+                .unwrap();
+        let _ = builder.build_store(minus_ptr, i8_type.const_int(b'-'.into(), false));
+        let start = builder
+            .build_select(is_negative, minus_ptr, next_ptr, "")
+            .unwrap();
+        let _ = builder.build_unconditional_branch(done);
+
+        builder.position_at_end(done);
+        let _ = builder.build_return(Some(&start));
+    }
+}
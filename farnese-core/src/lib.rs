@@ -1,18 +1,41 @@
+pub mod array;
+pub mod boxed;
+pub mod cache;
+pub mod char;
 pub mod datatype;
+pub mod exception;
+pub mod expr;
+pub mod format;
+pub mod gc;
+pub mod integer;
 pub mod module;
 pub mod primitive;
+pub mod show;
+pub mod string;
 pub mod symbol;
 
 #[cfg(test)]
 pub mod test_utils;
 
+pub use array::Array;
+pub use boxed::FarneseBox;
+pub use cache::{ModuleCache, SourceHasher};
+pub use char::FarneseChar;
 pub use datatype::DataType;
-pub use module::Module;
+pub use exception::FarneseException;
+pub use expr::FarneseExpr;
+pub use format::FarneseFormat;
+pub use gc::FarneseGC;
+pub use integer::FarneseInteger;
+pub use module::{Module, ModuleError};
 pub use primitive::Primitive;
+pub use show::FarneseShow;
+pub use string::FarneseString;
 pub use symbol::Symbol;
 
 use inkwell::{
     self, AddressSpace,
+    attributes::{Attribute, AttributeLoc},
     builder::Builder,
     context::Context,
     module::Linkage,
@@ -91,13 +114,26 @@ pub trait LLVMPrintf<'a, 'b> {
 // move to a value file
 impl<'a, 'b> LLVMPrintf<'a, 'b> for (BasicMetadataValueEnum<'a>, DataType) {
     fn emit_ir_printf(&self, builder: &'b Builder<'a>, module: &Module<'a>) -> CallSiteValue<'a> {
+        if self.1.is_abstract {
+            return builder
+                .build_call(module.get_function("__print_box"), &[self.0], "")
+                .unwrap();
+        }
         let context = module.get_context();
         let datatype = self.1.name().name();
         let format_string = match datatype {
             "Float32" | "Float64" => context.const_string(b"%.8f\0", false),
-            "Int32" | "Int64" => context.const_string(b"%lld\0", false),
-            "String" => context.const_string(b"%s\0", false),
-            "Symbol" => context.const_string(b"%s\0", false),
+            "Int8" | "Int16" | "Int32" | "Int64" => context.const_string(b"%lld\0", false),
+            // unsigned integers show as zero padded hex like Julia
+            "UInt8" => context.const_string(b"0x%02llx\0", false),
+            "UInt16" => context.const_string(b"0x%04llx\0", false),
+            "UInt32" => context.const_string(b"0x%08llx\0", false),
+            "UInt64" => context.const_string(b"0x%016llx\0", false),
+            "UInt128" => context.const_string(b"0x%016llx%016llx\0", false),
+            "Bool" | "Char" | "Int128" | "Nothing" | "String" => {
+                context.const_string(b"%s\0", false)
+            }
+            "DataType" | "Symbol" => context.const_string(b"%s\0", false),
             _ => todo!(),
         };
         let ptr = builder.build_alloca(format_string.get_type(), "").unwrap();
@@ -114,15 +150,72 @@ impl<'a, 'b> LLVMPrintf<'a, 'b> for (BasicMetadataValueEnum<'a>, DataType) {
                 )
                 .unwrap()
         };
-        
-        let func_args = if datatype == "Symbol" {
+
+        let func_args = if datatype == "Bool" {
+            let true_str = builder.build_global_string_ptr("true", "").unwrap();
+            let false_str = builder.build_global_string_ptr("false", "").unwrap();
+            let val = builder
+                .build_select(
+                    self.0.into_int_value(),
+                    true_str.as_pointer_value(),
+                    false_str.as_pointer_value(),
+                    "",
+                )
+                .unwrap();
+            vec![gep_ptr.into(), val.into()]
+        } else if datatype == "Nothing" {
+            let val = builder.build_global_string_ptr("nothing", "").unwrap();
+            vec![gep_ptr.into(), val.as_pointer_value().into()]
+        } else if datatype == "Char" {
+            let (buffer, _) =
+                FarneseChar::new().emit_ir_utf8(builder, module, self.0.into_int_value());
+            vec![gep_ptr.into(), buffer.into()]
+        } else if datatype == "DataType" {
+            let sym_ptr = self.0.load_nth_field(builder, 0).into_pointer_value();
+            let sym_str = sym_ptr.load_nth_field(builder, 1).into_pointer_value();
+            vec![gep_ptr.into(), sym_str.into()]
+        } else if datatype == "Symbol" || datatype == "String" {
             // panic!("hur")
-            let sym_str = self.0.get_nth_field(builder, 1);
+            let field = if datatype == "String" { 0 } else { 1 };
+            let sym_str = self.0.get_nth_field(builder, field);
             let sym_str = builder
                 .build_load(sym_str, "")
                 .unwrap()
                 .into_pointer_value();
             vec![gep_ptr.into(), sym_str.into()]
+        } else if datatype == "Int128" {
+            let chars = FarneseInteger::new().emit_ir_int128_chars(
+                builder,
+                module,
+                self.0.into_int_value(),
+            );
+            vec![gep_ptr.into(), chars.into()]
+        } else if datatype == "UInt128" {
+            let val = self.0.into_int_value();
+            let i64_type = context.i64_type();
+            let hi = builder
+                .build_right_shift(val, val.get_type().const_int(64, false), false, "")
+                .unwrap();
+            let hi = builder.build_int_truncate(hi, i64_type, "").unwrap();
+            let lo = builder.build_int_truncate(val, i64_type, "").unwrap();
+            vec![gep_ptr.into(), hi.into(), lo.into()]
+        } else if self.1.supertype.name() == "Signed" || self.1.supertype.name() == "Unsigned" {
+            // varargs need the full 64 bits for %lld
+            let val = builder
+                .build_int_cast_sign_flag(
+                    self.0.into_int_value(),
+                    context.i64_type(),
+                    self.1.supertype.name() == "Signed",
+                    "",
+                )
+                .unwrap();
+            vec![gep_ptr.into(), val.into()]
+        } else if datatype == "Float32" {
+            // varargs promote float to double
+            let val = builder
+                .build_float_ext(self.0.into_float_value(), context.f64_type(), "")
+                .unwrap();
+            vec![gep_ptr.into(), val.into()]
         } else {
             vec![gep_ptr.into(), self.0.clone()]
         };
@@ -154,13 +247,12 @@ impl<'a> Core<'a> {
 
     pub fn bootstrap(&mut self) -> Module<'a> {
         let _ = self.basic_c_funcs();
+        FarneseGC::new().bootstrap(&self.module);
 
         // symbol type
         let symbol_sym = Symbol::new("Symbol");
 
         symbol_sym.create_opaque_type(&self.module);
-        let sym_type = self.module.get_struct_type("Symbol");
-        let _ = self.module.add_global(sym_type, None, "Symbol");
 
         // self.module.insert_type(sym_type.clone());
         let datatype = DataType::new(
@@ -173,7 +265,7 @@ impl<'a> Core<'a> {
             Box::new(vec![]),
         );
         self.module.insert_type(datatype);
-        // self.module.push_export(symbol_sym);
+        self.module.push_export(symbol_sym);
 
         // data types
         let sym = Symbol::new("DataType");
@@ -181,7 +273,7 @@ impl<'a> Core<'a> {
         let field_types = Box::new(Vec::<DataType>::new());
         let datatype = DataType::new(
             sym.clone(),
-            sym.clone(),
+            Symbol::new("Any"),
             false,
             false,
             false,
@@ -189,9 +281,8 @@ impl<'a> Core<'a> {
             field_types,
         );
         let _ = self.module.insert_type(datatype.clone());
-        // let _ = self.module.push_export(sym);
-        datatype.bootstrap(&self.module);
-        // self.module.push_export(sym);
+        datatype.create_opaque_type(&self.module);
+        self.module.push_export(sym);
 
         // any type
         let sym = Symbol::new("Any");
@@ -200,16 +291,179 @@ impl<'a> Core<'a> {
         let datatype = DataType::new(
             sym.clone(),
             sym.clone(),
-            false,
+            true,
             false,
             false,
             field_names,
             field_types,
         );
         let _ = self.module.insert_type(datatype.clone());
-        // let _ = self.module.push_export(sym);
-        let _ = datatype.emit_ir_type(&self.module);
         self.module.push_export(sym);
+
+        // builtin primitive types
+        for (name, supertype, bits) in [
+            ("Float32", "AbstractFloat", 32),
+            ("Float64", "AbstractFloat", 64),
+            ("Int8", "Signed", 8),
+            ("Int16", "Signed", 16),
+            ("Int32", "Signed", 32),
+            ("Int64", "Signed", 64),
+            ("Int128", "Signed", 128),
+            ("UInt8", "Unsigned", 8),
+            ("UInt16", "Unsigned", 16),
+            ("UInt32", "Unsigned", 32),
+            ("UInt64", "Unsigned", 64),
+            ("UInt128", "Unsigned", 128),
+            ("Bool", "Integer", 8),
+        ] {
+            let datatype = DataType::new_primitive_type(name, supertype, bits);
+            self.module.insert_type(datatype);
+            self.module.push_export(Symbol::new(name));
+        }
+
+        FarneseInteger::new().bootstrap(&self.module);
+
+        // nothing, a singleton with no fields
+        let nothing_type = self.module.get_context().opaque_struct_type("Nothing");
+        nothing_type.set_body(&[], false);
+        self.module.insert_type(Primitive::Nothing.get_datatype());
+        self.module.push_export(Symbol::new("Nothing"));
+
+        // arrays
+        self.module
+            .insert_type(DataType::new_abstract_type("AbstractArray", "Any"));
+        self.module
+            .insert_type(DataType::new_abstract_type("DenseArray", "AbstractArray"));
+        let datatype = DataType::new(
+            Symbol::new("Array"),
+            Symbol::new("DenseArray"),
+            false,
+            true,
+            false,
+            vec![],
+            Box::default(),
+        );
+        self.module.insert_type(datatype.clone());
+        Array::new(datatype).bootstrap(&self.module);
+        self.module
+            .get_type("DataType")
+            .create_get_methods(&self.module);
+        FarneseBox::new().bootstrap(&self.module);
+        for name in ["AbstractArray", "DenseArray", "Array"] {
+            self.module.push_export(Symbol::new(name));
+        }
+
+        // strings
+        self.module
+            .insert_type(DataType::new_abstract_type("AbstractString", "Any"));
+        let datatype = DataType::new(
+            Symbol::new("String"),
+            Symbol::new("AbstractString"),
+            false,
+            false,
+            false,
+            vec![],
+            Box::default(),
+        );
+        self.module.insert_type(datatype);
+        FarneseString::new().bootstrap(&self.module);
+        for name in ["AbstractString", "String"] {
+            self.module.push_export(Symbol::new(name));
+        }
+
+        // chars
+        self.module
+            .insert_type(DataType::new_abstract_type("AbstractChar", "Any"));
+        self.module
+            .insert_type(DataType::new_primitive_type("Char", "AbstractChar", 32));
+        FarneseChar::new().bootstrap(&self.module);
+        for name in ["AbstractChar", "Char"] {
+            self.module.push_export(Symbol::new(name));
+        }
+
+        // exceptions
+        self.module
+            .insert_type(DataType::new_abstract_type("Exception", "Any"));
+        for (name, field_names, field_types) in [
+            ("ErrorException", vec!["msg"], vec!["String"]),
+            ("AssertionError", vec!["msg"], vec!["String"]),
+            ("BoundsError", vec!["a", "i"], vec!["Any", "Int64"]),
+            ("DivideError", vec![], vec![]),
+            ("DimensionMismatch", vec!["msg"], vec!["String"]),
+            (
+                "MethodError",
+                vec!["f", "args"],
+                vec!["Symbol", "Vector{Any}"],
+            ),
+        ] {
+            let field_types = field_types
+                .iter()
+                .map(|x| self.module.resolve_type(x))
+                .collect();
+            let datatype = DataType::new(
+                Symbol::new(name),
+                Symbol::new("Exception"),
+                false,
+                false,
+                false,
+                field_names.into_iter().map(Symbol::new).collect(),
+                Box::new(field_types),
+            );
+            self.module.insert_type(datatype);
+        }
+        for name in [
+            "Exception",
+            "ErrorException",
+            "AssertionError",
+            "BoundsError",
+            "DivideError",
+            "DimensionMismatch",
+            "MethodError",
+        ] {
+            self.module.push_export(Symbol::new(name));
+        }
+
+        // runtime type objects
+        let mut datatypes = self.module.get_types().values().collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+        for datatype in datatypes {
+            datatype.emit_ir_type(&self.module);
+        }
+
+        FarneseBox::new().create_print_method(&self.module);
+        FarneseException::new().bootstrap(&self.module);
+        Array::create_checkbounds_method(&self.module);
+
+        // print and show
+        let show = FarneseShow::new();
+        show.bootstrap(&self.module);
+        for (name, arg_types, return_type) in show.methods(&self.module) {
+            self.module.insert_method(name, arg_types, return_type);
+        }
+        for name in ["print", "show"] {
+            self.module.push_export(Symbol::new(name));
+        }
+
+        // quoted code
+        let expr = FarneseExpr::new();
+        self.module.insert_type(expr.datatype(&self.module));
+        expr.bootstrap(&self.module);
+        for (name, arg_types, return_type) in expr.methods(&self.module) {
+            self.module.insert_method(name, arg_types, return_type);
+        }
+        self.module.push_export(Symbol::new("Expr"));
+
+        // every module links in Core, so its definitions have to be
+        // mergeable when those modules are linked together
+        for func in self.module.get_functions() {
+            if func.count_basic_blocks() > 0 {
+                func.set_linkage(Linkage::WeakODR);
+            }
+        }
+        for datatype in self.module.get_types().values() {
+            let global = self.module.get_global(&datatype.ir_name());
+            global.set_linkage(Linkage::WeakODR);
+        }
         self.module.clone()
     }
 
@@ -228,5 +482,75 @@ impl<'a> Core<'a> {
         let _ = self
             .module
             .add_function("printf", printf_type, Some(Linkage::External));
+
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let realloc_type =
+            i8_ptr_type.fn_type(&[i8_ptr_type.into(), context.i64_type().into()], false);
+        let _ = self
+            .module
+            .add_function("realloc", realloc_type, Some(Linkage::External));
+
+        let i32_type = context.i32_type();
+        let i64_type = context.i64_type();
+        let snprintf_type = i32_type.fn_type(
+            &[i8_ptr_type.into(), i64_type.into(), i8_ptr_type.into()],
+            true,
+        );
+        let _ = self
+            .module
+            .add_function("snprintf", snprintf_type, Some(Linkage::External));
+
+        let memcmp_type = i32_type.fn_type(
+            &[i8_ptr_type.into(), i8_ptr_type.into(), i64_type.into()],
+            false,
+        );
+        let _ = self
+            .module
+            .add_function("memcmp", memcmp_type, Some(Linkage::External));
+
+        let strtod_type = context.f64_type().fn_type(
+            &[
+                i8_ptr_type.into(),
+                i8_ptr_type.ptr_type(AddressSpace::default()).into(),
+            ],
+            false,
+        );
+        let _ = self
+            .module
+            .add_function("strtod", strtod_type, Some(Linkage::External));
+
+        let strpbrk_type = i8_ptr_type.fn_type(&[i8_ptr_type.into(), i8_ptr_type.into()], false);
+        let _ = self
+            .module
+            .add_function("strpbrk", strpbrk_type, Some(Linkage::External));
+
+        let strchr_type = i8_ptr_type.fn_type(&[i8_ptr_type.into(), i32_type.into()], false);
+        let _ = self
+            .module
+            .add_function("strchr", strchr_type, Some(Linkage::External));
+
+        let atoi_type = i32_type.fn_type(&[i8_ptr_type.into()], false);
+        let _ = self
+            .module
+            .add_function("atoi", atoi_type, Some(Linkage::External));
+
+        let longjmp_type = context
+            .void_type()
+            .fn_type(&[i8_ptr_type.into(), i32_type.into()], false);
+        let longjmp = self
+            .module
+            .add_function("longjmp", longjmp_type, Some(Linkage::External));
+        let kind_id = Attribute::get_named_enum_kind_id("noreturn");
+        longjmp.add_attribute(
+            AttributeLoc::Function,
+            context.create_enum_attribute(kind_id, 0),
+        );
+
+        let exit_type = context
+            .void_type()
+            .fn_type(&[context.i32_type().into()], false);
+        let _ = self
+            .module
+            .add_function("exit", exit_type, Some(Linkage::External));
     }
 }
@@ -5,10 +5,15 @@ use inkwell::{
     context::Context,
     execution_engine::ExecutionEngine,
     module::{self, FunctionIterator, Linkage},
-    types::{FunctionType, IntType, PointerType, StructType},
+    types::{
+        BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType, IntType, PointerType,
+        StructType,
+    },
     values::{FunctionValue, GlobalValue},
 };
 use std::collections::HashMap;
+use std::fmt::{self, Formatter};
+use std::path::Path;
 
 // type Methods<'a> = HashMap<(Symbol, FunctionType<'a>), FunctionValue<'a>>;
 // type Methods<'a> = HashMap<Symbol, FunctionValue<'a>>;
@@ -18,12 +23,49 @@ type ReturnType = DataType;
 type MethodTable = HashMap<Symbol, (ArgTypes, ReturnType)>;
 // type Methods = HashMap<Symbol, Vec<
 type Types = HashMap<Symbol, DataType>;
+/// name of the LLVM global, type and whether it is const
+type Globals = HashMap<Symbol, (Symbol, DataType, bool)>;
+/// where imported names came from
+type Bindings = HashMap<Symbol, Symbol>;
+
+/// errors from resolving names between modules
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModuleError {
+    /// name, module it is already bound in, module it was imported from
+    Conflict(Symbol, Symbol, Symbol),
+    /// module being linked in, message from LLVM
+    Link(Symbol, String),
+    /// name, module it was looked up in
+    UndefVar(Symbol, Symbol),
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ModuleError::Conflict(name, module, other) => write!(
+                f,
+                "importing {} from {} into {} conflicts with an existing identifier",
+                name, other, module
+            ),
+            ModuleError::Link(module, msg) => {
+                write!(f, "failed to link in module {}: {}", module, msg)
+            }
+            ModuleError::UndefVar(name, module) => {
+                write!(f, "UndefVarError: `{}` not defined in `{}`", name, module)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
 
 #[derive(Clone, Debug)]
 pub struct Module<'a> {
+    bindings: Bindings,
     context: &'a Context,
-    // dependencies: Vec<Symbol>,
+    dependencies: Vec<Symbol>,
     exports: Exports,
+    globals: Globals,
     methods: MethodTable,
     module: module::Module<'a>,
     name: Symbol,
@@ -48,8 +90,11 @@ impl<'a> Module<'a> {
         let _ = module.add_function("printf", printf_type, Some(Linkage::External));
 
         Self {
+            bindings: Bindings::new(),
             context: context,
+            dependencies: Vec::new(),
             exports: exports,
+            globals: Globals::new(),
             methods: methods,
             module: module,
             name: symbol,
@@ -57,6 +102,23 @@ impl<'a> Module<'a> {
         }
     }
 
+    /// wraps LLVM bitcode written by a previous compilation, the
+    /// rest of the module has to be filled in by the caller
+    pub fn from_bitcode(context: &'a Context, name: &str, path: &Path) -> Option<Self> {
+        let module = module::Module::parse_bitcode_from_path(path, context).ok()?;
+        Some(Self {
+            bindings: Bindings::new(),
+            context,
+            dependencies: Vec::new(),
+            exports: Exports::new(),
+            globals: Globals::new(),
+            methods: MethodTable::new(),
+            module,
+            name: Symbol::new(name),
+            types: Types::new(),
+        })
+    }
+
     pub fn add_function(
         &self,
         name: &str,
@@ -66,9 +128,9 @@ impl<'a> Module<'a> {
         self.module.add_function(name, func, opt)
     }
 
-    pub fn add_global(
+    pub fn add_global<T: BasicType<'a>>(
         &self,
-        datatype: StructType<'a>,
+        datatype: T,
         address_space: Option<AddressSpace>,
         name: &str,
     ) -> GlobalValue<'a> {
@@ -83,6 +145,10 @@ impl<'a> Module<'a> {
         self.module.create_jit_execution_engine(opt_level).unwrap()
     }
 
+    pub fn get_bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
     pub fn get_context(&self) -> &'a Context {
         &self.context
     }
@@ -99,6 +165,11 @@ impl<'a> Module<'a> {
         )
     }
 
+    /// modules whose code needs to be linked in with this one
+    pub fn get_dependencies(&self) -> &Vec<Symbol> {
+        &self.dependencies
+    }
+
     pub fn get_exports(&self) -> &Exports {
         &self.exports
     }
@@ -107,10 +178,82 @@ impl<'a> Module<'a> {
         self.module.get_functions()
     }
 
+    pub fn get_method(&self, name: &str) -> Option<&(ArgTypes, ReturnType)> {
+        self.methods.get(&Symbol::new(name))
+    }
+
+    pub fn get_methods(&self) -> &MethodTable {
+        &self.methods
+    }
+
+    /// the LLVM function of a method, named Module.mangled like globals
+    /// so modules can define methods with the same signature. Core's
+    /// methods are built in and keep their mangled names
+    pub fn method_symbol(&self, mangled: &str) -> String {
+        let owner = self
+            .bindings
+            .get(&Symbol::new(mangled))
+            .unwrap_or(&self.name);
+        match owner.name() {
+            "Core" => mangled.to_owned(),
+            _ => format!("{}.{}", owner, mangled),
+        }
+    }
+
+    /// the LLVM signature of a method, built from this module's
+    /// types so it matches even for methods of other modules
+    pub fn method_type(&self, arg_types: &[DataType], return_type: &DataType) -> FunctionType<'a> {
+        let arg_ir_types = arg_types
+            .iter()
+            .map(|x| x.get_ir_value_type(self))
+            .collect::<Vec<_>>();
+        match return_type.get_ir_value_type(self) {
+            BasicMetadataTypeEnum::FloatType(x) => x.fn_type(&arg_ir_types, false),
+            BasicMetadataTypeEnum::IntType(x) => x.fn_type(&arg_ir_types, false),
+            BasicMetadataTypeEnum::PointerType(x) => x.fn_type(&arg_ir_types, false),
+            BasicMetadataTypeEnum::StructType(x) => x.fn_type(&arg_ir_types, false),
+            _ => todo!("Unsupported return type {}", return_type),
+        }
+    }
+
+    /// all methods of a generic function, keyed by mangled name
+    pub fn get_methods_named(&self, name: &str) -> Vec<(Symbol, ArgTypes, ReturnType)> {
+        let mut methods = self
+            .methods
+            .iter()
+            .filter(|(k, (arg_types, _))| {
+                let mangled = name.to_owned()
+                    + "_"
+                    + &arg_types
+                        .iter()
+                        .map(|x| x.full_name())
+                        .collect::<Vec<_>>()
+                        .join("_");
+                k.name() == mangled
+            })
+            .map(|(k, (arg_types, return_type))| {
+                (k.clone(), arg_types.clone(), return_type.clone())
+            })
+            .collect::<Vec<_>>();
+        methods.sort_by(|a, b| a.0.name().cmp(b.0.name()));
+        methods
+    }
+
     pub fn get_global(&self, name: &str) -> GlobalValue<'a> {
         self.module.get_global(name).unwrap()
     }
 
+    pub fn get_globals(&self) -> &Globals {
+        &self.globals
+    }
+
+    /// the LLVM global, type and constness of a module level binding
+    pub fn get_module_global(&self, name: &str) -> Option<(GlobalValue<'a>, DataType, bool)> {
+        let (ir_name, datatype, is_const) = self.globals.get(&Symbol::new(name))?;
+        let global = self.module.get_global(ir_name.name())?;
+        Some((global, datatype.clone(), *is_const))
+    }
+
     pub fn get_type(&self, sym: &str) -> &DataType {
         let sym = Symbol::new(sym);
         &self
@@ -119,6 +262,67 @@ impl<'a> Module<'a> {
             .expect(format!("Type {} not found in module {}", sym, self.name).as_str())
     }
 
+    /// like get_type but also builds parametric types such as
+    /// Vector{Float64} or Array{Int64, 1} from their name
+    pub fn resolve_type(&self, name: &str) -> DataType {
+        let name = name.trim();
+        let (base, params) = match name.find('{') {
+            Some(n) if name.ends_with('}') => (&name[..n], &name[n + 1..name.len() - 1]),
+            _ => return self.get_type(name).clone(),
+        };
+
+        // split parameters on top level commas only
+        let mut parameters = Vec::<&str>::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (n, c) in params.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ',' if depth == 0 => {
+                    parameters.push(params[start..n].trim());
+                    start = n + 1;
+                }
+                _ => {}
+            }
+        }
+        parameters.push(params[start..].trim());
+
+        match (base, parameters.as_slice()) {
+            ("Vector", [eltype]) => DataType::new_array_type(&self.resolve_type(eltype), 1),
+            ("Array", [eltype, ndims]) => {
+                let ndims = ndims
+                    .parse::<u32>()
+                    .unwrap_or_else(|_| panic!("Invalid number of dimensions in {}", name));
+                DataType::new_array_type(&self.resolve_type(eltype), ndims)
+            }
+            _ => todo!("Unsupported parametric type {}", name),
+        }
+    }
+
+    /// the type followed by its supertypes up to Any
+    pub fn supertypes(&self, datatype: &DataType) -> Vec<DataType> {
+        let mut supertypes = vec![datatype.clone()];
+        let mut current = datatype;
+        while current.name().name() != "Any" {
+            current = match self.types.get(&current.supertype) {
+                Some(x) => x,
+                None => break,
+            };
+            supertypes.push(current.clone());
+        }
+        supertypes
+    }
+
+    /// whether sub is datatype or one of its subtypes
+    pub fn is_subtype(&self, sub: &DataType, datatype: &DataType) -> bool {
+        datatype.name().name() == "Any"
+            || self
+                .supertypes(sub)
+                .iter()
+                .any(|x| x.full_name() == datatype.full_name())
+    }
+
     pub fn get_types(&self) -> &Types {
         &self.types
     }
@@ -151,30 +355,160 @@ impl<'a> Module<'a> {
         self.i64_type().ptr_type(AddressSpace::default())
     }
 
+    /// adds a module level binding, it is stored in an LLVM global
+    /// named Module.name so bindings from different modules can't
+    /// clash once linked together
+    pub fn insert_global(
+        &mut self,
+        name: Symbol,
+        datatype: DataType,
+        is_const: bool,
+    ) -> GlobalValue<'a> {
+        let ir_name = Symbol::from_string(format!("{}.{}", self.name, name));
+        let ir_type: BasicTypeEnum<'a> = datatype.get_ir_value_type(self).try_into().unwrap();
+        let global = self.add_global(ir_type, None, ir_name.name());
+        global.set_initializer(&ir_type.const_zero());
+        global.set_constant(is_const);
+        self.globals.insert(name, (ir_name, datatype, is_const));
+        global
+    }
+
+    /// declares a binding defined in another module
+    pub fn insert_global_declaration(
+        &mut self,
+        name: Symbol,
+        ir_name: Symbol,
+        datatype: DataType,
+        is_const: bool,
+    ) {
+        if self.module.get_global(ir_name.name()).is_none() {
+            let ir_type: BasicTypeEnum<'a> = datatype.get_ir_value_type(self).try_into().unwrap();
+            let global = self.add_global(ir_type, None, ir_name.name());
+            global.set_constant(is_const);
+            global.set_linkage(Linkage::External);
+        }
+        self.globals.insert(name, (ir_name, datatype, is_const));
+    }
+
+    /// records that an imported method is defined by module
+    pub fn insert_binding(&mut self, mangled: Symbol, module: Symbol) {
+        self.bindings.insert(mangled, module);
+    }
+
+    pub fn insert_method(&mut self, name: Symbol, arg_types: ArgTypes, return_type: ReturnType) {
+        self.methods.insert(name, (arg_types, return_type));
+    }
+
     pub fn insert_type(&mut self, datatype: DataType) {
         self.types.insert(datatype.name().clone(), datatype);
     }
 
-    pub fn link(&mut self, module: &Module<'a>) {
-        self.module.link_in_module(module.module.clone()).unwrap();
-
-        for k in module.get_exports() {
-            // first try assuming this key is a type
-            if self.types.contains_key(k) {
-                panic!(
-                    "Conflicting type {} in modules {} and {}",
-                    k,
-                    self.name(),
-                    module.name()
-                )
-            } else {
-                // TODO note this will error if the type doesn't exist
-                // need to change this to use a Result<>
-                self.insert_type(module.get_type(k.name()).clone())
+    /// brings names from another module into this one, all of its
+    /// exports if names is empty. Methods are declared here and
+    /// resolved once the other module is linked in
+    pub fn import(&mut self, module: &Module<'a>, names: &[Symbol]) -> Result<(), ModuleError> {
+        self.import_names(module, names)?;
+        self.push_dependency(module.name());
+        Ok(())
+    }
+
+    fn import_names(&mut self, module: &Module<'a>, names: &[Symbol]) -> Result<(), ModuleError> {
+        let explicit = !names.is_empty();
+        let names = match explicit {
+            true => names.to_vec(),
+            false => module.get_exports().clone(),
+        };
+
+        for name in names.iter() {
+            let mut found = false;
+            if let Some(datatype) = module.types.get(name) {
+                found = true;
+                match self.types.get(name) {
+                    Some(x) if x == datatype => {}
+                    Some(_) => {
+                        return Err(ModuleError::Conflict(
+                            name.clone(),
+                            self.name(),
+                            module.name(),
+                        ));
+                    }
+                    None => self.insert_type(datatype.clone()),
+                }
             }
 
-            // TODO need to handle globals, functions, etc.
+            if let Some((ir_name, datatype, is_const)) = module.globals.get(name) {
+                found = true;
+                match self.globals.get(name) {
+                    Some((x, _, _)) if x == ir_name => {}
+                    Some(_) => {
+                        return Err(ModuleError::Conflict(
+                            name.clone(),
+                            self.name(),
+                            module.name(),
+                        ));
+                    }
+                    None => self.insert_global_declaration(
+                        name.clone(),
+                        ir_name.clone(),
+                        datatype.clone(),
+                        *is_const,
+                    ),
+                }
+            }
+
+            for (mangled, arg_types, return_type) in module.get_methods_named(name.name()) {
+                found = true;
+                // the module that defines the method, even if it is imported
+                // from one that only imports it
+                let owner = module.bindings.get(&mangled).unwrap_or(&module.name);
+                // importing the same method twice is fine
+                if self.bindings.get(&mangled) == Some(owner) {
+                    continue;
+                }
+                if self.methods.contains_key(&mangled) {
+                    return Err(ModuleError::Conflict(
+                        name.clone(),
+                        self.name(),
+                        module.name(),
+                    ));
+                }
+                let ir_name = module.method_symbol(mangled.name());
+                if self.module.get_function(&ir_name).is_none() {
+                    let func = self.method_type(&arg_types, &return_type);
+                    let _ = self
+                        .module
+                        .add_function(&ir_name, func, Some(Linkage::External));
+                }
+                self.bindings.insert(mangled.clone(), owner.clone());
+                self.insert_method(mangled, arg_types, return_type);
+            }
+
+            if explicit && !found {
+                return Err(ModuleError::UndefVar(name.clone(), module.name()));
+            }
         }
+        Ok(())
+    }
+
+    /// links in the code of another module and imports its exports,
+    /// new modules start out by linking Core this way
+    pub fn link(&mut self, module: &Module<'a>) -> Result<(), ModuleError> {
+        self.module
+            .link_in_module(module.module.clone())
+            .map_err(|e| ModuleError::Link(module.name(), e.to_string()))?;
+        self.import_names(module, &[])
+    }
+
+    /// links in the code of another module without importing anything.
+    /// the code is copied through bitcode like a cached module would be,
+    /// LLVM drops the names of struct types a clone shares with this module
+    pub fn link_in_module(&mut self, module: &Module<'a>) -> Result<(), ModuleError> {
+        let buffer = module.module.write_bitcode_to_memory();
+        let copy = module::Module::parse_bitcode_from_buffer(&buffer, self.context)
+            .map_err(|e| ModuleError::Link(module.name(), e.to_string()))?;
+        self.module
+            .link_in_module(copy)
+            .map_err(|e| ModuleError::Link(module.name(), e.to_string()))
     }
 
     pub fn module(&self) -> &module::Module<'a> {
@@ -189,7 +523,113 @@ impl<'a> Module<'a> {
         self.module.print_to_file(name).unwrap()
     }
 
+    pub fn push_dependency(&mut self, sym: Symbol) {
+        if !self.dependencies.contains(&sym) {
+            self.dependencies.push(sym)
+        }
+    }
+
     pub fn push_export(&mut self, sym: Symbol) {
         self.exports.push(sym)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LLVMPrintf;
+    use crate::test_utils::TestHelper;
+
+    /// module Foo with double(x::Int64)::Int64 = 2x
+    fn create_foo<'a>(context: &'a Context) -> Module<'a> {
+        let mut foo = Module::new(context, "Foo");
+        let int64 = DataType::new_primitive_type("Int64", "Signed", 64);
+        let func = context
+            .i64_type()
+            .fn_type(&[context.i64_type().into()], false);
+        let func = foo.add_function("Foo.double_Int64", func, None);
+        let builder = context.create_builder();
+        builder.position_at_end(context.append_basic_block(func, "entry"));
+        let x = func.get_nth_param(0).unwrap().into_int_value();
+        let _ = builder.build_return(Some(&builder.build_int_add(x, x, "").unwrap()));
+        foo.insert_method(Symbol::new("double_Int64"), vec![int64.clone()], int64);
+        foo.push_export(Symbol::new("double"));
+        foo
+    }
+
+    #[test]
+    fn test_import_method() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let mut tester = TestHelper::new("test_import_method", &builder, &context);
+        let foo = create_foo(&context);
+        tester.module.import(&foo, &[]).unwrap();
+        assert_eq!(tester.module.get_dependencies(), &vec![Symbol::new("Foo")]);
+        tester.start();
+
+        let (_, return_type) = tester.module.get_method("double_Int64").unwrap().clone();
+        let ir_name = tester.module.method_symbol("double_Int64");
+        assert_eq!("Foo.double_Int64", ir_name);
+        let x = context.i64_type().const_int(21, false);
+        let val = builder
+            .build_call(tester.module.get_function(&ir_name), &[x.into()], "")
+            .unwrap()
+            .try_as_basic_value()
+            .unwrap_left();
+        let _ = (val.into(), return_type).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+        tester.module.link_in_module(&foo).unwrap();
+
+        assert_eq!("42", tester.run());
+    }
+
+    #[test]
+    fn test_import_global() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let mut tester = TestHelper::new("test_import_global", &builder, &context);
+        let mut foo = create_foo(&context);
+        let int64 = DataType::new_primitive_type("Int64", "Signed", 64);
+        let global = foo.insert_global(Symbol::new("x"), int64.clone(), true);
+        global.set_initializer(&context.i64_type().const_int(7, false));
+        foo.push_export(Symbol::new("x"));
+        tester.module.import(&foo, &[]).unwrap();
+        tester.start();
+
+        let (global, datatype, is_const) = tester.module.get_module_global("x").unwrap();
+        assert_eq!("Foo.x", global.get_name().to_str().unwrap());
+        assert!(is_const);
+        let val = builder.build_load(global.as_pointer_value(), "").unwrap();
+        let _ = (val.into(), datatype).emit_ir_printf(&builder, &tester.module);
+        tester.end();
+        tester.module.link_in_module(&foo).unwrap();
+
+        assert_eq!("7", tester.run());
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let context = Context::create();
+        let mut main = Module::new(&context, "Main");
+        let foo = create_foo(&context);
+        assert_eq!(
+            main.import(&foo, &[Symbol::new("triple")]),
+            Err(ModuleError::UndefVar(
+                Symbol::new("triple"),
+                Symbol::new("Foo")
+            ))
+        );
+
+        // a local method with the same signature
+        let int64 = DataType::new_primitive_type("Int64", "Signed", 64);
+        main.insert_method(Symbol::new("double_Int64"), vec![int64.clone()], int64);
+        assert_eq!(
+            main.import(&foo, &[]),
+            Err(ModuleError::Conflict(
+                Symbol::new("double"),
+                Symbol::new("Main"),
+                Symbol::new("Foo")
+            ))
+        );
+    }
+}
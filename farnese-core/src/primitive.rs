@@ -1,12 +1,15 @@
-use super::{DataType, LLVMAlloca, LLVMValue, Module};
+use super::{
+    DataType, FarneseChar, FarneseString, LLVMAlloca, LLVMPrintf, LLVMValue, Module, Symbol,
+};
 use farnese_lexer::ast;
 use inkwell::builder::Builder;
-use inkwell::values::{BasicValueEnum, PointerValue};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, CallSiteValue, PointerValue};
 
 // TODO refactor whole file to use core::Module
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Primitive {
+    Bool(bool),
     Char(char),
     // Float16(f16), // not in rust
     Float32(f32),
@@ -14,40 +17,80 @@ pub enum Primitive {
     Int16(i16),
     Int32(i32),
     Int64(i64),
+    Int128(i128),
+    Nothing,
     String(String),
-    // UInt16(u16),
-    // UInt32(u32),
-    // UInt64(u64)
+    UInt8(u8),
+    UInt16(u16),
+    UInt32(u32),
+    UInt64(u64),
+    UInt128(u128),
 }
 
 impl Primitive {
     pub fn get_datatype(&self) -> DataType {
+        // nothing is the singleton instance of a struct without fields
+        if let Primitive::Nothing = self {
+            return DataType::new(
+                Symbol::new("Nothing"),
+                Symbol::new("Any"),
+                false,
+                false,
+                false,
+                vec![],
+                Box::default(),
+            );
+        }
         let name = match &self {
+            Primitive::Bool(_) => "Bool",
+            Primitive::Char(_) => "Char",
             Primitive::Float32(_) => "Float32",
             Primitive::Float64(_) => "Float64",
             Primitive::Int16(_) => "Int16",
             Primitive::Int32(_) => "Int32",
             Primitive::Int64(_) => "Int64",
+            Primitive::Int128(_) => "Int128",
+            Primitive::Nothing => unreachable!(),
             Primitive::String(_) => "String",
-            _ => todo!(),
+            Primitive::UInt8(_) => "UInt8",
+            Primitive::UInt16(_) => "UInt16",
+            Primitive::UInt32(_) => "UInt32",
+            Primitive::UInt64(_) => "UInt64",
+            Primitive::UInt128(_) => "UInt128",
         };
         let supertype = match &self {
+            Primitive::Bool(_) => "Integer",
+            Primitive::Char(_) => "AbstractChar",
             Primitive::Float32(_) => "AbstractFloat",
             Primitive::Float64(_) => "AbstractFloat",
             Primitive::Int16(_) => "Signed",
             Primitive::Int32(_) => "Signed",
             Primitive::Int64(_) => "Signed",
+            Primitive::Int128(_) => "Signed",
+            Primitive::Nothing => unreachable!(),
             Primitive::String(_) => "AbstractString",
-            _ => todo!(),
+            Primitive::UInt8(_)
+            | Primitive::UInt16(_)
+            | Primitive::UInt32(_)
+            | Primitive::UInt64(_)
+            | Primitive::UInt128(_) => "Unsigned",
         };
         let bits = match &self {
+            Primitive::Bool(_) => 8,
+            Primitive::Char(_) => 32,
             Primitive::Float32(_) => 32,
             Primitive::Float64(_) => 64,
             Primitive::Int16(_) => 16,
             Primitive::Int32(_) => 32,
             Primitive::Int64(_) => 64,
+            Primitive::Int128(_) => 128,
+            Primitive::Nothing => unreachable!(),
             Primitive::String(_) => 8, // do we want this to be primitive?
-            _ => todo!(),
+            Primitive::UInt8(_) => 8,
+            Primitive::UInt16(_) => 16,
+            Primitive::UInt32(_) => 32,
+            Primitive::UInt64(_) => 64,
+            Primitive::UInt128(_) => 128,
         };
         DataType::new_primitive_type(name, supertype, bits)
     }
@@ -56,17 +99,31 @@ impl Primitive {
 impl From<ast::Primitive> for Primitive {
     fn from(value: ast::Primitive) -> Self {
         match value {
+            ast::Primitive::Bool(x) => Primitive::Bool(x),
+            ast::Primitive::Char(x) => Primitive::Char(x),
             ast::Primitive::Float32(x) => Primitive::Float32(x),
             ast::Primitive::Float64(x) => Primitive::Float64(x),
             ast::Primitive::Int16(x) => Primitive::Int16(x),
             ast::Primitive::Int32(x) => Primitive::Int32(x),
             ast::Primitive::Int64(x) => Primitive::Int64(x),
+            ast::Primitive::Int128(x) => Primitive::Int128(x),
+            ast::Primitive::Nothing => Primitive::Nothing,
             ast::Primitive::String(x) => Primitive::String(x),
-            _ => todo!(),
+            ast::Primitive::UInt8(x) => Primitive::UInt8(x),
+            ast::Primitive::UInt16(x) => Primitive::UInt16(x),
+            ast::Primitive::UInt32(x) => Primitive::UInt32(x),
+            ast::Primitive::UInt64(x) => Primitive::UInt64(x),
+            ast::Primitive::UInt128(x) => Primitive::UInt128(x),
         }
     }
 }
 
+impl From<bool> for Primitive {
+    fn from(value: bool) -> Self {
+        Primitive::Bool(value)
+    }
+}
+
 impl From<char> for Primitive {
     fn from(value: char) -> Self {
         Primitive::Char(value)
@@ -103,14 +160,54 @@ impl From<i64> for Primitive {
     }
 }
 
+impl From<i128> for Primitive {
+    fn from(value: i128) -> Self {
+        Primitive::Int128(value)
+    }
+}
+
 impl From<String> for Primitive {
     fn from(value: String) -> Self {
         Primitive::String(value)
     }
 }
 
+impl From<u8> for Primitive {
+    fn from(value: u8) -> Self {
+        Primitive::UInt8(value)
+    }
+}
+
+impl From<u16> for Primitive {
+    fn from(value: u16) -> Self {
+        Primitive::UInt16(value)
+    }
+}
+
+impl From<u32> for Primitive {
+    fn from(value: u32) -> Self {
+        Primitive::UInt32(value)
+    }
+}
+
+impl From<u64> for Primitive {
+    fn from(value: u64) -> Self {
+        Primitive::UInt64(value)
+    }
+}
+
+impl From<u128> for Primitive {
+    fn from(value: u128) -> Self {
+        Primitive::UInt128(value)
+    }
+}
+
 impl<'a, 'b> LLVMAlloca<'a, 'b> for Primitive {
     fn emit_ir_alloca(&self, builder: &'b Builder<'a>, module: &Module<'a>) -> PointerValue<'a> {
+        // strings are immutable so literals can live in a constant
+        if let Primitive::String(x) = self {
+            return FarneseString::new().emit_ir_literal(module, x);
+        }
         let val = self.emit_ir_value(module);
         let ptr = builder.build_alloca(val.get_type(), "").unwrap();
         let _ = builder.build_store(ptr, val);
@@ -118,41 +215,44 @@ impl<'a, 'b> LLVMAlloca<'a, 'b> for Primitive {
     }
 }
 
+impl<'a, 'b> LLVMPrintf<'a, 'b> for Primitive {
+    fn emit_ir_printf(&self, builder: &'b Builder<'a>, module: &Module<'a>) -> CallSiteValue<'a> {
+        let val: BasicMetadataValueEnum<'a> = match self {
+            Primitive::String(_) => self.emit_ir_alloca(builder, module).into(),
+            _ => self.emit_ir_value(module).into(),
+        };
+        (val, self.get_datatype()).emit_ir_printf(builder, module)
+    }
+}
+
 impl<'a, 'b> LLVMValue<'a> for Primitive {
     fn emit_ir_value(&self, module: &Module<'a>) -> BasicValueEnum<'a> {
         let context = module.get_context();
         match &self {
-            Primitive::Char(x) => context
-                .i8_type()
-                .const_int((*x).try_into().unwrap(), false)
-                .try_into()
-                .unwrap(),
-            Primitive::Float32(x) => context
-                .f32_type()
-                .const_float((*x).try_into().unwrap())
-                .try_into()
-                .unwrap(),
-            Primitive::Float64(x) => context
-                .f64_type()
-                .const_float((*x).try_into().unwrap())
-                .try_into()
-                .unwrap(),
-            Primitive::Int16(x) => context
-                .i16_type()
-                .const_int((*x).try_into().unwrap(), false)
-                .try_into()
-                .unwrap(),
-            Primitive::Int32(x) => context
-                .i32_type()
-                .const_int((*x).try_into().unwrap(), false)
-                .try_into()
-                .unwrap(),
-            Primitive::Int64(x) => context
-                .i64_type()
-                .const_int((*x).try_into().unwrap(), false)
-                .try_into()
-                .unwrap(),
-            Primitive::String(x) => context.const_string(x.as_bytes(), true).try_into().unwrap(),
+            Primitive::Bool(x) => context.bool_type().const_int((*x).into(), false).into(),
+            Primitive::Char(x) => FarneseChar::new().emit_ir_value(module, *x).into(),
+            Primitive::Float32(x) => context.f32_type().const_float((*x).into()).into(),
+            Primitive::Float64(x) => context.f64_type().const_float(*x).into(),
+            Primitive::Int16(x) => context.i16_type().const_int(*x as u64, true).into(),
+            Primitive::Int32(x) => context.i32_type().const_int(*x as u64, true).into(),
+            Primitive::Int64(x) => context.i64_type().const_int(*x as u64, true).into(),
+            Primitive::Int128(x) => context
+                .i128_type()
+                .const_int_arbitrary_precision(&[*x as u64, (*x >> 64) as u64])
+                .into(),
+            Primitive::Nothing => module
+                .get_struct_type("Nothing")
+                .const_named_struct(&[])
+                .into(),
+            Primitive::String(x) => context.const_string(x.as_bytes(), true).into(),
+            Primitive::UInt8(x) => context.i8_type().const_int((*x).into(), false).into(),
+            Primitive::UInt16(x) => context.i16_type().const_int((*x).into(), false).into(),
+            Primitive::UInt32(x) => context.i32_type().const_int((*x).into(), false).into(),
+            Primitive::UInt64(x) => context.i64_type().const_int(*x, false).into(),
+            Primitive::UInt128(x) => context
+                .i128_type()
+                .const_int_arbitrary_precision(&[*x as u64, (*x >> 64) as u64])
+                .into(),
         }
     }
 }
@@ -163,6 +263,20 @@ mod tests {
     use crate::test_utils::TestHelper;
     use inkwell::context::Context;
 
+    #[test]
+    fn test_bool_printf() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_bool", &builder, &context);
+        tester.start();
+        for prim_val in [Primitive::Bool(true), Primitive::Bool(false)] {
+            let _ = prim_val.emit_ir_printf(tester.builder, &tester.module);
+        }
+        tester.end();
+
+        assert_eq!("truefalse", tester.run())
+    }
+
     #[test]
     fn test_float32_printf() {
         let context = Context::create();
@@ -219,6 +333,74 @@ mod tests {
         assert_eq!(69, test_output.parse::<i64>().unwrap())
     }
 
+    #[test]
+    fn test_int128_printf() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_int128", &builder, &context);
+        tester.start();
+        let prim_val: Primitive = i128::MIN.into();
+        let _ = prim_val.emit_ir_printf(tester.builder, &tester.module);
+        tester.end();
+
+        let test_output = tester.run();
+        assert_eq!(i128::MIN, test_output.parse::<i128>().unwrap())
+    }
+
+    #[test]
+    fn test_negative_printf() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_negative", &builder, &context);
+        tester.start();
+        for prim_val in [
+            Primitive::Int16(i16::MIN),
+            Primitive::Int32(-1),
+            Primitive::Int64(-42),
+            Primitive::Int128(-7),
+        ] {
+            let _ = prim_val.emit_ir_printf(tester.builder, &tester.module);
+        }
+        tester.end();
+
+        assert_eq!("-32768-1-42-7", tester.run());
+    }
+
+    #[test]
+    fn test_unsigned_printf() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_unsigned", &builder, &context);
+        tester.start();
+        for prim_val in [
+            Primitive::UInt8(0x1),
+            Primitive::UInt16(0xbeef),
+            Primitive::UInt32(0xff),
+            Primitive::UInt128(u128::MAX - 1),
+        ] {
+            let _ = prim_val.emit_ir_printf(tester.builder, &tester.module);
+        }
+        tester.end();
+
+        let test_output = tester.run();
+        assert_eq!(
+            "0x010xbeef0x000000ff0xfffffffffffffffffffffffffffffffe",
+            test_output
+        )
+    }
+
+    #[test]
+    fn test_nothing_printf() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_nothing", &builder, &context);
+        tester.start();
+        let _ = Primitive::Nothing.emit_ir_printf(tester.builder, &tester.module);
+        tester.end();
+
+        assert_eq!("nothing", tester.run())
+    }
+
     #[test]
     fn test_string_printf() {
         let context = Context::create();
@@ -226,9 +408,9 @@ mod tests {
         let tester = TestHelper::new("test_string", &builder, &context);
         tester.start();
         let prim_val: Primitive = "MyTestString".to_string().into();
+        let datatype = prim_val.get_datatype();
         let prim_val = prim_val.emit_ir_alloca(&tester.builder, &tester.module);
         let prim_val: BasicMetadataValueEnum = prim_val.into();
-        let datatype = tester.module.get_type("String");
         let _ = (prim_val, datatype.clone()).emit_ir_printf(&tester.builder, &tester.module);
         tester.end();
 
@@ -0,0 +1,619 @@
+use super::{
+    Array, DataType, FarneseBox, FarneseChar, FarneseInternal, LLVMPrintf, MethodHelper, Module,
+    StructHelper, Symbol,
+};
+use inkwell::builder::Builder;
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, CallableValue, PointerValue};
+use inkwell::{AddressSpace, IntPredicate};
+
+/// shows a value of the given type, used for the fields of structs and
+/// the elements of arrays so the compiler can pick user defined methods
+pub type ShowFn<'a, 'c> = &'c dyn Fn(&Builder<'a>, BasicValueEnum<'a>, &DataType);
+
+/// julia's show and print, Core has a method of both for each of its
+/// types, e.g. show_Float64, and show_Any/print_Any for boxed values
+///
+/// show writes a value the way it would be written in code, strings
+/// are quoted and floats always have a decimal point. print is the same
+/// except strings, chars and symbols are written as they are. Structs
+/// get a default show, Pair(1.0, 2.0), referenced by their type object
+#[derive(Clone, Debug)]
+pub struct FarneseShow;
+
+impl<'a, 'b> FarneseShow {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// the Core types with their own show and print
+    fn datatypes(&self, module: &Module<'a>) -> Vec<DataType> {
+        let mut datatypes = module
+            .get_types()
+            .values()
+            .filter(|x| !x.is_abstract && !x.is_struct() && x.name().name() != "Array")
+            .cloned()
+            .collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+        datatypes
+    }
+
+    /// the methods Core adds to its method table
+    pub fn methods(&self, module: &Module<'a>) -> Vec<(Symbol, Vec<DataType>, DataType)> {
+        let mut datatypes = self.datatypes(module);
+        datatypes.push(module.get_type("Any").clone());
+        let nothing = module.get_type("Nothing").clone();
+        let mut methods = Vec::new();
+        for name in ["print", "show"] {
+            for datatype in datatypes.iter() {
+                let method_name = format!("{}_{}", name, datatype.name().name());
+                methods.push((
+                    Symbol::new(&method_name),
+                    vec![datatype.clone()],
+                    nothing.clone(),
+                ));
+            }
+        }
+        methods
+    }
+
+    fn call(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        args: &[BasicMetadataValueEnum<'a>],
+    ) {
+        let _ = builder.build_call(module.get_function(name), args, "");
+    }
+
+    fn printf_args(
+        &self,
+        builder: &Builder<'a>,
+        module: &Module<'a>,
+        format: &str,
+        args: &[BasicMetadataValueEnum<'a>],
+    ) {
+        let format_string = builder
+            .build_global_string_ptr(format, "")
+            .unwrap()
+            .as_pointer_value();
+        let mut func_args: Vec<BasicMetadataValueEnum<'a>> = vec![format_string.into()];
+        func_args.extend_from_slice(args);
+        let _ = builder.build_call(module.get_function("printf"), &func_args, "");
+    }
+
+    /// writes a string as it is
+    pub fn emit_ir_print_str(&self, builder: &Builder<'a>, module: &Module<'a>, val: &str) {
+        let val = builder
+            .build_global_string_ptr(val, "")
+            .unwrap()
+            .as_pointer_value();
+        self.printf_args(builder, module, "%s", &[val.into()]);
+    }
+
+    /// shows a value with Core's methods, boxed values are shown by
+    /// show_Any and structs by their default show
+    pub fn emit_ir_show(
+        &self,
+        builder: &Builder<'a>,
+        module: &Module<'a>,
+        val: BasicValueEnum<'a>,
+        datatype: &DataType,
+    ) {
+        let show = |builder: &Builder<'a>, val: BasicValueEnum<'a>, datatype: &DataType| {
+            self.emit_ir_show(builder, module, val, datatype)
+        };
+        match datatype {
+            x if x.is_abstract => {
+                let _ = builder.build_call(module.get_function("show_Any"), &[val.into()], "");
+            }
+            x if x.name().name() == "Array" => {
+                self.emit_ir_show_array(builder, module, val.into_pointer_value(), x, &show);
+            }
+            x if x.is_struct() => {
+                self.emit_ir_show_struct(module, x, &show);
+                self.emit_ir_call_show_struct(builder, module, val.into_pointer_value(), x);
+            }
+            x => {
+                let name = format!("show_{}", x.name().name());
+                match module.module().get_function(&name) {
+                    Some(func) => {
+                        let _ = builder.build_call(func, &[val.into()], "");
+                    }
+                    None if x.is_primitive => self.emit_ir_show_primitive(builder, module, val, x),
+                    None => panic!("MethodError: no method matching show(::{})", x.full_name()),
+                }
+            }
+        }
+    }
+
+    /// calls the default show of a struct
+    pub fn emit_ir_call_show_struct(
+        &self,
+        builder: &Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+        datatype: &DataType,
+    ) {
+        let i8_ptr_type = module
+            .get_context()
+            .i8_type()
+            .ptr_type(AddressSpace::default());
+        let ptr = builder.build_pointer_cast(ptr, i8_ptr_type, "").unwrap();
+        let func = datatype.emit_ir_show_method(module);
+        let _ = builder.build_call(func, &[ptr.into()], "");
+    }
+
+    /// defines the default show of a struct unless it already is, the
+    /// type name followed by its fields, e.g. Pair(1.0, 2.0)
+    pub fn emit_ir_show_struct(
+        &self,
+        module: &Module<'a>,
+        datatype: &DataType,
+        show: ShowFn<'a, '_>,
+    ) {
+        let func = datatype.emit_ir_show_method(module);
+        if func.count_basic_blocks() > 0 {
+            return;
+        }
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let ptr = func.get_nth_method_input(0).into_pointer_value();
+        let ptr = builder
+            .build_pointer_cast(
+                ptr,
+                datatype
+                    .struct_ir_type(module)
+                    .ptr_type(AddressSpace::default()),
+                "",
+            )
+            .unwrap();
+        self.emit_ir_print_str(&builder, module, &format!("{}(", datatype.full_name()));
+        for (n, name) in datatype.field_names.iter().enumerate() {
+            if n > 0 {
+                self.emit_ir_print_str(&builder, module, ", ");
+            }
+            let (val, field_type) = datatype
+                .emit_ir_getfield(&builder, ptr, name.name())
+                .unwrap();
+            show(&builder, val, &field_type);
+        }
+        self.emit_ir_print_str(&builder, module, ")");
+        let _ = builder.build_return(None);
+    }
+
+    /// primitive types without a show of their own are shown as their
+    /// bits in hex, most significant first, e.g. Int24(0x00002a)
+    pub fn emit_ir_show_primitive(
+        &self,
+        builder: &Builder<'a>,
+        module: &Module<'a>,
+        val: BasicValueEnum<'a>,
+        datatype: &DataType,
+    ) {
+        let context = module.get_context();
+        let int_type = context.custom_width_int_type(datatype.bits);
+        let val = builder
+            .build_bit_cast(val, int_type, "")
+            .unwrap()
+            .into_int_value();
+        // printf takes at most 64 bits at a time
+        let mut format = format!("{}(0x", datatype.full_name());
+        let mut args = Vec::<BasicMetadataValueEnum<'a>>::new();
+        for n in (0..datatype.bits.div_ceil(64)).rev() {
+            let shift = int_type.const_int(64 * n as u64, false);
+            let chunk = builder.build_right_shift(val, shift, false, "").unwrap();
+            let chunk = builder
+                .build_int_cast_sign_flag(chunk, context.i64_type(), false, "")
+                .unwrap();
+            let digits = (datatype.bits - 64 * n).min(64) / 4;
+            format.push_str(&format!("%0{}llx", digits));
+            args.push(chunk.into());
+        }
+        format.push(')');
+        self.printf_args(builder, module, &format, &args);
+    }
+
+    /// shows the elements of an array between brackets, e.g. [1, 2].
+    /// Like julia the element type comes first if it is abstract or
+    /// the array is empty, e.g. Any[1, "a"] or Int64[]
+    pub fn emit_ir_show_array(
+        &self,
+        builder: &Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+        datatype: &DataType,
+        show: ShowFn<'a, '_>,
+    ) {
+        let context = module.get_context();
+        let i64_type = context.i64_type();
+        let array = Array::new(datatype.clone());
+        let eltype = array.eltype(module);
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let prefix = context.append_basic_block(func, "prefix");
+        let open = context.append_basic_block(func, "open");
+        let body = context.append_basic_block(func, "body");
+        let comma = context.append_basic_block(func, "comma");
+        let element = context.append_basic_block(func, "element");
+        let close = context.append_basic_block(func, "close");
+
+        let length = array.emit_ir_length(builder, ptr);
+        let is_empty = builder
+            .build_int_compare(IntPredicate::EQ, length, i64_type.const_zero(), "")
+            .unwrap();
+        let has_prefix = match eltype.is_abstract {
+            true => context.bool_type().const_int(1, false),
+            false => is_empty,
+        };
+        let _ = builder.build_conditional_branch(has_prefix, prefix, open);
+
+        builder.position_at_end(prefix);
+        self.emit_ir_print_str(builder, module, &eltype.full_name());
+        let _ = builder.build_unconditional_branch(open);
+
+        builder.position_at_end(open);
+        self.emit_ir_print_str(builder, module, "[");
+        let _ = builder.build_conditional_branch(is_empty, close, body);
+
+        builder.position_at_end(body);
+        let i = builder.build_phi(i64_type, "i").unwrap();
+        let index = i.as_basic_value().into_int_value();
+        let is_first = builder
+            .build_int_compare(IntPredicate::EQ, index, i64_type.const_int(1, false), "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_first, element, comma);
+
+        builder.position_at_end(comma);
+        self.emit_ir_print_str(builder, module, ", ");
+        let _ = builder.build_unconditional_branch(element);
+
+        builder.position_at_end(element);
+        let element_ptr = array.emit_ir_element_ptr(builder, module, ptr, index, false);
+        let val = builder.build_load(element_ptr, "").unwrap();
+        show(builder, val, &eltype);
+        let next = builder
+            .build_int_add(index, i64_type.const_int(1, false), "")
+            .unwrap();
+        let is_done = builder
+            .build_int_compare(IntPredicate::SGT, next, length, "")
+            .unwrap();
+        i.add_incoming(&[
+            (&i64_type.const_int(1, false), open),
+            (&next, builder.get_insert_block().unwrap()),
+        ]);
+        let _ = builder.build_conditional_branch(is_done, close, body);
+
+        builder.position_at_end(close);
+        self.emit_ir_print_str(builder, module, "]");
+    }
+
+    /// shows a string in quotes, escaping what would end or interpolate it
+    fn emit_ir_show_string(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        ptr: PointerValue<'a>,
+    ) {
+        let context = module.get_context();
+        let i8_type = context.i8_type();
+        let i64_type = context.i64_type();
+        let func = builder.get_insert_block().unwrap().get_parent().unwrap();
+        let body = context.append_basic_block(func, "body");
+        let latch = context.append_basic_block(func, "latch");
+        let plain = context.append_basic_block(func, "plain");
+        let done = context.append_basic_block(func, "done");
+
+        let data = ptr.load_nth_field(builder, 0).into_pointer_value();
+        let length = ptr.load_nth_field(builder, 1).into_int_value();
+        self.emit_ir_print_str(builder, module, "\"");
+        let entry = builder.get_insert_block().unwrap();
+        let is_empty = builder
+            .build_int_compare(IntPredicate::EQ, length, i64_type.const_zero(), "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_empty, done, body);
+
+        builder.position_at_end(body);
+        let i = builder.build_phi(i64_type, "i").unwrap();
+        let index = i.as_basic_value().into_int_value();
+        let char_ptr = unsafe { builder.build_in_bounds_gep(data, &[index], "") }.unwrap();
+        let byte = builder.build_load(char_ptr, "").unwrap().into_int_value();
+        let escapes = [
+            (b'"', "\\\""),
+            (b'\\', "\\\\"),
+            (b'$', "\\$"),
+            (b'\n', "\\n"),
+            (b'\t', "\\t"),
+        ];
+        let cases = escapes
+            .iter()
+            .map(|(x, escaped)| {
+                let block = context.append_basic_block(func, "escape");
+                builder.position_at_end(block);
+                self.emit_ir_print_str(builder, module, escaped);
+                let _ = builder.build_unconditional_branch(latch);
+                (i8_type.const_int(*x as u64, false), block)
+            })
+            .collect::<Vec<_>>();
+        builder.position_at_end(body);
+        let _ = builder.build_switch(byte, plain, &cases);
+
+        builder.position_at_end(plain);
+        self.printf_args(builder, module, "%c", &[byte.into()]);
+        let _ = builder.build_unconditional_branch(latch);
+
+        builder.position_at_end(latch);
+        let next = builder
+            .build_int_add(index, i64_type.const_int(1, false), "")
+            .unwrap();
+        i.add_incoming(&[(&i64_type.const_zero(), entry), (&next, latch)]);
+        let is_done = builder
+            .build_int_compare(IntPredicate::EQ, next, length, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_done, done, body);
+
+        builder.position_at_end(done);
+        self.emit_ir_print_str(builder, module, "\"");
+    }
+
+    fn emit_ir_show_float(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: BasicValueEnum<'a>,
+        is_float32: bool,
+    ) {
+        let context = module.get_context();
+        let buffer = builder
+            .build_array_alloca(
+                context.i8_type(),
+                context.i64_type().const_int(32, false),
+                "",
+            )
+            .unwrap();
+        let val = builder
+            .build_float_ext(val.into_float_value(), context.f64_type(), "")
+            .unwrap();
+        let is_float32 = context.bool_type().const_int(is_float32 as u64, false);
+        self.call(
+            builder,
+            module,
+            "__float_chars",
+            &[val.into(), is_float32.into(), buffer.into()],
+        );
+        self.printf_args(builder, module, "%s", &[buffer.into()]);
+    }
+
+    fn create_show_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let nothing = module.get_struct_type("Nothing").const_named_struct(&[]);
+        let nothing_type = module.get_type("Nothing");
+
+        for datatype in self.datatypes(module) {
+            let name = datatype.name().name();
+            let func = module.method_type(std::slice::from_ref(&datatype), nothing_type);
+            let func = module.add_function(&format!("show_{}", name), func, None);
+            let entry = context.append_basic_block(func, "entry");
+            builder.position_at_end(entry);
+
+            let val = func.get_nth_method_input(0);
+            match name {
+                "Float32" | "Float64" => {
+                    let val = val.into_float_value().into();
+                    self.emit_ir_show_float(&builder, module, val, name == "Float32");
+                }
+                "String" => self.emit_ir_show_string(&builder, module, val.into_pointer_value()),
+                "Char" => {
+                    let (buffer, _) =
+                        FarneseChar::new().emit_ir_utf8(&builder, module, val.into_int_value());
+                    self.printf_args(&builder, module, "'%s'", &[buffer.into()]);
+                }
+                "Symbol" => {
+                    self.emit_ir_print_str(&builder, module, ":");
+                    let _ = (val, datatype.clone()).emit_ir_printf(&builder, module);
+                }
+                _ => {
+                    let _ = (val, datatype.clone()).emit_ir_printf(&builder, module);
+                }
+            }
+            let _ = builder.build_return(Some(&nothing));
+
+            // print only differs for text
+            let func = module.method_type(std::slice::from_ref(&datatype), nothing_type);
+            let func = module.add_function(&format!("print_{}", name), func, None);
+            let entry = context.append_basic_block(func, "entry");
+            builder.position_at_end(entry);
+
+            let val = func.get_nth_method_input(0);
+            match name {
+                "Char" | "String" | "Symbol" => {
+                    let _ = (val, datatype.clone()).emit_ir_printf(&builder, module);
+                }
+                _ => self.call(&builder, module, &format!("show_{}", name), &[val]),
+            }
+            let _ = builder.build_return(Some(&nothing));
+        }
+    }
+
+    /// show_Any and print_Any switch on the tag of the box, structs
+    /// are shown through the show of their type object
+    fn create_any_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let box_helper = FarneseBox::new();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let nothing = module.get_struct_type("Nothing").const_named_struct(&[]);
+        let nothing_type = module.get_type("Nothing");
+        let any = module.get_type("Any").clone();
+        let datatypes = self.datatypes(module);
+
+        for name in ["show", "print"] {
+            let func = module.method_type(std::slice::from_ref(&any), nothing_type);
+            let func = module.add_function(&format!("{}_Any", name), func, None);
+            let entry = context.append_basic_block(func, "entry");
+            builder.position_at_end(entry);
+
+            let ptr = func.get_nth_method_input(0).into_pointer_value();
+            let tag = box_helper.emit_ir_typeof(&builder, ptr);
+            let names = match name {
+                // everything but text is printed the same as it is shown
+                "print" => vec!["Char", "String", "Symbol"],
+                _ => datatypes.iter().map(|x| x.name().name()).collect(),
+            };
+            for datatype in names.into_iter().map(|x| module.get_type(x)) {
+                let found = context.append_basic_block(func, datatype.name().name());
+                let next = context.append_basic_block(func, "");
+                let is_type = builder
+                    .build_int_compare(IntPredicate::EQ, tag, datatype.emit_ir_object(module), "")
+                    .unwrap();
+                let _ = builder.build_conditional_branch(is_type, found, next);
+
+                builder.position_at_end(found);
+                let val = box_helper.emit_ir_unbox(&builder, module, ptr, datatype);
+                let method_name = format!("{}_{}", name, datatype.name().name());
+                self.call(&builder, module, &method_name, &[val.into()]);
+                let _ = builder.build_return(Some(&nothing));
+                builder.position_at_end(next);
+            }
+
+            if name == "print" {
+                self.call(&builder, module, "show_Any", &[ptr.into()]);
+                let _ = builder.build_return(Some(&nothing));
+                continue;
+            }
+
+            // the payload of a boxed struct is a pointer to it
+            let struct_block = context.append_basic_block(func, "struct");
+            let other = context.append_basic_block(func, "other");
+            let show = tag.load_nth_field(&builder, 5).into_pointer_value();
+            let is_null = builder.build_is_null(show, "").unwrap();
+            let _ = builder.build_conditional_branch(is_null, other, struct_block);
+
+            builder.position_at_end(struct_block);
+            let payload_type =
+                context.struct_type(&[tag.get_type().into(), i8_ptr_type.into()], false);
+            let payload = builder
+                .build_pointer_cast(ptr, payload_type.ptr_type(AddressSpace::default()), "")
+                .unwrap();
+            let val = payload.load_nth_field(&builder, 1);
+            let show_type = context.void_type().fn_type(&[i8_ptr_type.into()], false);
+            let show = builder
+                .build_pointer_cast(show, show_type.ptr_type(AddressSpace::default()), "")
+                .unwrap();
+            let show = CallableValue::try_from(show).unwrap();
+            let _ = builder.build_call(show, &[val.into()], "");
+            let _ = builder.build_return(Some(&nothing));
+
+            // anything else, e.g. arrays, shows its type
+            builder.position_at_end(other);
+            let tag: BasicMetadataValueEnum<'a> = tag.into();
+            let _ = (tag, module.get_type("DataType").clone()).emit_ir_printf(&builder, module);
+            let _ = builder.build_return(Some(&nothing));
+        }
+    }
+}
+
+impl Default for FarneseShow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FarneseInternal<'a> for FarneseShow {
+    fn create_opaque_type(&self, _module: &Module<'a>) {}
+
+    fn create_new_method(&self, _module: &Module<'a>) {}
+
+    /// has to come after every Core type and type object exists
+    fn create_get_methods(&self, module: &Module<'a>) {
+        self.create_show_methods(module);
+        self.create_any_methods(module);
+
+        // Core's own structs, e.g. ErrorException("oops")
+        let show = |builder: &Builder<'a>, val: BasicValueEnum<'a>, datatype: &DataType| {
+            self.emit_ir_show(builder, module, val, datatype)
+        };
+        let mut datatypes = module
+            .get_types()
+            .values()
+            .filter(|x| x.is_struct())
+            .collect::<Vec<_>>();
+        datatypes.sort_by(|a, b| a.name().name().cmp(b.name().name()));
+        for datatype in datatypes {
+            self.emit_ir_show_struct(module, datatype, &show);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestHelper;
+    use crate::{FarneseException, FarneseString};
+    use inkwell::context::Context;
+
+    #[test]
+    fn test_show() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_show", &builder, &context);
+        tester.start();
+
+        let show = FarneseShow::new();
+        let module = &tester.module;
+        let space = || show.emit_ir_print_str(&builder, module, " ");
+        let float = module.get_type("Float64").clone();
+        for x in [1.0, 0.1, -0.0, 100000.0, 1e6, 1.5e-7, 1.0 / 3.0, f64::NAN] {
+            let val = context.f64_type().const_float(x);
+            show.emit_ir_show(&builder, module, val.into(), &float);
+            space();
+        }
+        let val = context.f32_type().const_float(1.5);
+        show.emit_ir_show(&builder, module, val.into(), module.get_type("Float32"));
+        space();
+
+        let string = module.get_type("String").clone();
+        let val = FarneseString::new().emit_ir_literal(module, "say \"hi\"\n");
+        show.emit_ir_show(&builder, module, val.into(), &string);
+        space();
+        let _ = builder.build_call(module.get_function("print_String"), &[val.into()], "");
+
+        // boxed structs are shown through their type object
+        let msg = FarneseString::new().emit_ir_literal(module, "oops");
+        let e =
+            FarneseException::new().emit_ir_new(&builder, module, "ErrorException", &[msg.into()]);
+        show.emit_ir_show(&builder, module, e.into(), module.get_type("Any"));
+        space();
+
+        let int = module.get_type("Int64").clone();
+        let one = context.i64_type().const_int(1, false);
+        let two = context.i64_type().const_int(2, false);
+        for vals in [vec![one.into(), two.into()], vec![]] {
+            let vector = DataType::new_array_type(&int, 1);
+            let a = Array::new(vector.clone()).emit_ir_literal(&builder, module, &vals);
+            show.emit_ir_show(&builder, module, a.into(), &vector);
+            space();
+        }
+
+        // user defined primitive types show their bits
+        let int24 = DataType::new_primitive_type("Int24", "Signed", 24);
+        let val = context.custom_width_int_type(24).const_int(42, false);
+        show.emit_ir_show(&builder, module, val.into(), &int24);
+        space();
+        let big = DataType::new_primitive_type("Big", "Unsigned", 128);
+        let val = context.i128_type().const_int_arbitrary_precision(&[255, 1]);
+        show.emit_ir_show(&builder, module, val.into(), &big);
+        tester.end();
+
+        assert_eq!(
+            "1.0 0.1 -0.0 100000.0 1.0e6 1.5e-7 0.3333333333333333 NaN 1.5f0 \
+             \"say \\\"hi\\\"\\n\" say \"hi\"\n\
+             ErrorException(\"oops\") [1, 2] Int64[] \
+             Int24(0x00002a) Big(0x000000000000000100000000000000ff)",
+            tester.run()
+        );
+    }
+}
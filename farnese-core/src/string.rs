@@ -0,0 +1,817 @@
+use super::{FarneseGC, FarneseInternal, MethodHelper, Module, StructHelper};
+use inkwell::builder::Builder;
+use inkwell::module::Linkage;
+use inkwell::values::{BasicMetadataValueEnum, FloatValue, IntValue, PointerValue};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate};
+
+/// runtime layout of a String, the bytes are UTF-8 and always
+/// followed by a NUL so they can be handed directly to libc
+///
+///   %String = type { i8* data, i64 length }
+///
+/// length is the number of bytes (ncodeunits) not characters. data comes
+/// first so the type isn't isomorphic to Symbol, otherwise the two get
+/// merged when modules are linked together
+#[derive(Clone, Debug)]
+pub struct FarneseString;
+
+impl<'a, 'b> FarneseString {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// emits a constant String for a literal, no allocation needed
+    pub fn emit_ir_literal(&self, module: &Module<'a>, value: &str) -> PointerValue<'a> {
+        let context = module.get_context();
+        let i32_type = context.i32_type();
+        let bytes = context.const_string(value.as_bytes(), true);
+        let bytes_global = module.module().add_global(bytes.get_type(), None, "str");
+        bytes_global.set_initializer(&bytes);
+        bytes_global.set_constant(true);
+        bytes_global.set_linkage(Linkage::Private);
+        let data = unsafe {
+            bytes_global
+                .as_pointer_value()
+                .const_in_bounds_gep(&[i32_type.const_zero(), i32_type.const_zero()])
+        };
+
+        // literals get a static header so the GC can tell them apart
+        let string_type = module.get_struct_type("String");
+        let string = string_type.const_named_struct(&[
+            data.into(),
+            context
+                .i64_type()
+                .const_int(value.len() as u64, false)
+                .into(),
+        ]);
+        let header = FarneseGC::new().const_static_header(module, 16);
+        let global_type = context.struct_type(&[header.get_type(), string_type.into()], false);
+        let string_global = module.add_global(global_type, None, "str");
+        string_global.set_initializer(&global_type.const_named_struct(&[header, string.into()]));
+        string_global.set_constant(true);
+        string_global.set_linkage(Linkage::Private);
+        unsafe {
+            string_global
+                .as_pointer_value()
+                .const_in_bounds_gep(&[i32_type.const_zero(), i32_type.const_int(1, false)])
+        }
+    }
+
+    pub fn emit_ir_concat(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        lhs: PointerValue<'a>,
+        rhs: PointerValue<'a>,
+    ) -> PointerValue<'a> {
+        self.call(builder, module, "__string_concat", lhs, rhs)
+            .into_pointer_value()
+    }
+
+    /// three way comparison of the bytes, negative when lhs < rhs
+    pub fn emit_ir_cmp(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        lhs: PointerValue<'a>,
+        rhs: PointerValue<'a>,
+    ) -> IntValue<'a> {
+        self.call(builder, module, "__string_cmp", lhs, rhs)
+            .into_int_value()
+    }
+
+    pub fn emit_ir_from_float64(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: FloatValue<'a>,
+    ) -> PointerValue<'a> {
+        builder
+            .build_call(
+                module.get_function("__string_from_float64"),
+                &[val.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    pub fn emit_ir_from_int64(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        val: IntValue<'a>,
+    ) -> PointerValue<'a> {
+        builder
+            .build_call(
+                module.get_function("__string_from_int64"),
+                &[val.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    /// number of characters, i.e. UTF-8 code points
+    pub fn emit_ir_length(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        string_ptr: PointerValue<'a>,
+    ) -> IntValue<'a> {
+        builder
+            .build_call(
+                module.get_function("__string_length"),
+                &[string_ptr.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value()
+    }
+
+    /// number of bytes
+    pub fn emit_ir_ncodeunits(
+        &self,
+        builder: &'b Builder<'a>,
+        string_ptr: PointerValue<'a>,
+    ) -> IntValue<'a> {
+        string_ptr.load_nth_field(builder, 1).into_int_value()
+    }
+
+    fn call(
+        &self,
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        name: &str,
+        lhs: PointerValue<'a>,
+        rhs: PointerValue<'a>,
+    ) -> inkwell::values::BasicValueEnum<'a> {
+        builder
+            .build_call(module.get_function(name), &[lhs.into(), rhs.into()], "")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+    }
+
+    /// allocates a header and a NUL terminated buffer of length bytes
+    pub(crate) fn emit_ir_alloc(
+        builder: &'b Builder<'a>,
+        module: &Module<'a>,
+        length: IntValue<'a>,
+    ) -> (PointerValue<'a>, PointerValue<'a>) {
+        let context = module.get_context();
+        let i8_type = context.i8_type();
+        let num_bytes = builder
+            .build_int_add(length, context.i64_type().const_int(1, false), "")
+            .unwrap();
+        let gc = FarneseGC::new();
+        let is_tracked = context.bool_type().const_zero();
+        let data = gc.emit_ir_alloc_bytes(builder, module, num_bytes, is_tracked);
+        let nul_ptr = unsafe { builder.build_in_bounds_gep(data, &[length], "") }.unwrap();
+        let _ = builder.build_store(nul_ptr, i8_type.const_zero());
+
+        let string_type = module.get_struct_type("String");
+        let string_ptr = gc.emit_ir_alloc(builder, module, string_type.into(), 0, 1);
+        let _ = builder.build_store(string_ptr.get_nth_field(builder, 1), length);
+        string_ptr.set_nth_field(builder, 0, data.into());
+        (string_ptr, data)
+    }
+
+    fn create_cmp_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let string_ptr_type = module
+            .get_struct_type("String")
+            .ptr_type(AddressSpace::default());
+
+        let func = i64_type.fn_type(&[string_ptr_type.into(), string_ptr_type.into()], false);
+        let func = module.add_function("__string_cmp", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let differ = context.append_basic_block(func, "differ");
+        let same = context.append_basic_block(func, "same");
+        builder.position_at_end(entry);
+
+        let lhs = func.get_nth_method_input(0);
+        let rhs = func.get_nth_method_input(1);
+        let lhs_length = lhs.load_nth_field(&builder, 1).into_int_value();
+        let rhs_length = rhs.load_nth_field(&builder, 1).into_int_value();
+        let is_shorter = builder
+            .build_int_compare(IntPredicate::SLT, lhs_length, rhs_length, "")
+            .unwrap();
+        let min_length = builder
+            .build_select(is_shorter, lhs_length, rhs_length, "")
+            .unwrap();
+        let result = builder
+            .build_call(
+                module.get_function("memcmp"),
+                &[
+                    lhs.load_nth_field(&builder, 0).into(),
+                    rhs.load_nth_field(&builder, 0).into(),
+                    min_length.into(),
+                ],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let is_zero = builder
+            .build_int_compare(IntPredicate::EQ, result, result.get_type().const_zero(), "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_zero, same, differ);
+
+        builder.position_at_end(differ);
+        let result = builder.build_int_s_extend(result, i64_type, "").unwrap();
+        let _ = builder.build_return(Some(&result));
+
+        // common prefix so the shorter string comes first
+        builder.position_at_end(same);
+        let result = builder.build_int_sub(lhs_length, rhs_length, "").unwrap();
+        let _ = builder.build_return(Some(&result));
+    }
+
+    fn create_concat_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let string_ptr_type = module
+            .get_struct_type("String")
+            .ptr_type(AddressSpace::default());
+
+        let func =
+            string_ptr_type.fn_type(&[string_ptr_type.into(), string_ptr_type.into()], false);
+        let func = module.add_function("__string_concat", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let lhs = func.get_nth_method_input(0);
+        let rhs = func.get_nth_method_input(1);
+        let lhs_length = lhs.load_nth_field(&builder, 1).into_int_value();
+        let rhs_length = rhs.load_nth_field(&builder, 1).into_int_value();
+        let length = builder.build_int_add(lhs_length, rhs_length, "").unwrap();
+        let (string_ptr, data) = Self::emit_ir_alloc(&builder, module, length);
+        let lhs_data = lhs.load_nth_field(&builder, 0).into_pointer_value();
+        let rhs_data = rhs.load_nth_field(&builder, 0).into_pointer_value();
+        let _ = builder.build_memcpy(data, 1, lhs_data, 1, lhs_length);
+        let rhs_dest = unsafe { builder.build_in_bounds_gep(data, &[lhs_length], "") }.unwrap();
+        let _ = builder.build_memcpy(rhs_dest, 1, rhs_data, 1, rhs_length);
+        let _ = builder.build_return(Some(&string_ptr));
+    }
+
+    fn create_from_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i8_type = context.i8_type();
+        let i64_type = context.i64_type();
+        let f64_type = context.f64_type();
+        let string_ptr_type = module
+            .get_struct_type("String")
+            .ptr_type(AddressSpace::default());
+
+        // integers
+        let func = string_ptr_type.fn_type(&[i64_type.into()], false);
+        let func = module.add_function("__string_from_int64", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let buffer = builder
+            .build_array_alloca(i8_type, i64_type.const_int(32, false), "")
+            .unwrap();
+        let format_string = builder
+            .build_global_string_ptr("%lld", "")
+            .unwrap()
+            .as_pointer_value();
+        let length = builder
+            .build_call(
+                module.get_function("snprintf"),
+                &[
+                    buffer.into(),
+                    i64_type.const_int(32, false).into(),
+                    format_string.into(),
+                    func.get_nth_method_input(0),
+                ],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let length = builder.build_int_s_extend(length, i64_type, "").unwrap();
+        let string_ptr = builder
+            .build_call(
+                module.get_function("__string_new"),
+                &[buffer.into(), length.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        let _ = builder.build_return(Some(&string_ptr));
+
+        // floats are formatted like julia by __float_chars
+        let func = string_ptr_type.fn_type(&[f64_type.into()], false);
+        let func = module.add_function("__string_from_float64", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let buffer = builder
+            .build_array_alloca(i8_type, i64_type.const_int(32, false), "")
+            .unwrap();
+        let length = builder
+            .build_call(
+                module.get_function("__float_chars"),
+                &[
+                    func.get_nth_method_input(0),
+                    context.bool_type().const_zero().into(),
+                    buffer.into(),
+                ],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        let string_ptr = builder
+            .build_call(
+                module.get_function("__string_new"),
+                &[buffer.into(), length.into()],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        let _ = builder.build_return(Some(&string_ptr));
+    }
+
+    /// writes the shortest decimal that round trips to a buffer of at
+    /// least 32 bytes and returns its length. Like julia there is always
+    /// a decimal point, exponents are only used outside of 1e-5 to 1e6,
+    /// e.g. 100000.0 and 1.0e6, and Float32s end in f0, e.g. 1.5f0
+    fn create_float_chars_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let bool_type = context.bool_type();
+        let i8_type = context.i8_type();
+        let i32_type = context.i32_type();
+        let i64_type = context.i64_type();
+        let f32_type = context.f32_type();
+        let f64_type = context.f64_type();
+        let i8_ptr_type = i8_type.ptr_type(AddressSpace::default());
+        let size = i64_type.const_int(32, false);
+
+        let func = i64_type.fn_type(
+            &[f64_type.into(), bool_type.into(), i8_ptr_type.into()],
+            false,
+        );
+        let func = module.add_function("__float_chars", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let special = context.append_basic_block(func, "special");
+        let finite = context.append_basic_block(func, "finite");
+        let check = context.append_basic_block(func, "check");
+        let done = context.append_basic_block(func, "done");
+        let fixed = context.append_basic_block(func, "fixed");
+        let exponent = context.append_basic_block(func, "exponent");
+        builder.position_at_end(entry);
+
+        let val = func.get_nth_method_input(0).into_float_value();
+        let is_float32 = func.get_nth_method_input(1).into_int_value();
+        let buffer = func.get_nth_method_input(2).into_pointer_value();
+        let global_string = |x: &str| {
+            builder
+                .build_global_string_ptr(x, "")
+                .unwrap()
+                .as_pointer_value()
+        };
+        let snprintf =
+            |buffer: PointerValue<'a>, format: &str, args: &[BasicMetadataValueEnum<'a>]| {
+                let mut func_args: Vec<BasicMetadataValueEnum<'a>> =
+                    vec![buffer.into(), size.into(), global_string(format).into()];
+                func_args.extend_from_slice(args);
+                let length = builder
+                    .build_call(module.get_function("snprintf"), &func_args, "")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_int_value();
+                builder.build_int_s_extend(length, i64_type, "").unwrap()
+            };
+        let is_nan = builder
+            .build_float_compare(FloatPredicate::UNO, val, val, "")
+            .unwrap();
+        let is_inf = builder
+            .build_float_compare(
+                FloatPredicate::OEQ,
+                val,
+                f64_type.const_float(f64::INFINITY),
+                "",
+            )
+            .unwrap();
+        let is_ninf = builder
+            .build_float_compare(
+                FloatPredicate::OEQ,
+                val,
+                f64_type.const_float(f64::NEG_INFINITY),
+                "",
+            )
+            .unwrap();
+        let is_special = builder.build_or(is_nan, is_inf, "").unwrap();
+        let is_special = builder.build_or(is_special, is_ninf, "").unwrap();
+        let _ = builder.build_conditional_branch(is_special, special, finite);
+
+        // NaN, Inf and -Inf, with a 32 suffix for Float32
+        builder.position_at_end(special);
+        let name = builder
+            .build_select(is_inf, global_string("Inf"), global_string("-Inf"), "")
+            .unwrap();
+        let name = builder
+            .build_select(is_nan, global_string("NaN").into(), name, "")
+            .unwrap();
+        let suffix = builder
+            .build_select(is_float32, global_string("32"), global_string(""), "")
+            .unwrap();
+        let length = snprintf(buffer, "%s%s", &[name.into(), suffix.into()]);
+        let _ = builder.build_return(Some(&length));
+
+        // find the fewest significant digits that parse back to val
+        builder.position_at_end(finite);
+        let _ = builder.build_unconditional_branch(check);
+
+        builder.position_at_end(check);
+        let digits = builder.build_phi(i32_type, "").unwrap();
+        let digits_val = digits.as_basic_value().into_int_value();
+        let precision = builder
+            .build_int_sub(digits_val, i32_type.const_int(1, false), "")
+            .unwrap();
+        let _ = snprintf(buffer, "%.*e", &[precision.into(), val.into()]);
+        let parsed = builder
+            .build_call(
+                module.get_function("strtod"),
+                &[
+                    buffer.into(),
+                    i8_ptr_type
+                        .ptr_type(AddressSpace::default())
+                        .const_null()
+                        .into(),
+                ],
+                "",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_float_value();
+        // Float32s only have to round trip at single precision
+        let rounded = builder.build_float_trunc(parsed, f32_type, "").unwrap();
+        let rounded = builder.build_float_ext(rounded, f64_type, "").unwrap();
+        let parsed = builder
+            .build_select(is_float32, rounded, parsed, "")
+            .unwrap()
+            .into_float_value();
+        let round_trips = builder
+            .build_float_compare(FloatPredicate::OEQ, parsed, val, "")
+            .unwrap();
+        let next_digits = builder
+            .build_int_add(digits_val, i32_type.const_int(1, false), "")
+            .unwrap();
+        let is_max = builder
+            .build_int_compare(
+                IntPredicate::SGT,
+                next_digits,
+                i32_type.const_int(17, false),
+                "",
+            )
+            .unwrap();
+        let is_done = builder.build_or(round_trips, is_max, "").unwrap();
+        digits.add_incoming(&[
+            (&i32_type.const_int(1, false), finite),
+            (&next_digits, check),
+        ]);
+        let _ = builder.build_conditional_branch(is_done, done, check);
+
+        // the decimal exponent, buffer holds e.g. 1.5e+06
+        builder.position_at_end(done);
+        let call = |name: &str, args: &[BasicMetadataValueEnum<'a>]| {
+            builder
+                .build_call(module.get_function(name), args, "")
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+        };
+        let e_ptr = call(
+            "strchr",
+            &[buffer.into(), i32_type.const_int(b'e' as u64, false).into()],
+        )
+        .into_pointer_value();
+        let digits_ptr =
+            unsafe { builder.build_in_bounds_gep(e_ptr, &[i64_type.const_int(1, false)], "") }
+                .unwrap();
+        let exp = call("atoi", &[digits_ptr.into()]).into_int_value();
+        let is_small = builder
+            .build_int_compare(
+                IntPredicate::SGE,
+                exp,
+                i32_type.const_int(-4i64 as u64, true),
+                "",
+            )
+            .unwrap();
+        let is_large = builder
+            .build_int_compare(IntPredicate::SGT, exp, i32_type.const_int(5, false), "")
+            .unwrap();
+        let is_large = builder.build_not(is_large, "").unwrap();
+        let is_fixed = builder.build_and(is_small, is_large, "").unwrap();
+        let one = i32_type.const_int(1, false);
+        let _ = builder.build_conditional_branch(is_fixed, fixed, exponent);
+
+        // at least one decimal, e.g. 100.0 not 100
+        builder.position_at_end(fixed);
+        let decimals = builder.build_int_sub(precision, exp, "").unwrap();
+        let is_integral = builder
+            .build_int_compare(IntPredicate::SLT, decimals, one, "")
+            .unwrap();
+        let decimals = builder
+            .build_select(is_integral, one, decimals, "")
+            .unwrap();
+        let length = snprintf(buffer, "%.*f", &[decimals.into(), val.into()]);
+        let end = unsafe { builder.build_in_bounds_gep(buffer, &[length], "") }.unwrap();
+        let suffix = builder
+            .build_select(is_float32, global_string("f0"), global_string(""), "")
+            .unwrap();
+        let suffix_length = snprintf(end, "%s", &[suffix.into()]);
+        let length = builder.build_int_add(length, suffix_length, "").unwrap();
+        let _ = builder.build_return(Some(&length));
+
+        // e.g. 1.0e6 or 1.5f-7, without the sign and padding of %e. The
+        // digits are the ones found above, printing them again with an
+        // extra decimal would round, e.g. 5.0e-324 to 4.9e-324
+        builder.position_at_end(exponent);
+        let is_integral = builder
+            .build_int_compare(IntPredicate::SLT, precision, one, "")
+            .unwrap();
+        let decimal = builder
+            .build_select(is_integral, global_string(".0"), global_string(""), "")
+            .unwrap();
+        let decimal_length = snprintf(e_ptr, "%s", &[decimal.into()]);
+        let e_ptr = unsafe { builder.build_in_bounds_gep(e_ptr, &[decimal_length], "") }.unwrap();
+        let marker = builder
+            .build_select(
+                is_float32,
+                i32_type.const_int(b'f' as u64, false),
+                i32_type.const_int(b'e' as u64, false),
+                "",
+            )
+            .unwrap();
+        let exp_length = snprintf(e_ptr, "%c%d", &[marker.into(), exp.into()]);
+        let mantissa_length = builder.build_ptr_diff(e_ptr, buffer, "").unwrap();
+        let length = builder
+            .build_int_add(mantissa_length, exp_length, "")
+            .unwrap();
+        let _ = builder.build_return(Some(&length));
+    }
+}
+
+impl Default for FarneseString {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> FarneseInternal<'a> for FarneseString {
+    fn bootstrap(&self, module: &Module<'a>) {
+        self.create_opaque_type(module);
+        self.create_new_method(module);
+        self.create_get_methods(module);
+        self.create_cmp_method(module);
+        self.create_concat_method(module);
+        self.create_float_chars_method(module);
+        self.create_from_methods(module);
+    }
+
+    fn create_opaque_type(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let opaque_type = context.opaque_struct_type("String");
+        opaque_type.set_body(
+            &[
+                context.i8_type().ptr_type(AddressSpace::default()).into(),
+                context.i64_type().into(),
+            ],
+            false,
+        );
+    }
+
+    /// copies length bytes into a freshly allocated String
+    fn create_new_method(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i8_ptr_type = context.i8_type().ptr_type(AddressSpace::default());
+        let string_ptr_type = module
+            .get_struct_type("String")
+            .ptr_type(AddressSpace::default());
+
+        let func = string_ptr_type.fn_type(&[i8_ptr_type.into(), context.i64_type().into()], false);
+        let func = module.add_function("__string_new", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        builder.position_at_end(entry);
+
+        let bytes = func.get_nth_method_input(0).into_pointer_value();
+        let length = func.get_nth_method_input(1).into_int_value();
+        let (string_ptr, data) = Self::emit_ir_alloc(&builder, module, length);
+        let _ = builder.build_memcpy(data, 1, bytes, 1, length);
+        let _ = builder.build_return(Some(&string_ptr));
+    }
+
+    fn create_get_methods(&self, module: &Module<'a>) {
+        let context = module.get_context();
+        let builder = context.create_builder();
+        let i64_type = context.i64_type();
+        let string_ptr_type = module
+            .get_struct_type("String")
+            .ptr_type(AddressSpace::default());
+
+        // create length function, counts every byte that is
+        // not a UTF-8 continuation byte (0b10xxxxxx)
+        let func = i64_type.fn_type(&[string_ptr_type.into()], false);
+        let func = module.add_function("__string_length", func, None);
+        let entry = context.append_basic_block(func, "entry");
+        let check = context.append_basic_block(func, "check");
+        let body = context.append_basic_block(func, "body");
+        let done = context.append_basic_block(func, "done");
+        builder.position_at_end(entry);
+
+        let string_ptr = func.get_nth_method_input(0);
+        let length = string_ptr.load_nth_field(&builder, 1).into_int_value();
+        let data = string_ptr.load_nth_field(&builder, 0).into_pointer_value();
+        let _ = builder.build_unconditional_branch(check);
+
+        builder.position_at_end(check);
+        let n = builder.build_phi(i64_type, "").unwrap();
+        let count = builder.build_phi(i64_type, "").unwrap();
+        let n_val = n.as_basic_value().into_int_value();
+        let count_val = count.as_basic_value().into_int_value();
+        let is_done = builder
+            .build_int_compare(IntPredicate::SGE, n_val, length, "")
+            .unwrap();
+        let _ = builder.build_conditional_branch(is_done, done, body);
+
+        builder.position_at_end(body);
+        let byte_ptr = unsafe { builder.build_in_bounds_gep(data, &[n_val], "") }.unwrap();
+        let byte = builder.build_load(byte_ptr, "").unwrap().into_int_value();
+        let masked = builder
+            .build_and(byte, context.i8_type().const_int(0xc0, false), "")
+            .unwrap();
+        let is_start = builder
+            .build_int_compare(
+                IntPredicate::NE,
+                masked,
+                context.i8_type().const_int(0x80, false),
+                "",
+            )
+            .unwrap();
+        let is_start = builder.build_int_z_extend(is_start, i64_type, "").unwrap();
+        let next_count = builder.build_int_add(count_val, is_start, "").unwrap();
+        let next_n = builder
+            .build_int_add(n_val, i64_type.const_int(1, false), "")
+            .unwrap();
+        let _ = builder.build_unconditional_branch(check);
+
+        n.add_incoming(&[(&i64_type.const_zero(), entry), (&next_n, body)]);
+        count.add_incoming(&[(&i64_type.const_zero(), entry), (&next_count, body)]);
+
+        builder.position_at_end(done);
+        let _ = builder.build_return(Some(&count_val));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LLVMPrintf;
+    use crate::test_utils::TestHelper;
+    use inkwell::context::Context;
+    use inkwell::values::BasicMetadataValueEnum;
+
+    fn printf<'a>(tester: &TestHelper<'a, '_>, val: BasicMetadataValueEnum<'a>, name: &str) {
+        let datatype = tester.module.get_type(name).clone();
+        let _ = (val, datatype).emit_ir_printf(tester.builder, &tester.module);
+    }
+
+    #[test]
+    fn test_string_concat() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_string_concat", &builder, &context);
+        tester.start();
+
+        let string = FarneseString::new();
+        let lhs = string.emit_ir_literal(&tester.module, "Hello, ");
+        let rhs = string.emit_ir_literal(&tester.module, "λ!");
+        let result = string.emit_ir_concat(&builder, &tester.module, lhs, rhs);
+        printf(&tester, result.into(), "String");
+        tester.end();
+
+        assert_eq!("Hello, λ!", tester.run());
+    }
+
+    #[test]
+    fn test_string_length() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_string_length", &builder, &context);
+        tester.start();
+
+        let string = FarneseString::new();
+        let val = string.emit_ir_literal(&tester.module, "aλb");
+        let length = string.emit_ir_length(&builder, &tester.module, val);
+        let ncodeunits = string.emit_ir_ncodeunits(&builder, val);
+        printf(&tester, length.into(), "Int64");
+        printf(&tester, ncodeunits.into(), "Int64");
+        tester.end();
+
+        assert_eq!("34", tester.run());
+    }
+
+    #[test]
+    fn test_string_cmp() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_string_cmp", &builder, &context);
+        tester.start();
+
+        let string = FarneseString::new();
+        let abc = string.emit_ir_literal(&tester.module, "abc");
+        let abd = string.emit_ir_literal(&tester.module, "abd");
+        let ab = string.emit_ir_literal(&tester.module, "ab");
+        for (lhs, rhs) in [(abc, abd), (abd, abc), (abc, abc), (ab, abc)] {
+            let result = string.emit_ir_cmp(&builder, &tester.module, lhs, rhs);
+            let sign = builder
+                .build_int_compare(
+                    IntPredicate::SLT,
+                    result,
+                    result.get_type().const_zero(),
+                    "",
+                )
+                .unwrap();
+            let is_zero = builder
+                .build_int_compare(IntPredicate::EQ, result, result.get_type().const_zero(), "")
+                .unwrap();
+            let sign = builder
+                .build_int_z_extend(sign, context.i64_type(), "")
+                .unwrap();
+            let is_zero = builder
+                .build_int_z_extend(is_zero, context.i64_type(), "")
+                .unwrap();
+            printf(&tester, sign.into(), "Int64");
+            printf(&tester, is_zero.into(), "Int64");
+        }
+        tester.end();
+
+        assert_eq!("10000110", tester.run());
+    }
+
+    #[test]
+    fn test_string_from_numbers() {
+        let context = Context::create();
+        let builder = context.create_builder();
+        let tester = TestHelper::new("test_string_from_numbers", &builder, &context);
+        tester.start();
+
+        let string = FarneseString::new();
+        let space = string.emit_ir_literal(&tester.module, " ");
+        let mut result = string.emit_ir_from_int64(
+            &builder,
+            &tester.module,
+            context.i64_type().const_int(-42i64 as u64, true),
+        );
+        for x in [1.0, 0.1, 1.0 / 3.0, 2.5e-8, 5e-324, 1e-5, 1e16] {
+            let val = string.emit_ir_from_float64(
+                &builder,
+                &tester.module,
+                context.f64_type().const_float(x),
+            );
+            result = string.emit_ir_concat(&builder, &tester.module, result, space);
+            result = string.emit_ir_concat(&builder, &tester.module, result, val);
+        }
+        printf(&tester, result.into(), "String");
+        tester.end();
+
+        assert_eq!(
+            "-42 1.0 0.1 0.3333333333333333 2.5e-8 5.0e-324 1.0e-5 1.0e16",
+            tester.run()
+        );
+    }
+}
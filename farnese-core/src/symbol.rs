@@ -1,8 +1,9 @@
-use super::{FarneseInternal, LLVMAlloca, Module};
+use super::{DataType, FarneseInternal, LLVMAlloca, LLVMPrintf, Module};
 // use dashmap::DashMap;
 use inkwell::AddressSpace;
 use inkwell::builder::Builder;
-use inkwell::values::PointerValue;
+use inkwell::module::Linkage;
+use inkwell::values::{BasicMetadataValueEnum, CallSiteValue, PointerValue, StructValue};
 use std::collections::HashMap;
 use std::collections::hash_map::DefaultHasher;
 use std::ffi::CString;
@@ -54,6 +55,36 @@ impl<'a> Symbol {
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// constant Symbol struct, its string lives in a private global
+    pub fn emit_ir_const_value(&self, module: &Module<'a>) -> StructValue<'a> {
+        let context = module.get_context();
+        let i32_type = context.i32_type();
+        let bytes = context.const_string(self.name.as_bytes(), true);
+        let bytes_global = module.module().add_global(bytes.get_type(), None, "sym");
+        bytes_global.set_initializer(&bytes);
+        bytes_global.set_constant(true);
+        bytes_global.set_linkage(Linkage::Private);
+        let sym_str = unsafe {
+            bytes_global
+                .as_pointer_value()
+                .const_in_bounds_gep(&[i32_type.const_zero(), i32_type.const_zero()])
+        };
+        let hash = context.i64_type().const_int(self.hash as u64, true);
+        module
+            .get_struct_type("Symbol")
+            .const_named_struct(&[hash.into(), sym_str.into()])
+    }
+
+    /// pointer to a private constant global holding the symbol
+    pub fn emit_ir_constant(&self, module: &Module<'a>) -> PointerValue<'a> {
+        let value = self.emit_ir_const_value(module);
+        let global = module.add_global(value.get_type(), None, "sym");
+        global.set_initializer(&value);
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+        global.as_pointer_value()
+    }
 }
 
 ///
@@ -78,34 +109,7 @@ impl<'a> FarneseInternal<'a> for Symbol {
         );
     }
 
-    fn create_datatype(&self, module: &Module<'a>) {
-        let context = module.get_context();
-        let builder = context.create_builder();
-        let sym = Symbol::new("Symbol");
-        let datatype = module.get_struct_type("DataType");
-        let datatype_ptr = datatype.ptr_type(AddressSpace::default());
-        let func = datatype_ptr.fn_type(&[], false);
-        let func = module.add_function("__DataType_Symbol", func, None);
-        let entry = context.append_basic_block(func, "entry");
-        builder.position_at_end(entry);
-
-        let sym_ptr = sym.emit_ir_alloca(&builder, &module);
-        let supertype_ptr = module.get_global("Any").as_pointer_value();
-        let func_result = builder
-            .build_call(
-                module.get_function("Datatype"),
-                &[sym_ptr.into(), supertype_ptr.into()],
-                "",
-            )
-            .unwrap()
-            .try_as_basic_value()
-            .left()
-            .unwrap()
-            .into_pointer_value();
-        let _ = builder.build_return(Some(&func_result));
-    }
-
-    fn create_get_methods(&self, module: &Module<'a>) {}
+    fn create_get_methods(&self, _module: &Module<'a>) {}
 
     fn create_new_method(&self, _module: &Module<'a>) {}
 }
@@ -166,6 +170,22 @@ impl<'a, 'b> LLVMAlloca<'a, 'b> for Symbol {
     }
 }
 
+impl<'a, 'b> LLVMPrintf<'a, 'b> for Symbol {
+    fn emit_ir_printf(&self, builder: &'b Builder<'a>, module: &Module<'a>) -> CallSiteValue<'a> {
+        let ptr: BasicMetadataValueEnum<'a> = self.emit_ir_alloca(builder, module).into();
+        let datatype = DataType::new(
+            Symbol::new("Symbol"),
+            Symbol::new("Any"),
+            false,
+            false,
+            false,
+            vec![],
+            Box::default(),
+        );
+        (ptr, datatype).emit_ir_printf(builder, module)
+    }
+}
+
 #[derive(Clone)]
 pub struct SymbolTable {
     symbols: Arc<RwLock<HashMap<String, Symbol>>>,
@@ -276,7 +296,6 @@ impl SymbolTable {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Core;
     use inkwell::AddressSpace;
     use inkwell::context::Context;
     use std::ffi::CString;
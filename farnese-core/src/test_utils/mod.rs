@@ -1,4 +1,4 @@
-use crate::{Core, Module};
+use crate::{Core, DataType, LLVMType, Module, Symbol};
 use core::ffi::c_char;
 use inkwell::OptimizationLevel;
 use inkwell::builder::Builder;
@@ -21,7 +21,21 @@ impl<'a, 'b> TestHelper<'a, 'b> {
     pub fn new(name: &str, builder: &'b Builder<'a>, context: &'a Context) -> Self {
         let core = Core::new(&context).bootstrap();
         let mut module = Module::new(&context, name);
-        module.link(&core);
+        module.link(&core).unwrap();
+        // the number hierarchy Base defines, the types of Core point to it
+        for (name, supertype) in [
+            ("Number", "Any"),
+            ("Real", "Number"),
+            ("AbstractFloat", "Real"),
+            ("Integer", "Real"),
+            ("Signed", "Integer"),
+            ("Unsigned", "Integer"),
+        ] {
+            let mut datatype = DataType::new_abstract_type(name, supertype);
+            datatype.module = Symbol::new("Base");
+            datatype.emit_ir_type(&module);
+            module.insert_type(datatype);
+        }
         Self {
             builder: &builder,
             module: module,
@@ -1,27 +1,60 @@
+use std::fmt;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Operator {
     // Assignment,
     Divide,
     Equal,
     EqualEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
     Minus,
     Multiply,
+    NotEqual,
     Plus,
     SubType,
 }
 
+/// the operator as written in source, e.g. + for Plus
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            Operator::Divide => "/",
+            Operator::Equal => "==",
+            Operator::EqualEqual => "===",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Minus => "-",
+            Operator::Multiply => "*",
+            Operator::NotEqual => "!=",
+            Operator::Plus => "+",
+            Operator::SubType => "<:",
+        };
+        write!(f, "{}", op)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Primitive {
+    Bool(bool),
     Char(char),
     Float32(f32),
     Float64(f64),
     Int16(i16),
     Int32(i32),
     Int64(i64),
+    Int128(i128),
+    Nothing,
     String(String),
+    UInt8(u8),
     UInt16(u16),
     UInt32(u32),
     UInt64(u64),
+    UInt128(u128),
 }
 
 // note this is not the Symbol in core
@@ -29,6 +62,14 @@ pub enum Primitive {
 // converted to core::Symbol downstream
 pub type Symbol = String;
 
+/// a possibly relative path to a module, e.g. ..Foo.Bar has
+/// a level of 2 and names [Foo, Bar]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModulePath {
+    pub level: usize,
+    pub names: Vec<Symbol>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Node {
     AbstractType {
@@ -36,8 +77,13 @@ pub enum Node {
         // params: Box<Node>,
         supertype: Symbol,
     },
+    ArrayExpr {
+        elements: Box<Vec<Node>>,
+    },
     AssignmentExpr {
         identifier: Symbol,
+        /// the declared type in x::T = value
+        datatype: Option<Symbol>,
         value: Box<Node>,
     },
     BinaryExpr {
@@ -45,6 +91,16 @@ pub enum Node {
         lhs: Box<Node>,
         rhs: Box<Node>,
     },
+    // f.(x), x .+ y or x .= y, the call, operator or assignment
+    // is applied elementwise and nested ones fuse into one loop
+    Broadcast {
+        expr: Box<Node>,
+    },
+    // expressions evaluated in order, the last one is the value,
+    // e.g. begin ... end or the code a macro expands to
+    Block {
+        exprs: Box<Vec<Node>>,
+    },
     ConstExpr {
         expr: Box<Node>,
     },
@@ -74,15 +130,54 @@ pub enum Node {
         // TODO elseif
         else_block: Box<Vec<Node>>,
     },
+    // global x = 1
+    GlobalExpr {
+        expr: Box<Node>,
+    },
+    // import Foo: bar or import Foo.bar
+    ImportExpr {
+        path: ModulePath,
+        names: Vec<Symbol>,
+    },
+    IndexExpr {
+        array: Box<Node>,
+        indices: Box<Vec<Node>>,
+    },
+    // $x in a quote
+    Interpolation {
+        expr: Box<Node>,
+    },
+    // $x = value in a quote, the target is spliced in before it
+    // becomes an assignment
+    InterpolatedAssignment {
+        target: Box<Node>,
+        datatype: Option<Symbol>,
+        value: Box<Node>,
+    },
+    // let x = 1, y = 2 ... end, the bindings are assignments
+    Let {
+        bindings: Box<Vec<Node>>,
+        exprs: Box<Vec<Node>>,
+    },
     Macro {
         name: Symbol,
         args: Box<Vec<Node>>,
         body: Box<Vec<Node>>,
     },
+    // @name(a, b) or @name a b, the name is stored without the @
+    MacroCall {
+        name: Symbol,
+        args: Box<Vec<Node>>,
+    },
     MethodCall {
         name: Symbol,
         args: Box<Vec<Node>>,
     },
+    // a, b = b, a, the targets are variables, fields or elements
+    MultipleAssignment {
+        targets: Box<Vec<Node>>,
+        values: Box<Vec<Node>>,
+    },
     Module {
         name: Symbol,
         exprs: Box<Vec<Node>>,
@@ -97,6 +192,15 @@ pub enum Node {
         supertype: Symbol,
         bits: u32,
     },
+    SetIndexExpr {
+        array: Box<Node>,
+        indices: Box<Vec<Node>>,
+        value: Box<Node>,
+    },
+    // :x, :(a + b) or quote ... end, which quotes a Block
+    Quote {
+        exprs: Box<Vec<Node>>,
+    },
     StructField {
         name: Symbol,
         field_type: Symbol,
@@ -105,13 +209,27 @@ pub enum Node {
         name: Symbol,
         // generics: Vec<Symbol>,
         supertype: Symbol,
+        is_mutable: bool,
         field_names: Vec<Symbol>,
         field_types: Vec<Symbol>,
     },
     SuperType(Symbol),
     Symbol(Symbol),
+    // try ... catch e ... finally ... end
+    TryExpr {
+        try_block: Box<Vec<Node>>,
+        /// the name the exception is bound to in the catch block
+        catch_name: Option<Symbol>,
+        catch_block: Option<Box<Vec<Node>>>,
+        finally_block: Option<Box<Vec<Node>>>,
+    },
     UnaryExpr {
         op: Operator,
         child: Box<Node>,
     },
+    // using Foo or using Foo: bar
+    UsingExpr {
+        path: ModulePath,
+        names: Vec<Symbol>,
+    },
 }
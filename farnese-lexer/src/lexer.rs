@@ -1,5 +1,6 @@
-use crate::ast::{Node, Operator, Primitive, Symbol};
+use crate::ast::{ModulePath, Node, Operator, Primitive, Symbol};
 use crate::parser::{FarneseParser, Rule};
+use pest::Parser;
 use std::fs;
 
 pub fn parse_file(file_name: &str) -> std::result::Result<Vec<Node>, pest::error::Error<Rule>> {
@@ -42,7 +43,7 @@ pub fn create_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
                 .filter(|p| matches!(p.as_rule(), Rule::SuperType))
                 .next();
             let supertype = match supertype {
-                Some(x) => x.as_str().to_string(),
+                Some(x) => x.clone().into_inner().next().unwrap().as_str().to_string(),
                 None => "Any".to_string(),
             };
             Node::AbstractType {
@@ -50,41 +51,133 @@ pub fn create_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
                 supertype: supertype,
             }
         }
+        Rule::ArrayExpr => {
+            let elements = pair
+                .clone()
+                .into_inner()
+                .map(|p| create_ast(&p))
+                .collect::<Vec<_>>();
+            Node::ArrayExpr {
+                elements: Box::new(elements),
+            }
+        }
         Rule::AssignmentExpr => {
             let terms: Vec<_> = pair.clone().into_inner().collect();
-            let name = terms[0].as_str().to_string();
-            let val = create_ast(&terms[1]);
-            Node::AssignmentExpr {
-                identifier: name,
-                value: Box::new(val),
+            let datatype = terms
+                .iter()
+                .find(|x| x.as_rule() == Rule::TypeExpr)
+                .map(|x| x.as_str().to_string());
+            let n = terms
+                .iter()
+                .position(|x| x.as_rule() == Rule::AssignmentOperator)
+                .unwrap();
+            let target = create_ast(&terms[0]);
+            let mut val = create_ast(&terms[n + 1]);
+            // x .+= 1 is x .= x .+ 1
+            let (is_dotted, op) = match terms[n].as_str().strip_prefix('.') {
+                Some(op) => (true, op),
+                None => (false, terms[n].as_str()),
+            };
+            let dotted = |expr: Node| match is_dotted {
+                true => Node::Broadcast {
+                    expr: Box::new(expr),
+                },
+                false => expr,
+            };
+            // x += 1 is x = x + 1
+            let op = match op {
+                "+=" => Some(Operator::Plus),
+                "-=" => Some(Operator::Minus),
+                "*=" => Some(Operator::Multiply),
+                "/=" => Some(Operator::Divide),
+                _ => None,
+            };
+            if let Some(op) = op {
+                val = dotted(Node::BinaryExpr {
+                    op,
+                    lhs: Box::new(target.clone()),
+                    rhs: Box::new(val),
+                });
             }
+            dotted(match target {
+                Node::IndexExpr { array, indices } => Node::SetIndexExpr {
+                    array,
+                    indices,
+                    value: Box::new(val),
+                },
+                target @ Node::Interpolation { .. } => Node::InterpolatedAssignment {
+                    target: Box::new(target),
+                    datatype,
+                    value: Box::new(val),
+                },
+                _ => Node::AssignmentExpr {
+                    identifier: terms[0].as_str().to_string(),
+                    datatype,
+                    value: Box::new(val),
+                },
+            })
         }
-        Rule::BinaryExpr => {
-            let terms: Vec<_> = pair.clone().into_inner().collect();
-            let lhs = create_ast(&terms[0]);
-            let op = match terms[1].as_str() {
-                "/" => Operator::Divide,
-                "==" => Operator::Equal,
-                "===" => Operator::EqualEqual,
-                "-" => Operator::Minus,
-                "*" => Operator::Multiply,
-                "+" => Operator::Plus,
-                _ => panic!("wtf {:?}", terms),
+        Rule::MultipleAssignment => {
+            let parts: Vec<_> = pair.clone().into_inner().collect();
+            assert!(
+                parts[1].as_str() == "=",
+                "syntax: invalid multiple assignment location"
+            );
+            let nodes = |x: &pest::iterators::Pair<Rule>| {
+                x.clone().into_inner().map(|x| create_ast(&x)).collect()
             };
-            let rhs = create_ast(&terms[2]);
-            Node::BinaryExpr {
-                op: op,
-                lhs: Box::new(lhs),
-                rhs: Box::new(rhs),
+            Node::MultipleAssignment {
+                targets: Box::new(nodes(&parts[0])),
+                values: Box::new(nodes(&parts[2])),
             }
         }
+        Rule::BinaryExpr => {
+            let terms: Vec<_> = pair.clone().into_inner().collect();
+            let operands = terms.iter().step_by(2).map(|p| create_ast(p));
+            let ops = terms
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .map(|p| {
+                    // a leading dot applies the operator elementwise
+                    let (is_dotted, op) = match p.as_str().strip_prefix('.') {
+                        Some(op) => (true, op),
+                        None => (false, p.as_str()),
+                    };
+                    let op = match op {
+                        "/" => Operator::Divide,
+                        "==" => Operator::Equal,
+                        "===" => Operator::EqualEqual,
+                        ">" => Operator::Greater,
+                        ">=" => Operator::GreaterEqual,
+                        "<" => Operator::Less,
+                        "<=" => Operator::LessEqual,
+                        "-" => Operator::Minus,
+                        "*" => Operator::Multiply,
+                        "!=" => Operator::NotEqual,
+                        "+" => Operator::Plus,
+                        _ => panic!("wtf {:?}", terms),
+                    };
+                    (op, is_dotted)
+                })
+                .collect::<Vec<_>>();
+            let mut operands = operands.collect::<Vec<_>>().into_iter();
+            let mut ops = ops.into_iter().peekable();
+            let lhs = operands.next().unwrap();
+            create_binary_ast(lhs, &mut operands, &mut ops, 0)
+        }
         Rule::BinaryOperator => {
             let op = match pair.as_str() {
                 "/" => Operator::Divide,
                 "==" => Operator::Equal,
                 "===" => Operator::EqualEqual,
+                ">" => Operator::Greater,
+                ">=" => Operator::GreaterEqual,
+                "<" => Operator::Less,
+                "<=" => Operator::LessEqual,
                 "-" => Operator::Minus,
                 "*" => Operator::Multiply,
+                "!=" => Operator::NotEqual,
                 "+" => Operator::Plus,
                 "<:" => Operator::SubType,
                 _ => todo!(),
@@ -110,7 +203,11 @@ pub fn create_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
                     Rule::ExportLine => {
                         let line_exports = export
                             .into_inner()
-                            .map(|p| create_ast(&p))
+                            .map(|p| match p.as_rule() {
+                                // macros are exported by their name with the @
+                                Rule::MacroIdentifier => Node::Symbol(p.as_str().to_string()),
+                                _ => create_ast(&p),
+                            })
                             .collect::<Vec<_>>();
                         exports.extend(line_exports);
                     }
@@ -244,65 +341,68 @@ pub fn create_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
                 Node::Empty
             }
         }
-        Rule::Identifier => {
-            let name = pair.as_str().to_string();
-            Node::Symbol(name)
-        }
+        Rule::Identifier => match pair.as_str() {
+            "false" => Node::Primitive(Primitive::Bool(false)),
+            "nothing" => Node::Primitive(Primitive::Nothing),
+            "true" => Node::Primitive(Primitive::Bool(true)),
+            name if name.starts_with(':') => create_quoted_symbol(name),
+            name => Node::Symbol(name.to_string()),
+        },
+        // elseif nests another if in the else block
         Rule::IfExpr => {
+            let mut else_block = Vec::new();
+            for part in pair.clone().into_inner().rev() {
+                // the else block is the only one without a condition
+                let mut parts = part.into_inner().peekable();
+                let condition = parts
+                    .next_if(|x| x.as_rule() == Rule::ConditionExpr)
+                    .map(|x| create_ast(&x.into_inner().next().unwrap()));
+                let block = parts
+                    .map(|x| create_ast(&x))
+                    .filter(|x| *x != Node::Empty)
+                    .collect::<Vec<_>>();
+                else_block = match condition {
+                    Some(condition) => vec![Node::IfExpr {
+                        condition: Box::new(condition),
+                        if_block: Box::new(block),
+                        else_block: Box::new(else_block),
+                    }],
+                    None => block,
+                };
+            }
+            else_block.pop().unwrap()
+        }
+        Rule::GlobalExpr => {
+            let expr = pair.clone().into_inner().next().unwrap();
+            Node::GlobalExpr {
+                expr: Box::new(create_ast(&expr)),
+            }
+        }
+        Rule::ImportExpr => {
+            let (mut path, mut names) = create_import_parts(pair);
+            // import Foo.bar imports bar from Foo
+            if names.is_empty() && path.names.len() > 1 {
+                names.push(path.names.pop().unwrap());
+            }
+            Node::ImportExpr { path, names }
+        }
+        Rule::IndexExpr => {
             let parts: Vec<_> = pair.clone().into_inner().collect();
-            let if_block = parts
+            let array = create_ast(&parts[0]);
+            let indices = parts
                 .iter()
-                .filter(|p| matches!(p.as_rule(), Rule::IfBlock))
+                .skip(1)
+                .map(|p| create_ast(p))
                 .collect::<Vec<_>>();
-
-            assert!(if_block.len() == 1);
-            let (condition, if_block) = match if_block[0].as_rule() {
-                Rule::IfBlock => {
-                    let block = if_block[0].clone().into_inner().collect::<Vec<_>>();
-                    let condition = block
-                        .iter()
-                        .filter(|p| matches!(p.as_rule(), Rule::ConditionExpr))
-                        .map(|p| {
-                            let parts = p.clone().into_inner().collect::<Vec<_>>();
-                            assert!(parts.len() == 1);
-                            create_ast(&parts[0])
-                        })
-                        .collect::<Vec<_>>();
-                    let asts = block
-                        .iter()
-                        .filter(|p| matches!(p.as_rule(), Rule::Expr))
-                        .map(|p| create_ast(&p))
-                        .collect::<Vec<_>>();
-                    (condition, asts)
-                }
-                _ => panic!(),
-            };
-            assert!(condition.len() == 1);
-            // TODO else if block
-
-            let else_block = parts
-                .iter()
-                .filter(|p| matches!(p.as_rule(), Rule::ElseBlock))
-                .collect::<Vec<_>>();
-            let else_block = match else_block[0].as_rule() {
-                Rule::ElseBlock => {
-                    let block = else_block[0].clone().into_inner().collect::<Vec<_>>();
-                    block
-                        .iter()
-                        .filter(|p| matches!(p.as_rule(), Rule::Expr))
-                        .map(|p| create_ast(&p))
-                        .collect::<Vec<_>>()
-                }
-                _ => panic!(),
-            };
-
-            let condition = Box::new(condition[0].clone());
-            let if_block = Box::new(if_block);
-            let else_block = Box::new(else_block);
-            Node::IfExpr {
-                condition,
-                if_block,
-                else_block,
+            Node::IndexExpr {
+                array: Box::new(array),
+                indices: Box::new(indices),
+            }
+        }
+        Rule::Interpolation => {
+            let expr = create_ast(&pair.clone().into_inner().next().unwrap());
+            Node::Interpolation {
+                expr: Box::new(expr),
             }
         }
         Rule::MacroExpr => {
@@ -342,22 +442,54 @@ pub fn create_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
             };
             let body = parts
                 .iter()
-                .filter(|p| matches!(p.as_rule(), Rule::Expr))
+                .filter(|p| matches!(p.as_rule(), Rule::FunctionExpr))
                 .map(|p| create_ast(&p))
+                .filter(|x| *x != Node::Empty)
                 .collect::<Vec<_>>();
             let args = Box::new(args);
             let body = Box::new(body);
             Node::Macro { name, args, body }
         }
-        Rule::MethodCall => {
+        Rule::MacroCall => {
+            let parts = pair.clone().into_inner().collect::<Vec<_>>();
+            let name = parts[0].as_str()[1..].to_string();
+            let args = match parts.get(1) {
+                Some(x) => x
+                    .clone()
+                    .into_inner()
+                    .map(|x| create_ast(&x.into_inner().next().unwrap()))
+                    .collect(),
+                None => Vec::new(),
+            };
+            Node::MacroCall {
+                name,
+                args: Box::new(args),
+            }
+        }
+        // qualified calls such as Foo.bar(x) keep the dotted name
+        Rule::BroadcastCall | Rule::DotCall | Rule::MethodCall => {
             let params: Vec<_> = pair.clone().into_inner().collect();
-            let name = params[0].as_str().to_string();
-            let args: Vec<_> = params[1].clone().into_inner().collect();
-            let args: Vec<_> = args.into_iter().map(|x| create_ast(&x)).collect();
+            // name includes type parameters, e.g. Vector{Float64}
+            let name = params[..params.len() - 1]
+                .iter()
+                .map(|p| p.as_str())
+                .collect::<String>();
+            let args: Vec<_> = params[params.len() - 1].clone().into_inner().collect();
+            // call arguments are plain expressions rather than FunctionArgs
+            let args: Vec<_> = args
+                .into_iter()
+                .map(|x| create_ast(&x.into_inner().next().unwrap()))
+                .collect();
             let args = Box::new(args);
-            Node::MethodCall {
+            let call = Node::MethodCall {
                 name: name,
                 args: args,
+            };
+            match pair.as_rule() {
+                Rule::BroadcastCall => Node::Broadcast {
+                    expr: Box::new(call),
+                },
+                _ => call,
             }
         }
         Rule::Module => {
@@ -423,7 +555,7 @@ pub fn create_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
                 field_type: field_type,
             }
         }
-        Rule::StructType => {
+        Rule::StructType | Rule::MutableStructType => {
             let exprs: Vec<_> = pair.clone().into_inner().collect();
             let mut name = "HOWTFDIDTHISHAPPEN".to_string();
             let mut supertype = "Any".to_string();
@@ -450,14 +582,89 @@ pub fn create_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
                 name: name,
                 // generics: generics,
                 supertype: supertype,
+                is_mutable: pair.as_rule() == Rule::MutableStructType,
                 field_names: field_names,
                 field_types: field_types,
             }
         }
+        Rule::BeginExpr => Node::Block {
+            exprs: Box::new(create_block_ast(pair)),
+        },
+        // c ? a : b is an if
+        Rule::TernaryExpr => {
+            let parts = pair
+                .clone()
+                .into_inner()
+                .map(|x| create_ast(&x))
+                .collect::<Vec<_>>();
+            let [condition, a, b] = <[Node; 3]>::try_from(parts).unwrap();
+            Node::IfExpr {
+                condition: Box::new(condition),
+                if_block: Box::new(vec![a]),
+                else_block: Box::new(vec![b]),
+            }
+        }
+        Rule::LetExpr => {
+            let mut parts = pair.clone().into_inner();
+            let bindings = parts.next().unwrap().into_inner().map(|x| create_ast(&x));
+            let exprs = parts
+                .map(|x| create_ast(&x))
+                .filter(|x| *x != Node::Empty)
+                .collect::<Vec<_>>();
+            Node::Let {
+                bindings: Box::new(bindings.collect()),
+                exprs: Box::new(exprs),
+            }
+        }
+        // the code of quote ... end is a block
+        Rule::QuoteExpr => Node::Quote {
+            exprs: Box::new(vec![Node::Block {
+                exprs: Box::new(create_block_ast(pair)),
+            }]),
+        },
+        Rule::Symbol => create_quoted_symbol(pair.as_str()),
         Rule::SuperType => {
             let exprs: Vec<_> = pair.clone().into_inner().collect();
             Node::SuperType(exprs[0].as_str().to_string())
         }
+        Rule::TryExpr => {
+            let block = |p: &pest::iterators::Pair<Rule>| {
+                p.clone()
+                    .into_inner()
+                    .filter(|x| matches!(x.as_rule(), Rule::FunctionExpr))
+                    .map(|x| create_ast(&x))
+                    .collect::<Vec<_>>()
+            };
+            let mut try_block = Vec::new();
+            let mut catch_name = None;
+            let mut catch_block = None;
+            let mut finally_block = None;
+            for part in pair.clone().into_inner() {
+                match part.as_rule() {
+                    Rule::TryBlock => try_block = block(&part),
+                    Rule::CatchBlock => {
+                        catch_name = part
+                            .clone()
+                            .into_inner()
+                            .find(|x| matches!(x.as_rule(), Rule::IdentifierBase))
+                            .map(|x| x.as_str().to_string());
+                        catch_block = Some(Box::new(block(&part)));
+                    }
+                    Rule::FinallyBlock => finally_block = Some(Box::new(block(&part))),
+                    _ => panic!("This shouldn't happend"),
+                }
+            }
+            // a bare try swallows the exception like an empty catch
+            if catch_block.is_none() && finally_block.is_none() {
+                catch_block = Some(Box::new(vec![]));
+            }
+            Node::TryExpr {
+                try_block: Box::new(try_block),
+                catch_name,
+                catch_block,
+                finally_block,
+            }
+        }
         Rule::UnaryExpr => {
             let terms: Vec<_> = pair.clone().into_inner().collect();
             let op = match terms[0].as_str() {
@@ -471,28 +678,381 @@ pub fn create_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
                 child: Box::new(val),
             }
         }
+        Rule::UsingExpr => {
+            let (path, names) = create_import_parts(pair);
+            Node::UsingExpr { path, names }
+        }
         _ => todo!("todo {:?} {:?}", pair.as_rule(), pair),
     };
     ast
 }
 
+/// the module path and the optional list of names after the colon
+fn create_import_parts(pair: &pest::iterators::Pair<Rule>) -> (ModulePath, Vec<Symbol>) {
+    let mut path = ModulePath {
+        level: 0,
+        names: vec![],
+    };
+    let mut names = Vec::<Symbol>::new();
+    for part in pair.clone().into_inner() {
+        match part.as_rule() {
+            Rule::ModulePath => {
+                for p in part.into_inner() {
+                    match p.as_rule() {
+                        Rule::ModuleDots => path.level = p.as_str().len(),
+                        _ => path.names.push(p.as_str().to_string()),
+                    }
+                }
+            }
+            Rule::ImportNames => {
+                names = part.into_inner().map(|p| p.as_str().to_string()).collect();
+            }
+            _ => panic!("Unexpected {:?} in {:?}", part.as_rule(), pair.as_rule()),
+        }
+    }
+    (path, names)
+}
+
+fn precedence(op: Operator) -> u8 {
+    match op {
+        Operator::Equal
+        | Operator::EqualEqual
+        | Operator::Greater
+        | Operator::GreaterEqual
+        | Operator::Less
+        | Operator::LessEqual
+        | Operator::NotEqual
+        | Operator::SubType => 1,
+        Operator::Minus | Operator::Plus => 2,
+        Operator::Divide | Operator::Multiply => 3,
+    }
+}
+
+/// precedence climbing over a flat list of operands and operators,
+/// everything is left associative for now
+fn create_binary_ast(
+    mut lhs: Node,
+    operands: &mut impl Iterator<Item = Node>,
+    ops: &mut std::iter::Peekable<impl Iterator<Item = (Operator, bool)>>,
+    min_precedence: u8,
+) -> Node {
+    while let Some((op, is_dotted)) = ops.next_if(|(op, _)| precedence(*op) >= min_precedence) {
+        let mut rhs = operands.next().unwrap();
+        while ops
+            .peek()
+            .is_some_and(|(next, _)| precedence(*next) > precedence(op))
+        {
+            let next_precedence = precedence(ops.peek().unwrap().0);
+            rhs = create_binary_ast(rhs, operands, ops, next_precedence);
+        }
+        lhs = Node::BinaryExpr {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        if is_dotted {
+            lhs = Node::Broadcast {
+                expr: Box::new(lhs),
+            };
+        }
+    }
+    lhs
+}
+
 fn create_primitive_ast(pair: &pest::iterators::Pair<Rule>) -> Node {
     let prim: Vec<_> = pair.clone().into_inner().collect();
     let prim = match prim[0].as_rule() {
         Rule::Char => {
-            let c = prim[0].as_str().replace("'", "");
-            let c = c.parse::<char>().unwrap();
+            let raw = prim[0].as_str();
+            let mut chars = raw[1..raw.len() - 1].chars().peekable();
+            let c = match chars.next() {
+                Some('\\') => unescape(&mut chars),
+                Some(c) => c,
+                None => panic!("ParseError: empty character literal"),
+            };
+            assert!(
+                chars.next().is_none(),
+                "ParseError: character literal contains multiple characters {}",
+                raw
+            );
             Primitive::Char(c)
         }
-        Rule::Float => Primitive::Float64(pair.as_str().parse::<f64>().unwrap()),
-        Rule::Int => Primitive::Int64(pair.as_str().parse::<i64>().unwrap()),
-        Rule::String => Primitive::String(
-            pair.as_str()
-                .to_string()
-                .replace("\"", "")
-                .replace("\"", ""),
-        ),
+        Rule::Float => create_float_primitive(prim[0].as_str()),
+        Rule::Int => create_int_primitive(prim[0].as_str()),
+        Rule::Binary | Rule::Hex | Rule::Octal => create_unsigned_primitive(prim[0].as_str()),
+        Rule::String => {
+            let raw = prim[0].as_str();
+            return create_string_ast(&raw[1..raw.len() - 1]);
+        }
+        Rule::QuotedExpr => {
+            let expr = create_ast(&prim[0].clone().into_inner().next().unwrap());
+            return Node::Quote {
+                exprs: Box::new(vec![expr]),
+            };
+        }
+        Rule::Symbol => return create_quoted_symbol(prim[0].as_str()),
         _ => panic!("Unsupported primitive encountered in ast {:?}", pair),
     };
     Node::Primitive(prim)
 }
+
+/// the expressions of a block, leaving out comments and blank lines
+fn create_block_ast(pair: &pest::iterators::Pair<Rule>) -> Vec<Node> {
+    pair.clone()
+        .into_inner()
+        .map(|x| create_ast(&x))
+        .filter(|x| *x != Node::Empty)
+        .collect()
+}
+
+/// :x is the quoted symbol x
+fn create_quoted_symbol(raw: &str) -> Node {
+    Node::Quote {
+        exprs: Box::new(vec![Node::Symbol(raw[1..].to_string())]),
+    }
+}
+
+/// literals with an f exponent are Float32, everything else is Float64
+fn create_float_primitive(raw: &str) -> Primitive {
+    let digits = raw.replace('_', "");
+    let overflow = || panic!("ParseError: overflow in numeric constant {}", raw);
+    match digits.split_once('f') {
+        Some((mantissa, exponent)) => {
+            let x = format!("{}e{}", mantissa, exponent).parse::<f32>().unwrap();
+            if x.is_infinite() {
+                overflow()
+            }
+            Primitive::Float32(x)
+        }
+        None => {
+            let x = digits.parse::<f64>().unwrap();
+            if x.is_infinite() {
+                overflow()
+            }
+            Primitive::Float64(x)
+        }
+    }
+}
+
+/// decimal literals are Int64 unless they need to be Int128
+fn create_int_primitive(raw: &str) -> Primitive {
+    let digits = raw.replace('_', "");
+    if let Ok(x) = digits.parse::<i64>() {
+        Primitive::Int64(x)
+    } else if let Ok(x) = digits.parse::<i128>() {
+        Primitive::Int128(x)
+    } else {
+        panic!(
+            "ParseError: integer literal {} is too large, BigInt is not supported",
+            raw
+        )
+    }
+}
+
+/// 0x, 0b and 0o literals are unsigned and, like in Julia, sized by how
+/// many digits are written rather than by their value, so 0x01 is a UInt8
+/// and 0x0001 a UInt16
+fn create_unsigned_primitive(raw: &str) -> Primitive {
+    let (radix, bits_per_digit) = match &raw[..2] {
+        "0b" => (2, 1),
+        "0o" => (8, 3),
+        _ => (16, 4),
+    };
+    let digits = raw[2..].replace('_', "");
+    let value = u128::from_str_radix(&digits, radix)
+        .unwrap_or_else(|_| panic!("ParseError: unsigned literal {} is too large", raw));
+    let bits = match radix {
+        // octal is sized as if the leading digit were a 1
+        8 => {
+            let leading = (digits.len() as u32 - 1) * bits_per_digit + 1;
+            leading.max(128 - value.leading_zeros())
+        }
+        _ => digits.len() as u32 * bits_per_digit,
+    };
+    match bits {
+        0..=8 => Primitive::UInt8(value as u8),
+        9..=16 => Primitive::UInt16(value as u16),
+        17..=32 => Primitive::UInt32(value as u32),
+        33..=64 => Primitive::UInt64(value as u64),
+        65..=128 => Primitive::UInt128(value),
+        _ => panic!("ParseError: unsigned literal {} is too large", raw),
+    }
+}
+
+/// splits a string literal into its pieces, a literal without any
+/// interpolation is just a String primitive, otherwise it's lowered
+/// to a call like string("x = ", x)
+fn create_string_ast(raw: &str) -> Node {
+    let mut pieces = Vec::<Node>::new();
+    let mut current = String::new();
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.push(unescape(&mut chars)),
+            '$' => {
+                if !current.is_empty() {
+                    pieces.push(Node::Primitive(Primitive::String(current.clone())));
+                    current.clear();
+                }
+                let mut source = String::new();
+                if chars.peek() == Some(&'(') {
+                    let _ = chars.next();
+                    let mut depth = 1;
+                    for c in chars.by_ref() {
+                        match c {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            _ => {}
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                        source.push(c);
+                    }
+                    assert!(
+                        depth == 0,
+                        "ParseError: unbalanced $( in string \"{}\"",
+                        raw
+                    );
+                } else {
+                    while let Some(c) = chars.peek() {
+                        if !(c.is_alphanumeric() || *c == '_' || *c == '!') {
+                            break;
+                        }
+                        source.push(*c);
+                        let _ = chars.next();
+                    }
+                    assert!(
+                        !source.is_empty(),
+                        "ParseError: invalid interpolation syntax in string \"{}\"",
+                        raw
+                    );
+                }
+                pieces.push(create_interpolation_ast(&source));
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if pieces.is_empty() {
+        return Node::Primitive(Primitive::String(current));
+    }
+    if !current.is_empty() {
+        pieces.push(Node::Primitive(Primitive::String(current)));
+    }
+    Node::MethodCall {
+        name: "string".to_string(),
+        args: Box::new(pieces),
+    }
+}
+
+/// parses the expression inside of $(...)
+fn create_interpolation_ast(source: &str) -> Node {
+    let source = source.trim();
+    let arg = FarneseParser::parse(Rule::FunctionArg, source)
+        .unwrap_or_else(|e| panic!("ParseError: invalid interpolation $({})\n{}", source, e))
+        .next()
+        .unwrap();
+    assert!(
+        arg.as_str().trim_end_matches([',', ' ']) == source,
+        "ParseError: invalid interpolation $({})",
+        source
+    );
+    create_ast(&arg.into_inner().next().unwrap())
+}
+
+/// handles the character(s) after a backslash
+fn unescape(chars: &mut std::iter::Peekable<std::str::Chars>) -> char {
+    let c = chars.next().expect("ParseError: string ends with \\");
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        'a' => '\u{07}',
+        'b' => '\u{08}',
+        'e' => '\u{1b}',
+        'f' => '\u{0c}',
+        'v' => '\u{0b}',
+        '\\' | '"' | '\'' | '$' => c,
+        'x' | 'u' | 'U' => {
+            let max_digits = match c {
+                'x' => 2,
+                'u' => 4,
+                _ => 8,
+            };
+            // \u{...} is also accepted
+            let braced = c != 'x' && chars.peek() == Some(&'{');
+            if braced {
+                let _ = chars.next();
+            }
+            let mut digits = String::new();
+            while let Some(d) = chars.peek() {
+                if !d.is_ascii_hexdigit() || (!braced && digits.len() == max_digits) {
+                    break;
+                }
+                digits.push(*d);
+                let _ = chars.next();
+            }
+            if braced {
+                assert!(
+                    chars.next() == Some('}'),
+                    "ParseError: unterminated \\{}{{ escape",
+                    c
+                );
+            }
+            let code = u32::from_str_radix(&digits, 16)
+                .unwrap_or_else(|_| panic!("ParseError: invalid \\{} escape", c));
+            char::from_u32(code)
+                .unwrap_or_else(|| panic!("ParseError: invalid unicode code point {:#x}", code))
+        }
+        _ => panic!("ParseError: invalid escape sequence \\{}", c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_macros() {
+        let ast = parse_source("export @twice, f\nusing .M: @twice, f\n").unwrap();
+        let names = vec!["@twice".to_string(), "f".to_string()];
+        assert_eq!(
+            Node::Exports {
+                symbols: Box::new(names.iter().map(|x| Node::Symbol(x.clone())).collect()),
+            },
+            ast[0]
+        );
+        assert_eq!(
+            Node::UsingExpr {
+                path: ModulePath {
+                    level: 1,
+                    names: vec!["M".to_string()],
+                },
+                names,
+            },
+            ast[1]
+        );
+    }
+
+    #[test]
+    fn test_interpolated_assignment() {
+        let ast = parse_source("quote\n  $v = 1\nend\n").unwrap();
+        let assignment = Node::InterpolatedAssignment {
+            target: Box::new(Node::Interpolation {
+                expr: Box::new(Node::Symbol("v".to_string())),
+            }),
+            datatype: None,
+            value: Box::new(Node::Primitive(Primitive::Int64(1))),
+        };
+        assert_eq!(
+            Node::Quote {
+                exprs: Box::new(vec![Node::Block {
+                    exprs: Box::new(vec![assignment]),
+                }]),
+            },
+            ast[0]
+        );
+    }
+}
@@ -17,13 +17,10 @@ pub struct ReplError;
 
 impl<'a> Repl<'a> {
     pub fn new(context: &'a Context) -> Self {
-        let mut compiler = Compiler::new(context);
+        let compiler = Compiler::new(context);
         let editor = DefaultEditor::new().unwrap();
-        let mut main_module = Module::new(context, "Main");
-
-        compiler.include(&mut main_module, "examples/base.jl");
-        main_module.link(compiler.get_module("Core"));
-        // main_module.link(compiler.get_module("Base"));
+        let mut main_module = compiler.create_module("Main");
+        compiler.link_dependencies(&mut main_module).unwrap();
 
         Self {
             compiler: compiler,
@@ -1,20 +1,10 @@
 module Base
 
-export 
-  # very basic stuff
-  Any, DataType, Symbol
-  # other stuff
+# the concrete types (Any, Float64, Int64, Char, String, ...) are
+# defined in Core, Base fills in the abstract number hierarchy
+export
   Number, Real, AbstractFloat,
-  Integer, Signed, Unsigned,
-  Float64,
-  AbstractChar, Char,
-  Int64,
-  Nothing
-
-# abstract type Any <: Any end
-# abstract type Type{T} end
-# abstract type Vararg{T} end
-
+  Integer, Signed, Unsigned
 
 abstract type Number end
 abstract type Real     <: Number end
@@ -23,29 +13,4 @@ abstract type Integer  <: Real end
 abstract type Signed   <: Integer end
 abstract type Unsigned <: Integer end
 
-# primitive type Float16 <: AbstractFloat 16 end
-# primitive type Float32 <: AbstractFloat 32 end
-primitive type Float64 <: AbstractFloat 64 end
-
-# primitive type BFloat16 <: AbstractFloat 16 end
-
-#primitive type Bool <: Integer 8 end
-abstract type AbstractChar end
-primitive type Char <: AbstractChar 32 end
-
-# primitive type Int8    <: Signed   8 end
-# primitive type UInt8   <: Unsigned 8 end
-# primitive type Int16   <: Signed   16 end
-# primitive type UInt16  <: Unsigned 16 end
-# primitive type Int32   <: Signed   32 end
-# primitive type UInt32  <: Unsigned 32 end
-primitive type Int64   <: Signed   64 end
-# primitive type UInt64  <: Unsigned 64 end
-# primitive type Int128  <: Signed   128 end
-# primitive type UInt128 <: Unsigned 128 end
-
-struct Nothing
-end
-
-
 end # module
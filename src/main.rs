@@ -1,6 +1,5 @@
 use clap::{Parser, Subcommand};
 use farnese_compiler::Compiler;
-use farnese_core::Module;
 use farnese_lexer::lexer;
 #[cfg(feature = "repl")]
 use farnese_repl::Repl;
@@ -57,6 +56,8 @@ fn setup_pass_manager<T: PassManagerSubType<Input = ()>>() -> PassManager<T> {
     pm.add_reassociate_pass();
     pm.add_gvn_pass();
     pm.add_cfg_simplification_pass();
+    // functions marked @inline
+    pm.add_always_inliner_pass();
     pm.add_basic_alias_analysis_pass();
     pm.add_promote_memory_to_register_pass();
     pm.add_dead_store_elimination_pass();
@@ -107,13 +108,13 @@ fn main() {
         }) => {
             let context = Context::create();
 
+            // Core and Base are set up by the compiler
             let mut compiler = Compiler::new(&context);
-            // let mut base_module = Module::new(&context, "Base");
-            // compiler.insert_module("Base", base_module.clone());
-            // compiler.include(&mut base_module, "src/base/base.jl");
-            let mut main_module = Module::new(&context, "Main");
+            let mut main_module = compiler.create_module("Main");
             compiler.insert_module("Main", main_module.clone());
             compiler.include(&mut main_module, &input);
+            compiler.link_dependencies(&mut main_module).unwrap_or_else(|e| panic!("{}", e));
+            compiler.emit_initializers(&mut main_module);
 
             // match optimization_level
             let optimization_level = match optimization_level {
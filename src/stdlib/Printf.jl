@@ -0,0 +1,7 @@
+module Printf
+
+# @printf and @sprintf are lowered by the compiler, which checks the
+# format string against the types of the arguments
+export @printf, @sprintf
+
+end # module